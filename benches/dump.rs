@@ -0,0 +1,19 @@
+//! Measures dumping large sessions, of a million statements unless given another number,
+//! e.g., `cargo bench --bench dump -- 100000`.
+
+use justact_pdx::Engine;
+use std::{io::BufWriter, time::Instant};
+
+fn main() {
+    let n: usize = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(1_000_000);
+    let mut engine = Engine::new();
+    let start = Instant::now();
+    for i in 0..n {
+        let command = format!("say agent{} \"fact({}).\"", i % 16, i);
+        engine.apply_line(&command).unwrap_or_else(|e| panic!("{:?} failed: {}", command, e));
+    }
+    println!("Said {} statements in {:?}", n, start.elapsed());
+    let start = Instant::now();
+    engine.write_trace(BufWriter::new(std::io::sink())).expect("writing to a sink cannot fail");
+    println!("Dumped {} statements in {:?}", n, start.elapsed());
+}
@@ -0,0 +1,15 @@
+fn main() {
+    // Generates the C header of the `ffi` module
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        cbindgen::Builder::new()
+            .with_crate(&crate_dir)
+            .with_language(cbindgen::Language::C)
+            .with_include_guard("JUSTACT_PDX_H")
+            .generate()
+            .expect("cannot generate C header")
+            .write_to_file(std::path::Path::new(&crate_dir).join("include/justact_pdx.h"));
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{datalog, policy::Verdict, AgreeIdx, Config, ResourceInterpreter, StmtIdx, UpdateCmd};
+use justact_prototype::wire::{Agreement, Message};
+use std::collections::BTreeSet;
+
+/// A validity condition of an enacted action, as checked for it.
+pub(crate) struct Check {
+    /// What holds, or what fails, as `explain` describes it.
+    pub description: String,
+    /// Why the action is invalid, as audit reports it, and how to make the condition hold,
+    /// if it fails.
+    pub failure: Option<(String, Fix)>,
+}
+
+/// The smallest change that makes a failing validity condition hold.
+pub(crate) enum Fix {
+    Change(String),
+    /// Leaving out the fewest statements of a justification that the policy engine
+    /// forbids, which only `explain` searches for, as it is costly.
+    LeaveOut,
+}
+
+impl Check {
+    fn holds(description: String) -> Self {
+        Self { description, failure: None }
+    }
+
+    fn fails(description: String, violation: String, fix: Fix) -> Self {
+        Self { description, failure: Some((violation, fix)) }
+    }
+}
+
+impl Config {
+    /// Checks the validity conditions of the given enacted action, in order,
+    /// returning a description of the first one that is violated, if any.
+    pub fn violation(&self, idx: usize) -> Option<String> {
+        self.checks(idx).find_map(|check| check.failure).map(|(violation, _)| violation)
+    }
+
+    /// The validity conditions of the given enacted action, in the order that audit checks
+    /// them, checked lazily such that audit stops at the first that fails. Supersession of
+    /// the basis is not one, as audit only warns about it.
+    pub(crate) fn checks(&self, idx: usize) -> impl Iterator<Item = Check> + '_ {
+        let conditions: [fn(&Self, usize) -> Vec<Check>; 5] = [
+            Self::check_basis_applies,
+            Self::check_justification_stated,
+            Self::check_basis_included,
+            Self::check_justification_closed,
+            Self::check_policy,
+        ];
+        conditions.into_iter().flat_map(move |check| check(self, idx))
+    }
+
+    fn check_basis_applies(&self, idx: usize) -> Vec<Check> {
+        let action = &self.enacted[idx];
+        let (basis, from, at) = (self.basis_idx(idx), action.basis.at, self.enacted_at[idx]);
+        let (applies, window, violation) = match self.expires_at.get(&basis) {
+            Some(&until) => (
+                (from..=until).contains(&at),
+                format!("from {} until {}", from, until),
+                format!(
+                    "basis agreement of times {} until {} did not apply at enactment time {}",
+                    from, until, at
+                ),
+            ),
+            None => (
+                from == at,
+                format!("at {}", from),
+                format!(
+                    "basis agreement of time {} was not current at enactment time {}",
+                    from, at
+                ),
+            ),
+        };
+        if applies {
+            return vec![Check::holds(format!(
+                "agreement {} applies at enactment time {}",
+                basis, at
+            ))];
+        }
+        let fix = if at < from {
+            format!("the action must be enacted at or after {}", from)
+        } else {
+            let on = self.stmt_idx(&action.basis.message);
+            let on = on.map_or("its statement".to_string(), |s| format!("statement {}", s));
+            format!(
+                "the action must be based on an agreement that applies at {}, e.g., a new \
+                 one on {} until {}, or an amendment of agreement {} that does",
+                at, on, at, basis
+            )
+        };
+        let description =
+            format!("agreement {} applies {}, not at enactment time {}", basis, window, at);
+        vec![Check::fails(description, violation, Fix::Change(fix))]
+    }
+
+    /// Whether each statement of the justification was stated, is not retracted, and is
+    /// trusted by the actor, in the order they were stated.
+    fn check_justification_stated(&self, idx: usize) -> Vec<Check> {
+        let action = &self.enacted[idx];
+        let (actor, at) = (&action.id.0, self.enacted_at[idx]);
+        let mut justification: Vec<_> =
+            action.justification.iter().map(|m| (self.stmt_idx(m), m)).collect();
+        justification.sort_by_key(|(i, _)| *i);
+        (justification.into_iter())
+            .map(|(i, m)| match i {
+                None => {
+                    let description = format!("justification message {:?} was never stated", m.id);
+                    let fix =
+                        format!("{} must state {:?} at or before t={}", m.id.0, m.payload, at);
+                    Check::fails(description.clone(), description, Fix::Change(fix))
+                }
+                Some(i) if self.retracted.contains(&i) => {
+                    let description = format!("justification statement {} was retracted", i);
+                    let fix = format!("statement {} must not be retracted, or left out", i);
+                    Check::fails(description.clone(), description, Fix::Change(fix))
+                }
+                Some(i) if m.id != action.basis.message.id && !self.trusts(actor, &m.id.0) => {
+                    Check::fails(
+                        format!("justification statement {} is said by {}, untrusted", i, m.id.0),
+                        format!(
+                            "justification statement {} is said by {}, whom {} does not trust",
+                            i, m.id.0, actor
+                        ),
+                        Fix::Change(format!(
+                            "{} must trust {}, or leave statement {} out",
+                            actor, m.id.0, i
+                        )),
+                    )
+                }
+                Some(i) => Check::holds(format!(
+                    "justification statement {} by {} was stated at {}, and is trusted",
+                    i, m.id.0, self.stated_at[i]
+                )),
+            })
+            .collect()
+    }
+
+    fn check_basis_included(&self, idx: usize) -> Vec<Check> {
+        let action = &self.enacted[idx];
+        let on = self.stmt_idx(&action.basis.message);
+        let on =
+            on.map_or("the statement of the basis".to_string(), |s| format!("statement {}", s));
+        vec![match action.justification.iter().any(|m| m.id == action.basis.message.id) {
+            true => Check::holds(format!("the justification includes {}", on)),
+            false => Check::fails(
+                format!("the justification does not include {}", on),
+                "justification does not include the basis agreement".into(),
+                Fix::Change(format!("{} must be added to the justification", on)),
+            ),
+        }]
+    }
+
+    fn check_justification_closed(&self, idx: usize) -> Vec<Check> {
+        vec![match self.unclosed_justification(idx) {
+            None => Check::holds(
+                "the justification is closed, including every statement defining what it uses"
+                    .to_string(),
+            ),
+            Some((i, pred)) => Check::fails(
+                format!("the justification uses {}, which statement {} defines", pred, i),
+                format!(
+                    "justification is not closed: it uses {}, which statement {} defines",
+                    pred, i
+                ),
+                Fix::Change(format!("statement {} must be added to the justification", i)),
+            ),
+        }]
+    }
+
+    fn check_policy(&self, idx: usize) -> Vec<Check> {
+        let Some(policy) = &self.policy else { return vec![] };
+        let payloads: Vec<&str> =
+            self.enacted[idx].justification.iter().map(|m| &*m.payload).collect();
+        vec![match policy.check(&payloads) {
+            Ok(Verdict::Permitted) => {
+                Check::holds(format!("{} permits the justification", policy.name()))
+            }
+            Ok(Verdict::Forbidden(why)) => Check::fails(
+                format!("{} forbids the justification: {}", policy.name(), why),
+                format!("justification is forbidden by {}: {}", policy.name(), why),
+                Fix::LeaveOut,
+            ),
+            Err(e) => Check::fails(
+                format!("{} cannot evaluate the justification: {}", policy.name(), e),
+                format!("{} cannot evaluate justification: {}", policy.name(), e),
+                Fix::Change(format!("the justification must be written for {}", policy.name())),
+            ),
+        }]
+    }
+
+    /// The first statement that the justification of the given enacted action leaves out,
+    /// though it must include it to be closed, with the predicate it defines. It must include
+    /// each statement that its actor received, trusted and did not see retracted by
+    /// enactment time, and that defines a predicate that a rule of it uses, or `error`.
+    /// Payloads that are not Datalog define and use nothing.
+    pub fn unclosed_justification(&self, idx: usize) -> Option<(StmtIdx, String)> {
+        let action = &self.enacted[idx];
+        let (actor, at) = (&action.id.0, self.enacted_at[idx]);
+        let mut used = BTreeSet::from(["error".to_string()]);
+        for m in action.justification.iter() {
+            for rule in datalog::parse(&m.payload).unwrap_or_default() {
+                used.extend(rule.body.into_iter().map(|atom| atom.pred));
+            }
+        }
+        let included: BTreeSet<StmtIdx> =
+            action.justification.iter().filter_map(|m| self.stmt_idx(m)).collect();
+        (0..self.statements.len())
+            .take_while(|&i| self.stated_at[i] <= at)
+            .filter(|i| !included.contains(i) && !self.retracted.contains(i))
+            .filter(|&i| {
+                let sayer = &self.statements[i].id.0;
+                (sayer == actor || self.received_at(actor, i).is_some_and(|r| r <= at))
+                    && self.trusts(actor, sayer)
+            })
+            // Parses only the payloads that mention a used predicate at all
+            .filter(|&i| used.iter().any(|pred| self.statements[i].payload.contains(&**pred)))
+            .find_map(|i| {
+                let rules = datalog::parse(&self.statements[i].payload).ok()?;
+                let pred = rules.into_iter().map(|r| r.head.pred).find(|p| used.contains(p))?;
+                Some((i, pred))
+            })
+    }
+
+    /// Why enacting the action would be invalid at the current time, if so, as audit would
+    /// find right after, without enacting it. Fails if the action cannot be enacted at all,
+    /// e.g., as its effect does not apply.
+    pub(crate) fn enactment_violation(
+        &mut self,
+        enact: &UpdateCmd,
+    ) -> Result<Option<String>, String> {
+        let UpdateCmd::Enact { actor, basis, justification, effect } = enact else {
+            return Ok(None);
+        };
+        let (action, basis) = self.new_action(actor, basis, justification)?;
+        if let Some(effect) = effect {
+            let interpreter = self.interpreter.as_deref().unwrap_or(&ResourceInterpreter);
+            (interpreter.apply(&mut self.resources.clone(), actor, effect))
+                .map_err(|e| format!("Cannot apply effect {:?}: {}", effect, e))?;
+        }
+        // Audits the action as if it were enacted, leaving the index, which audit does not use
+        self.enacted.push(action);
+        self.enacted_at.push(self.current);
+        self.enacted_basis.push(basis);
+        let violation = self.violation(self.enacted.len() - 1);
+        self.enacted.pop();
+        self.enacted_at.pop();
+        self.enacted_basis.pop();
+        Ok(violation)
+    }
+
+    /// Prints whether enacting the action would be valid at the current time, without
+    /// enacting it.
+    pub fn check_enact(&mut self, enact: &UpdateCmd) -> Result<(), String> {
+        match self.enactment_violation(enact)? {
+            None => println!("The action would be valid at {}", self.current),
+            Some(why) => println!("The action would be INVALID at {}: {}", self.current, why),
+        }
+        if let UpdateCmd::Enact { basis, .. } = enact
+            && let Some(why) = self.not_current(basis.resolve(&self.agree_labels)?)
+        {
+            println!("Warning: its basis agreement is not current: {}", why);
+        }
+        Ok(())
+    }
+
+    /// The agreement that superseded the basis of the given enacted action at or before its
+    /// enactment time, if any.
+    pub fn superseded_basis(&self, idx: usize) -> Option<AgreeIdx> {
+        let basis = self.enacted_basis[idx];
+        let at = self.enacted_at[idx];
+        self.supersession_chain(basis)
+            .into_iter()
+            .take_while(|&by| self.agreements[by].at <= at)
+            .last()
+    }
+
+    /// Prints a verdict for each enacted action.
+    pub fn audit(&self) {
+        if self.enacted.is_empty() {
+            println!("No actions to audit");
+            return;
+        }
+        println!("___act.id__|___actor___|___verdict___ AUDIT");
+        for (i, e) in self.enacted.iter().enumerate() {
+            match self.violation(i) {
+                None => println!("{: >10} | {: <9} | valid", i, e.id.0),
+                Some(violation) => println!("{: >10} | {: <9} | INVALID: {}", i, e.id.0, violation),
+            }
+        }
+        for i in 0..self.enacted.len() {
+            if let Some(by) = self.superseded_basis(i) {
+                println!(
+                    "Warning: the basis of action {} was superseded by agreement {} at time {}",
+                    i, by, self.enacted_at[i]
+                );
+            }
+        }
+        self.audit_reads();
+    }
+
+    /// Reports the violations of the actions enacted since there were `actions`, and of the
+    /// earlier ones that the updates journaled since there were `journaled` may invalidate,
+    /// i.e., retractions of their justification, trust declarations of their actors, and
+    /// amendments of their basis, which audit warns about.
+    pub(crate) fn audit_updates(&self, journaled: usize, actions: usize) {
+        let actions = actions.min(self.enacted.len());
+        let mut affected: BTreeSet<usize> = (actions..self.enacted.len()).collect();
+        for update_cmd in self.journal.iter().skip(journaled) {
+            match update_cmd {
+                UpdateCmd::Retract { stmt, .. } => {
+                    let Ok(stmt) = stmt.resolve(&self.stmt_labels) else { continue };
+                    affected.extend(self.actions_using(&self.statements[stmt]));
+                }
+                UpdateCmd::Trust { truster, .. } => {
+                    affected.extend((0..actions).filter(|&i| self.enacted[i].id.0 == *truster));
+                }
+                UpdateCmd::Amend { supersedes, .. } => {
+                    let Ok(agreement) = supersedes.resolve(&self.agree_labels) else { continue };
+                    let on = &self.agreements[agreement].message;
+                    affected.extend(
+                        self.actions_using(on)
+                            .iter()
+                            .copied()
+                            .filter(|&i| i < actions && self.enacted_basis[i] == agreement),
+                    );
+                }
+                _ => {}
+            }
+        }
+        for i in affected {
+            if let Some(violation) = self.violation(i) {
+                println!(
+                    "Violation: action {} by {} is invalid: {}",
+                    i, self.enacted[i].id.0, violation
+                );
+            }
+            if let Some(by) = self.superseded_basis(i) {
+                println!(
+                    "Warning: the basis of action {} was superseded by agreement {} at time {}",
+                    i, by, self.enacted_at[i]
+                );
+            }
+        }
+    }
+
+    /// The index of the statement of the given message, if any.
+    pub fn stmt_idx(&self, msg: &Message) -> Option<StmtIdx> {
+        self.stmt_idx_by_id(&msg.id)
+    }
+
+    /// The index of the first agreement equal to the given one, if any. Agreements on the
+    /// same statement at the same time are equal, so an enacted action's basis is found by
+    /// [`Config::basis_idx`] instead.
+    pub fn agree_idx(&self, agree: &Agreement) -> Option<AgreeIdx> {
+        (self.agreements_on(&agree.message).iter().copied())
+            .find(|&i| self.agreements[i].at == agree.at)
+    }
+
+    /// The index of the agreement on which the given enacted action is based.
+    pub fn basis_idx(&self, idx: usize) -> AgreeIdx {
+        self.enacted_basis[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fix;
+    use crate::config;
+
+    #[test]
+    fn reports_the_first_failing_check_as_the_violation() {
+        let config = config(&[
+            "say amy a.",
+            "agree 0 0 until 2",
+            "say cho b.",
+            "trust bob amy",
+            "now 3",
+            "enact bob 0 0 1",
+        ]);
+        let checks: Vec<_> = config.checks(0).collect();
+        let failures: Vec<_> = checks.iter().filter_map(|c| c.failure.as_ref()).collect();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(config.violation(0).as_ref(), Some(&failures[0].0));
+        let Fix::Change(fix) = &failures[0].1 else { panic!("no change to the basis") };
+        assert!(fix.contains("an amendment of agreement 0"), "{}", fix);
+        assert!(failures[1].0.contains("whom bob does not trust"));
+    }
+
+    #[test]
+    fn keys_bases_by_index_among_equal_agreements() {
+        let config =
+            config(&["say amy a.", "agree 0 0", "agree 0 0 until 5", "now 3", "enact bob 1 0"]);
+        assert_eq!(config.basis_idx(0), 1);
+        assert_eq!(config.violation(0), None);
+    }
+
+    #[test]
+    fn requires_justifications_to_include_the_definitions_they_use() {
+        let mut config = config(&[
+            "say amy \"allowed :- member(X).\"",
+            "agree 0 0",
+            "say bob \"member(bob).\"",
+            "say cho \"member(cho).\"",
+            "say dan unrelated.",
+            "enact bob 0 0 1",
+            "enact bob 0 0 1 2",
+        ]);
+        assert_eq!(config.unclosed_justification(0), Some((2, "member".to_string())));
+        assert_eq!(config.violation(1), None);
+
+        let cmd = config.parse("retract cho 2").unwrap();
+        config.execute(cmd).unwrap();
+        assert_eq!(config.unclosed_justification(0), None);
+    }
+
+    #[test]
+    fn audits_nothing_incrementally_after_the_journal_is_replaced() {
+        let mut config = config(&[
+            "set audit continuous",
+            "say amy a.",
+            "agree 0 0",
+            "say amy b.",
+            "say amy c.",
+            "trust bob amy",
+            "checkpoint before",
+            "undo",
+            "undo",
+            "undo",
+            "enact bob 0 0",
+            "enact bob 0 0",
+        ]);
+        config.run("rollback before");
+        assert!(config.enacted.is_empty());
+        assert_eq!(config.journal.len(), 5);
+    }
+
+    #[test]
+    fn requires_only_trusted_received_statements_stated_by_enactment() {
+        let config = config(&[
+            "say amy \"error :- late.\"",
+            "agree 0 0 until 5",
+            "say cho \"error :- early.\"",
+            "trust bob amy",
+            "enact bob 0 0",
+            "now 1",
+            "say amy \"late :- error.\"",
+        ]);
+        assert_eq!(config.unclosed_justification(0), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! Agents run by scripts of rules, which react to the statements, agreements and actions
+//! made by others, such that not every role has to be played by hand.
+//!
+//! Each line of a script is a rule of the form
+//! `on <say|agree|enact> [by <agent>] [matching <regex>] [with <p>] do <command>(; <command>)*`,
+//! whose commands are executed whenever a matching statement is said, agreement is made,
+//! or action is enacted, or of the form `on step [with <p>] do ...`, executed every `step`
+//! of a simulation. Rules with a probability `p` only fire with that probability. Within them, `$self` is the name of the agent, `$by` the sayer
+//! or actor, `$stmt` the statement said or agreed on, `$agreement` the agreement made or
+//! acted on, and `$action` the action enacted. Lines starting with `#` are comments.
+
+use crate::{rng::Rng, tokens, tokens::Tokens, Config, Flow};
+use regex::Regex;
+use std::path::Path;
+
+/// At most this many reactions follow a single command, as reactions may trigger others.
+const MAX_REACTIONS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    Say,
+    Agree,
+    Enact,
+    Step,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    trigger: Trigger,
+    by: Option<String>,
+    matching: Option<Regex>,
+    probability: f64,
+    commands: Vec<String>,
+}
+
+/// The rules by which an agent acts, as read from a script.
+#[derive(Debug, Clone)]
+pub struct Behavior {
+    script: String,
+    rules: Vec<Rule>,
+}
+
+/// A statement, agreement or action that agents may react to.
+struct Occurrence {
+    trigger: Trigger,
+    by: String,
+    payloads: Vec<String>,
+    bindings: Vec<(&'static str, usize)>,
+}
+
+/// How much of the state the agents have seen, and thus reacted to.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Seen {
+    statements: usize,
+    agreements: usize,
+    actions: usize,
+}
+
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let mut tokens = Tokens::new(line);
+    let mut next = |what: &str| tokens.next().ok_or(format!("expected {}", what));
+    if next("on")? != "on" {
+        return Err("expected a rule starting with on".to_string());
+    }
+    let trigger = match &*next("say, agree, enact or step")? {
+        "say" => Trigger::Say,
+        "agree" => Trigger::Agree,
+        "enact" => Trigger::Enact,
+        "step" => Trigger::Step,
+        other => return Err(format!("expected say, agree, enact or step, got {:?}", other)),
+    };
+    let (mut by, mut matching, mut probability) = (None, None, None);
+    loop {
+        match &*next("by, matching, with or do")? {
+            "by" if by.is_none() && trigger != Trigger::Step => {
+                by = Some(next("an agent")?.into_owned())
+            }
+            "matching" if matching.is_none() && trigger != Trigger::Step => {
+                let pattern = next("a pattern")?;
+                matching = Some(Regex::new(&pattern).map_err(|e| e.to_string())?);
+            }
+            "with" if probability.is_none() => {
+                let p = next("a probability")?;
+                probability =
+                    Some(p.parse().ok().filter(|p| (0.0..=1.0).contains(p)).ok_or_else(|| {
+                        format!("expected a probability between 0 and 1, got {:?}", p)
+                    })?);
+            }
+            "do" => break,
+            other => return Err(format!("expected by, matching, with or do, got {:?}", other)),
+        }
+    }
+    let probability = probability.unwrap_or(1.0);
+    let commands: Vec<String> = tokens::split_commands(tokens.rest()).map(String::from).collect();
+    if commands.is_empty() {
+        return Err("expected a command after do".to_string());
+    }
+    Ok(Rule { trigger, by, matching, probability, commands })
+}
+
+impl Behavior {
+    fn parse(script: &str, text: &str) -> Result<Self, String> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !tokens::is_comment(line))
+            .map(|(i, line)| parse_rule(line).map_err(|e| format!("{}:{}: {}", script, i + 1, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { script: script.to_string(), rules })
+    }
+}
+
+impl Rule {
+    fn matches(&self, occurrence: &Occurrence) -> bool {
+        self.trigger == occurrence.trigger
+            && self.by.as_ref().is_none_or(|by| *by == occurrence.by)
+            && self
+                .matching
+                .as_ref()
+                .is_none_or(|r| occurrence.payloads.iter().any(|p| r.is_match(p)))
+    }
+
+    /// The commands of the rule, with the variables of the agent and occurrence replaced.
+    fn commands(&self, agent: &str, occurrence: &Occurrence) -> Vec<String> {
+        let value = |name: &str| match name {
+            "self" => Some(tokens::quote(agent).into_owned()),
+            "by" => Some(tokens::quote(&occurrence.by).into_owned()),
+            name => {
+                occurrence.bindings.iter().find(|(n, _)| *n == name).map(|(_, i)| i.to_string())
+            }
+        };
+        let mut commands = vec![];
+        for command in &self.commands {
+            let (mut substituted, mut end) = (String::new(), 0);
+            for (idx, _) in command.match_indices('$') {
+                if let Some(name) = tokens::variable_at(command, idx)
+                    && let Some(value) = value(name)
+                {
+                    substituted.push_str(&command[end..idx]);
+                    substituted.push_str(&value);
+                    end = idx + 1 + name.len();
+                }
+            }
+            substituted.push_str(&command[end..]);
+            commands.push(substituted);
+        }
+        commands
+    }
+}
+
+impl Config {
+    pub fn run_agent(&mut self, name: String, path: &str) -> Result<(), String> {
+        let text = std::fs::read_to_string(self.relative_path(Path::new(path)))
+            .map_err(|e| format!("Cannot read {:?}: {}", path, e))?;
+        let behavior = Behavior::parse(path, &text)?;
+        if self.behaviors.is_empty() {
+            // Agents react to what happens from now on only
+            self.seen = self.seen_now();
+        }
+        self.behaviors.insert(name, behavior);
+        Ok(())
+    }
+
+    pub fn stop_agent(&mut self, name: &str) -> Result<(), String> {
+        self.behaviors.remove(name).map(|_| ()).ok_or(format!("Agent {:?} is not running", name))
+    }
+
+    pub fn list_behaviors(&self) {
+        for (name, behavior) in &self.behaviors {
+            println!("{} runs {} ({} rules)", name, behavior.script, behavior.rules.len());
+        }
+    }
+
+    fn seen_now(&self) -> Seen {
+        Seen {
+            statements: self.statements.len(),
+            agreements: self.agreements.len(),
+            actions: self.enacted.len(),
+        }
+    }
+
+    /// The statements, agreements and actions made since the agents last reacted.
+    fn occurrences(&self) -> Vec<Occurrence> {
+        let mut occurrences = vec![];
+        // Undoing may have removed what the agents have seen
+        for i in self.seen.statements.min(self.statements.len())..self.statements.len() {
+            let s = &self.statements[i];
+            occurrences.push(Occurrence {
+                trigger: Trigger::Say,
+                by: s.id.0.clone(),
+                payloads: vec![s.payload.clone()],
+                bindings: vec![("stmt", i)],
+            });
+        }
+        for i in self.seen.agreements.min(self.agreements.len())..self.agreements.len() {
+            let message = &self.agreements[i].message;
+            let mut bindings = vec![("agreement", i)];
+            bindings.extend(self.stmt_idx(message).map(|stmt| ("stmt", stmt)));
+            occurrences.push(Occurrence {
+                trigger: Trigger::Agree,
+                by: message.id.0.clone(),
+                payloads: vec![message.payload.clone()],
+                bindings,
+            });
+        }
+        for i in self.seen.actions.min(self.enacted.len())..self.enacted.len() {
+            let e = &self.enacted[i];
+            let bindings = vec![("action", i), ("agreement", self.basis_idx(i))];
+            occurrences.push(Occurrence {
+                trigger: Trigger::Enact,
+                by: e.id.0.clone(),
+                payloads: e.justification.iter().map(|m| m.payload.clone()).collect(),
+                bindings,
+            });
+        }
+        occurrences
+    }
+
+    /// Advances the time by one, `n` times, each time letting the running agents react to
+    /// the step, and whatever follows from that. Rules fire by chance as decided by the
+    /// given seed, if any.
+    pub fn step(&mut self, n: u64, seed: Option<u64>) -> Result<Flow, String> {
+        if self.behaviors.is_empty() {
+            return Err("No agents are running to step, see agent run".into());
+        }
+        if let Some(seed) = seed {
+            self.rng = Rng::seeded(seed);
+        }
+        for _ in 0..n {
+            self.advance(self.current.checked_add(1).ok_or("Time cannot advance that far")?)?;
+            let step = Occurrence {
+                trigger: Trigger::Step,
+                by: String::new(),
+                payloads: vec![],
+                bindings: vec![],
+            };
+            if let Flow::Quit = self.react_to(vec![step]) {
+                return Ok(Flow::Quit);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Lets the running agents react to what happened since they last did, including to
+    /// each other's reactions, up to a limit.
+    pub fn react(&mut self) -> Flow {
+        self.react_to(vec![])
+    }
+
+    /// Lets the running agents react to the given occurrences, and then to what happened
+    /// since they last did.
+    fn react_to(&mut self, mut occurrences: Vec<Occurrence>) -> Flow {
+        let mut reactions = 0;
+        loop {
+            occurrences.extend(self.occurrences());
+            self.seen = self.seen_now();
+            if occurrences.is_empty() || self.behaviors.is_empty() {
+                return Flow::Continue;
+            }
+            let mut fired = vec![];
+            for occurrence in occurrences.drain(..) {
+                for (name, behavior) in &self.behaviors {
+                    for rule in behavior.rules.iter().filter(|rule| rule.matches(&occurrence)) {
+                        if self.rng.chance(rule.probability) {
+                            fired.push((name.clone(), rule.commands(name, &occurrence)));
+                        }
+                    }
+                }
+            }
+            for (name, commands) in fired {
+                reactions += 1;
+                if reactions > MAX_REACTIONS {
+                    println!(
+                        "Stopping after {} reactions, which may be triggering each other",
+                        MAX_REACTIONS
+                    );
+                    self.seen = self.seen_now();
+                    return Flow::Continue;
+                }
+                for command in commands {
+                    let cmd = match self.parse(&command) {
+                        Ok(cmd) => cmd,
+                        Err(e) => {
+                            println!("{} cannot react with {:?}: {}", name, command, e);
+                            break;
+                        }
+                    };
+                    println!("{} reacts: {:?}", name, cmd);
+                    match self.execute(cmd) {
+                        Ok(Flow::Continue) => {}
+                        Ok(Flow::Quit) => return Flow::Quit,
+                        Err(e) => {
+                            println!("{} cannot react with {:?}: {}", name, command, e);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_rule;
+
+    #[test]
+    fn splits_rules_on_unquoted_semicolons_only() {
+        let rule = parse_rule(
+            r#"on say do enact $self $agreement $stmt --effect "create a; transfer a to bob"; say $self "x; y""#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.commands,
+            [
+                r#"enact $self $agreement $stmt --effect "create a; transfer a to bob""#,
+                r#"say $self "x; y""#
+            ]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Printing a single statement, agreement or action in full, as `show` truncates payloads.
+
+use crate::{query::Section, AgreeIdx, Config, Ref, StmtIdx};
+
+impl Config {
+    pub fn cat(&self, section: Section, target: &Ref) -> Result<(), String> {
+        match section {
+            Section::Statements => self.cat_statement(target.resolve(&self.stmt_labels)?),
+            Section::Agreements => self.cat_agreement(target.resolve(&self.agree_labels)?),
+            Section::Actions => match target {
+                Ref::Idx(idx) => self.cat_action(*idx),
+                Ref::Label(label) => {
+                    Err(format!("Cannot show action {}, as actions have no labels", label))
+                }
+            },
+        }
+    }
+
+    fn cat_statement(&self, idx: StmtIdx) -> Result<(), String> {
+        let s = self.statements.get(idx).ok_or(format!("Cannot show unsaid message {}", idx))?;
+        println!("statement {} by {} at {}", idx, s.id.0, self.stated_at[idx]);
+        if let Some((label, _)) = self.stmt_labels.iter().find(|&(_, &i)| i == idx) {
+            println!("label: {}", label);
+        }
+        if s.id.1 as usize != idx {
+            println!("id: {:08x}", s.id.1);
+        }
+        if let Some(source) = self.sources.get(&idx) {
+            println!("from file: {}", source);
+        }
+        match self.recipients.get(&idx) {
+            Some(recipients) => println!("to: {}", recipients.join(", ")),
+            None => println!("to: all"),
+        }
+        if let Some(cut_off) = self.cut_off.get(&idx) {
+            let cut_off: Vec<&str> = cut_off.iter().map(String::as_str).collect();
+            println!("cut off: {}", cut_off.join(", "));
+        }
+        if let Some(delayed) = self.delayed.get(&idx) {
+            let delayed: Vec<String> =
+                delayed.iter().map(|(to, at)| format!("{} at {}", to, at)).collect();
+            println!("delayed: {}", delayed.join(", "));
+        }
+        if let Some(record) = self.signature_record(idx) {
+            println!("signed with: {}", record.key);
+        } else if self.signing() {
+            println!("unsigned");
+        }
+        if self.retracted.contains(&idx) {
+            println!("retracted");
+        }
+        println!("payload:\n{}", self.payload_text(Some(idx), &s.payload));
+        Ok(())
+    }
+
+    fn cat_agreement(&self, idx: AgreeIdx) -> Result<(), String> {
+        let a = self.agreements.get(idx).ok_or(format!("Cannot show unmade agreement {}", idx))?;
+        match self.expires_at.get(&idx) {
+            Some(until) => println!("agreement {} at {} until {}", idx, a.at, until),
+            None => println!("agreement {} at {}", idx, a.at),
+        }
+        if let Some((label, _)) = self.agree_labels.iter().find(|&(_, &i)| i == idx) {
+            println!("label: {}", label);
+        }
+        if let Some(by) = self.superseded_by.get(&idx) {
+            println!("superseded by: {}", by);
+        }
+        let on = self.stmt_idx(&a.message).map_or("?".to_string(), |i| i.to_string());
+        println!("on statement {} by {}", on, a.message.id.0);
+        println!("payload:\n{}", self.payload_text(self.stmt_idx(&a.message), &a.message.payload));
+        Ok(())
+    }
+
+    fn cat_action(&self, idx: usize) -> Result<(), String> {
+        let e = self.enacted.get(idx).ok_or(format!("Cannot show unenacted action {}", idx))?;
+        println!("action {} by {} at {}", idx, e.id.0, self.enacted_at[idx]);
+        if let Some(at) = self.withdrawn.get(&idx) {
+            println!("withdrawn at: {}", at);
+        }
+        println!("basis: agreement {} at {}", self.basis_idx(idx), e.basis.at);
+        if let Some(effect) = self.effects.get(&idx) {
+            println!("effect: {}", effect);
+        }
+        match self.violation(idx) {
+            None => println!("verdict: valid"),
+            Some(violation) => println!("verdict: INVALID: {}", violation),
+        }
+        let mut justification: Vec<_> =
+            e.justification.iter().map(|m| (self.stmt_idx(m), m)).collect();
+        justification.sort_by_key(|(i, _)| *i);
+        for (i, m) in justification {
+            let payload = self.payload_text(i, &m.payload);
+            let i = i.map_or("?".to_string(), |i| i.to_string());
+            println!("justification statement {} by {}:\n{}", i, m.id.0, payload);
+        }
+        Ok(())
+    }
+
+    /// The payload of a statement, pretty-printed if it is structured.
+    fn payload_text(&self, idx: Option<StmtIdx>, payload: &str) -> String {
+        match idx.and_then(|idx| self.structured.get(&idx)) {
+            Some(value) => serde_json::to_string_pretty(value).unwrap_or(payload.to_string()),
+            None => payload.to_string(),
+        }
+    }
+}
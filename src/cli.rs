@@ -0,0 +1,201 @@
+//! The command-line interface of the `justact-pdx` binary.
+
+use crate::{
+    compare, dump, http, policy, repl, rng, rpc, tokens, tokens::Tokens, verify, Config, Flow,
+};
+use std::{io::IsTerminal, path::Path};
+
+/// Environment variable overriding the default inspector command.
+const INSPECTOR_VAR: &str = "JUSTACT_INSPECTOR";
+
+/// The inspector command given by the environment, or the bundled inspector otherwise.
+fn default_inspector() -> Vec<String> {
+    match std::env::var(INSPECTOR_VAR) {
+        Ok(command) => Tokens::new(&command).map(|t| t.into_owned()).collect(),
+        Err(_) => vec!["./inspector.exe".to_string()],
+    }
+}
+
+fn exit_with_usage() -> ! {
+    println!("Usage: justact-pdx [--import <file>] [--inspector <cmd> <arg>*] [--policy <name>]");
+    println!(
+        "       [--history <file>] [--seed <n>] [--batch <file> | --verify <dump> [--keys <file>] | --check <script> <dump> | --compare <dump> <dump> | --rpc | --listen <addr> \
+         | --http <addr> | --tui]"
+    );
+    println!("The inspector command defaults to ${}, or ./inspector.exe if unset.", INSPECTOR_VAR);
+    std::process::exit(1)
+}
+
+/// Runs the commands in the given script without prompting, exiting with status 0 if all
+/// of them succeed. Otherwise, reports the first offending line on stderr and exits with
+/// status 2 if it cannot be parsed, or 1 if it fails.
+fn batch(config: &mut Config, path: &str) -> ! {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Cannot read {:?}: {}", path, e);
+        std::process::exit(1)
+    });
+    config.fail_fast = true;
+    for (line, command) in tokens::commands(&text) {
+        let cmd = config.parse(&command).unwrap_or_else(|e| {
+            eprintln!("{}:{}: {}", path, line, e);
+            std::process::exit(2)
+        });
+        println!("{:?}", cmd);
+        config.record(command.trim());
+        let result = match config.execute(cmd) {
+            Ok(Flow::Continue) => Ok(config.react()),
+            result => result,
+        };
+        config.feed_inspector();
+        match result {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Quit) => break,
+            Err(e) => {
+                eprintln!("{}:{}: {}", path, line, e);
+                std::process::exit(1)
+            }
+        }
+    }
+    std::process::exit(0)
+}
+
+/// Verifies the signatures in the given trace, trusting only the keys in the key file, if
+/// any, exiting with status 0 if none failed, or 1 otherwise, or if either cannot be read.
+fn verify_trace(config: &Config, path: &str, keys: Option<&str>) -> ! {
+    let verification = (keys.map(|keys| verify::read_keys(Path::new(keys))).transpose())
+        .and_then(|keys| {
+            let text = dump::read_trace(Path::new(path)).map_err(|e| e.to_string())?;
+            config.verify_trace(&text, keys.as_ref())
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("Cannot verify {:?}: {}", path, e);
+            std::process::exit(1)
+        });
+    verification.print();
+    std::process::exit(if verification.failures.is_empty() { 0 } else { 1 })
+}
+
+/// Runs the script in a fresh session and compares its trace with the expected one,
+/// exiting with status 0 if they match, or 1 otherwise, or if either cannot be read.
+fn check_golden(config: &Config, script: &str, expected: &str) -> ! {
+    match config.check(script, expected) {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Runs the command-line interface, as configured by the arguments of the process.
+pub fn main() {
+    let mut config =
+        Config { inspector: default_inspector(), branch: "main".to_string(), ..Config::default() };
+    let mut rpc = false;
+    let mut script = None;
+    let mut verify = None;
+    let mut keys = None;
+    let mut check = None;
+    let mut compare = None;
+    let mut listen = None;
+    let mut http = None;
+    let mut tui = false;
+    let mut history = false;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--import" => {
+                let path = args.next().unwrap_or_else(|| exit_with_usage());
+                if let Err(e) = config.import(Path::new(&path)) {
+                    println!("Cannot import {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+            "--policy" => {
+                let name = args.next().unwrap_or_else(|| exit_with_usage());
+                match policy::by_name(&name) {
+                    Some(policy) => config.policy = policy,
+                    None => {
+                        println!("Unknown or disabled policy engine {:?}", name);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--seed" => {
+                let seed = args.next().and_then(|seed| seed.parse().ok());
+                config.rng = rng::Rng::seeded(seed.unwrap_or_else(|| exit_with_usage()));
+            }
+            "--rpc" => rpc = true,
+            "--tui" => tui = true,
+            "--batch" => script = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--verify" => verify = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--keys" => keys = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--check" => {
+                let script = args.next().unwrap_or_else(|| exit_with_usage());
+                check = Some((script, args.next().unwrap_or_else(|| exit_with_usage())));
+            }
+            "--compare" => {
+                let old = args.next().unwrap_or_else(|| exit_with_usage());
+                compare = Some((old, args.next().unwrap_or_else(|| exit_with_usage())));
+            }
+            "--http" => http = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--listen" => listen = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "--history" => {
+                let path = args.next().unwrap_or_else(|| exit_with_usage());
+                if let Err(e) = config.open_history(Path::new(&path)) {
+                    println!("Cannot open history {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+                history = true;
+            }
+            "--inspector" => {
+                config.inspector =
+                    std::iter::from_fn(|| args.next_if(|arg| !arg.starts_with("--"))).collect();
+                if config.inspector.is_empty() {
+                    exit_with_usage();
+                }
+            }
+            _ => exit_with_usage(),
+        }
+    }
+    if let Some(path) = script {
+        batch(&mut config, &path);
+    } else if let Some(path) = verify {
+        verify_trace(&config, &path, keys.as_deref());
+    } else if let Some((script, expected)) = check {
+        check_golden(&config, &script, &expected);
+    } else if let Some((old, new)) = compare {
+        if let Err(e) = compare::compare_files(&old, &new) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(addr) = http {
+        if let Err(e) = http::serve(config, &addr) {
+            println!("Cannot serve HTTP on {:?}: {}", addr, e);
+            std::process::exit(1);
+        }
+    } else if let Some(addr) = listen {
+        if let Err(e) = rpc::listen(config, &addr) {
+            println!("Cannot listen on {:?}: {}", addr, e);
+            std::process::exit(1);
+        }
+    } else if rpc {
+        rpc::serve(config);
+    } else if tui {
+        config.run("tui");
+    } else if std::io::stdin().is_terminal() {
+        if !history && let Some(path) = repl::history_path() {
+            // Sessions can do without a history file
+            let opened = match path.parent() {
+                Some(dir) => std::fs::create_dir_all(dir),
+                None => Ok(()),
+            };
+            if let Err(e) = opened.and_then(|()| config.open_history(&path)) {
+                println!("Cannot open history {:?}: {}", path, e);
+            }
+        }
+        repl::interactive(&mut config);
+    } else {
+        repl::piped(&mut config);
+    }
+}
@@ -0,0 +1,121 @@
+//! The source of the current time, which is either set manually or follows the system clock.
+
+use crate::{Config, Time, UpdateCmd};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Seconds,
+    Millis,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Clock {
+    /// Time only changes by `now` and `tick`.
+    #[default]
+    Manual,
+    /// Time is `base` plus the units elapsed since `origin`.
+    Real { origin: Instant, base: Time, unit: Unit },
+}
+
+impl Unit {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "s" | "seconds" => Some(Self::Seconds),
+            "ms" | "millis" => Some(Self::Millis),
+            _ => None,
+        }
+    }
+
+    fn of(self, elapsed: Duration) -> Time {
+        match self {
+            Self::Seconds => elapsed.as_secs(),
+            Self::Millis => elapsed.as_millis().try_into().unwrap_or(Time::MAX),
+        }
+    }
+}
+
+impl Config {
+    /// Makes time follow the system clock in the given unit, continuing from the current
+    /// time, or makes it manual again if `None`.
+    pub fn set_clock(&mut self, unit: Option<Unit>) {
+        self.clock = match unit {
+            Some(unit) => Clock::Real { origin: Instant::now(), base: self.current, unit },
+            None => Clock::Manual,
+        };
+    }
+
+    /// Sets the time as `now` does, committing the updates scheduled by then. Time only goes
+    /// back if forced, and not at all while it follows the system clock.
+    pub fn set_time(&mut self, now: Time, force: bool) -> Result<(), String> {
+        if let Clock::Real { .. } = self.clock {
+            return Err("Time follows the system clock, unless with set clock manual".into());
+        } else if now < self.current && !force {
+            return Err(format!(
+                "Cannot go back in time from {} to {}, unless with now --force",
+                self.current, now
+            ));
+        }
+        self.advance(now)
+    }
+
+    /// Advances time to that of the system clock, if time follows it. The tick is journaled,
+    /// such that replays state everything at the same time, but is no update that undo
+    /// reverts or that discards what redo would re-apply.
+    pub fn sync_clock(&mut self) -> Result<(), String> {
+        if let Clock::Real { origin, base, unit } = self.clock {
+            let now = base.saturating_add(unit.of(origin.elapsed()));
+            if now > self.current {
+                self.announce_due(now);
+                let tick = UpdateCmd::Now { now, tick: true };
+                self.validate(&tick)?;
+                self.apply(&tick)?;
+                self.journal.push(tick);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Unit};
+    use crate::{config, Config};
+    use std::time::{Duration, Instant};
+
+    /// Makes the system clock read the given time, as if set to real time at time 0.
+    fn follow_clock(config: &mut Config, now: u64) {
+        let origin = Instant::now() - Duration::from_secs(now);
+        config.clock = Clock::Real { origin, base: 0, unit: Unit::Seconds };
+    }
+
+    fn run(config: &mut Config, command: &str) {
+        let cmd = config.parse(command).unwrap();
+        config.execute(cmd).unwrap();
+    }
+
+    #[test]
+    fn undoes_and_redoes_updates_rather_than_ticks() {
+        let mut config = config(&["say amy a."]);
+        follow_clock(&mut config, 10);
+        run(&mut config, "say amy b.");
+        assert_eq!(config.stated_at, [0, 10]);
+
+        follow_clock(&mut config, 20);
+        run(&mut config, "undo");
+        assert_eq!(config.statements.len(), 1);
+        assert_eq!(config.current, 20);
+
+        follow_clock(&mut config, 30);
+        run(&mut config, "redo");
+        assert_eq!(config.statements.len(), 2);
+        assert_eq!(config.stated_at, [0, 30]);
+
+        run(&mut config, "undo");
+        run(&mut config, "undo");
+        assert!(config.statements.is_empty());
+        assert_eq!(config.current, 30);
+        run(&mut config, "undo");
+        assert_eq!(config.undone.len(), 2);
+    }
+}
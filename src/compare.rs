@@ -0,0 +1,129 @@
+//! Comparing traces by their meaning rather than their text, e.g., to check that a change
+//! to the engine or the prototype library changes no trace but the ones it should.
+//!
+//! Events are identified by what they state, agree on, enact or advance the time to, and
+//! compared along with the time at which they happen. The order of the events at the same
+//! time, the format of the trace, the formatting of JSON payloads, and seeds are ignored.
+
+use crate::{dump, json, Time};
+use justact_prototype::auditing::{Event, EventControl};
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+
+/// An event, as the time at which it happens and its record in the trace.
+type Happening = (Time, Value);
+
+/// The events of a trace by their identities. Repeated identities are numbered apart.
+fn happenings(text: &str) -> Result<BTreeMap<String, Happening>, String> {
+    let mut happenings = BTreeMap::new();
+    let mut now = 0;
+    for (i, mut record) in dump::read_records(text)? {
+        // Retracted statements are recorded before the events, and compared as such
+        if let Some(r) = dump::retracted(&record) {
+            let identity = format!("retracted statement {} {}", r.id.0, r.id.1);
+            happenings.insert(identity, (now, record));
+            continue;
+        } else if dump::is_preamble(&record) {
+            continue;
+        }
+        let mut event = record.clone();
+        dump::strip_annotations(&mut event);
+        json::destructure(&mut event);
+        let control = record.get("Control").is_some();
+        let identity = match serde_json::from_value::<Event>(event) {
+            Ok(Event::Control(EventControl::AdvanceTime { timestamp })) => {
+                now = timestamp;
+                format!("time {}", timestamp)
+            }
+            Ok(Event::Control(EventControl::StateMessage { to, msg, .. })) => {
+                format!("statement {} {} to {:?}", msg.id.0, msg.id.1, to)
+            }
+            Ok(Event::Control(EventControl::AddAgreement { agree })) => format!(
+                "agreement on {} {} at {}",
+                agree.message.id.0, agree.message.id.1, agree.at
+            ),
+            Ok(Event::Control(EventControl::EnactAction { action, .. })) => {
+                format!("action {} {}", action.id.0, action.id.1)
+            }
+            // Other kinds of events, e.g., the data events of the prototype's runners, are
+            // identified by themselves
+            Ok(_) => record.to_string(),
+            Err(_) if !control => record.to_string(),
+            Err(e) => return Err(format!("entry {}: {}", i, e)),
+        };
+        // Payloads that are JSON are compared as such, whether dumped as text or not
+        json::structure(&mut record);
+        let mut n = 1;
+        let mut key = identity.clone();
+        while happenings.contains_key(&key) {
+            n += 1;
+            key = format!("{} (#{})", identity, n);
+        }
+        happenings.insert(key, (now, record));
+    }
+    Ok(happenings)
+}
+
+/// The differences between two traces, each listed by the identity of the event.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    pub removed: Vec<(String, Happening)>,
+    pub added: Vec<(String, Happening)>,
+    pub changed: Vec<(String, Happening, Happening)>,
+}
+
+impl Comparison {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn print(&self) {
+        for (identity, (at, record)) in &self.removed {
+            println!("- {} at {}: {}", identity, at, record);
+        }
+        for (identity, (at, record)) in &self.added {
+            println!("+ {} at {}: {}", identity, at, record);
+        }
+        for (identity, (old_at, old), (new_at, new)) in &self.changed {
+            println!("~ {}", identity);
+            println!("  - at {}: {}", old_at, old);
+            println!("  + at {}: {}", new_at, new);
+        }
+        println!(
+            "{} removed, {} added, {} changed",
+            self.removed.len(),
+            self.added.len(),
+            self.changed.len()
+        );
+    }
+}
+
+/// Compares the traces, as written by `dump`, as JSON lines or a JSON array.
+pub fn compare(old: &str, new: &str) -> Result<Comparison, String> {
+    let old = happenings(old).map_err(|e| format!("first trace: {}", e))?;
+    let mut new = happenings(new).map_err(|e| format!("second trace: {}", e))?;
+    let mut comparison = Comparison::default();
+    for (identity, old) in old {
+        match new.remove(&identity) {
+            None => comparison.removed.push((identity, old)),
+            Some(new) if new != old => comparison.changed.push((identity, old, new)),
+            Some(_) => {}
+        }
+    }
+    comparison.added.extend(new);
+    Ok(comparison)
+}
+
+/// Compares the traces in the files, failing if they differ.
+pub fn compare_files(old: &str, new: &str) -> Result<(), String> {
+    let read = |path: &str| {
+        dump::read_trace(Path::new(path)).map_err(|e| format!("Cannot read {:?}: {}", path, e))
+    };
+    let comparison = compare(&read(old)?, &read(new)?)?;
+    if comparison.is_empty() {
+        println!("The traces are equivalent");
+        return Ok(());
+    }
+    comparison.print();
+    Err(format!("The traces of {} and {} differ", old, new))
+}
@@ -0,0 +1,151 @@
+//! Saying many statements at once, one per row of a CSV or TSV file.
+
+use crate::{Config, UpdateCmd};
+use std::path::Path;
+
+/// What the fields in a column of a CSV file are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Sayer,
+    Payload,
+    Label,
+    /// Whitespace-separated recipients, or all agents if empty.
+    Recipients,
+    Ignored,
+}
+
+impl Column {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sayer" => Some(Self::Sayer),
+            "payload" => Some(Self::Payload),
+            "label" => Some(Self::Label),
+            "recipients" | "to" => Some(Self::Recipients),
+            "_" => Some(Self::Ignored),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `<column>(,<column>)*`, in which the sayer and payload columns must occur.
+pub fn parse_columns(s: &str) -> Result<Vec<Column>, String> {
+    let columns = (s.split(','))
+        .map(|c| Column::parse(c).ok_or_else(|| format!("unknown column {:?}", c)))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (required, name) in [(Column::Sayer, "sayer"), (Column::Payload, "payload")] {
+        if !columns.contains(&required) {
+            return Err(format!("expected a {} column", name));
+        }
+    }
+    Ok(columns)
+}
+
+/// Splits the text into rows of fields, along with the lines at which they start. Fields
+/// may be quoted as per RFC 4180, in which case they can contain delimiters, `""` for
+/// quotes and line breaks. Empty lines are skipped.
+fn rows(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let (mut rows, mut line) = (vec![], 1);
+    let mut chars = text.chars().peekable();
+    while chars.peek().is_some() {
+        let (start, mut fields, mut field) = (line, vec![], String::new());
+        let mut quoted = false;
+        loop {
+            match chars.next() {
+                Some('"') if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                Some('"') if quoted => quoted = false,
+                Some('"') if field.is_empty() => quoted = true,
+                Some('\n') if quoted => {
+                    line += 1;
+                    field.push('\n');
+                }
+                Some(c) if quoted => field.push(c),
+                None if quoted => return Err(format!("line {}: unclosed quotes", start)),
+                Some(c) if c == delimiter => fields.push(std::mem::take(&mut field)),
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    break;
+                }
+                Some(c) => field.push(c),
+            }
+        }
+        if !fields.is_empty() || !field.is_empty() {
+            fields.push(field);
+            rows.push((start, fields));
+        }
+    }
+    Ok(rows)
+}
+
+impl Config {
+    /// Says a statement for every row of the file, whose columns are named by the first
+    /// row if not given. Nothing is said if any row is malformed, and saying stops at the
+    /// first statement that cannot be said.
+    pub fn import_csv(
+        &mut self,
+        path: &str,
+        delimiter: char,
+        columns: Option<Vec<Column>>,
+    ) -> Result<(), String> {
+        let text = std::fs::read_to_string(self.relative_path(Path::new(path)))
+            .map_err(|e| format!("Cannot read {:?}: {}", path, e))?;
+        let mut rows =
+            rows(&text, delimiter).map_err(|e| format!("Cannot import {:?}: {}", path, e))?;
+        let columns = match columns {
+            Some(columns) => columns,
+            None if rows.is_empty() => return Ok(()),
+            // Columns that are not understood, e.g., those exported by `export csv`, are ignored
+            None => rows
+                .remove(0)
+                .1
+                .iter()
+                .map(|c| Column::parse(c).unwrap_or(Column::Ignored))
+                .collect(),
+        };
+        let mut says = vec![];
+        for (line, fields) in rows {
+            if fields.len() != columns.len() {
+                let msg = format!(
+                    "line {}: expected {} fields, got {}",
+                    line,
+                    columns.len(),
+                    fields.len()
+                );
+                return Err(format!("Cannot import {:?}: {}", path, msg));
+            }
+            let (mut sayer, mut payload, mut label, mut recipients) = (None, None, None, None);
+            for (column, field) in columns.iter().zip(fields) {
+                match column {
+                    Column::Sayer => sayer = Some(field),
+                    Column::Payload => payload = Some(field),
+                    Column::Label if !field.is_empty() => label = Some(field),
+                    Column::Recipients if !field.trim().is_empty() => {
+                        recipients = Some(field.split_whitespace().map(str::to_string).collect())
+                    }
+                    _ => {}
+                }
+            }
+            let (Some(sayer), Some(payload)) = (sayer, payload) else {
+                return Err(format!(
+                    "Cannot import {:?}: expected sayer and payload columns",
+                    path
+                ));
+            };
+            let (payload, source) = (payload.into(), Some(format!("{}:{}", path, line)));
+            says.push((line, UpdateCmd::Say { sayer, payload, label, recipients, source }));
+        }
+        let total = says.len();
+        for (i, (line, say)) in says.into_iter().enumerate() {
+            self.update_and_bind(say, None).map_err(|e| {
+                format!(
+                    "Cannot import line {} of {:?}, after {} of {} rows: {}",
+                    line, path, i, total, e
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
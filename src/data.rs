@@ -0,0 +1,89 @@
+//! A simulated data plane: datasets, which are resources owned by agents, and the reads of
+//! agents from them, each enabled by an enacted action, as the prototype's data exchanges.
+//!
+//! Reads are recorded whether or not they are authorized, such that auditing can find the
+//! unauthorized ones, and are stated in the trace as data events.
+
+use crate::{effects::Resource, Config, Time};
+use std::collections::BTreeSet;
+
+/// A read of an agent from a dataset.
+#[derive(Debug, Clone)]
+pub struct Read {
+    pub reader: String,
+    pub dataset: String,
+    /// The owner of the dataset at the time of reading.
+    pub owner: String,
+    /// The action that the read is enabled by.
+    pub via: usize,
+    pub at: Time,
+    /// Whether the reader owned or could access the dataset at the time of reading.
+    pub access: bool,
+    /// The time at which the action was withdrawn, if it was before the read.
+    pub withdrawn: Option<Time>,
+}
+
+impl Config {
+    pub(crate) fn add_dataset(&mut self, name: &str, owner: &str) -> Result<(), String> {
+        self.check_agent(owner)?;
+        if self.resources.contains_key(name) {
+            return Err(format!("Resource {} exists already", name));
+        }
+        let resource = Resource { owner: owner.to_string(), access: BTreeSet::new() };
+        self.resources.insert(name.to_string(), resource);
+        Ok(())
+    }
+
+    pub(crate) fn read(&mut self, reader: &str, dataset: &str, via: usize) -> Result<(), String> {
+        self.check_agent(reader)?;
+        let resource =
+            self.resources.get(dataset).ok_or(format!("There is no dataset {}", dataset))?;
+        if via >= self.enacted.len() {
+            return Err(format!("Cannot read via unenacted action {}", via));
+        }
+        let access = resource.owner == reader || resource.access.contains(reader);
+        self.reads.push(Read {
+            reader: reader.to_string(),
+            dataset: dataset.to_string(),
+            owner: resource.owner.clone(),
+            via,
+            at: self.current,
+            access,
+            withdrawn: self.withdrawn.get(&via).copied(),
+        });
+        Ok(())
+    }
+
+    /// Why the read with the given index is unauthorized, if it is.
+    pub fn unauthorized(&self, idx: usize) -> Option<String> {
+        let read = &self.reads[idx];
+        let action = &self.enacted[read.via];
+        if let Some(at) = read.withdrawn {
+            return Some(format!("action {} was withdrawn at {}", read.via, at));
+        } else if action.id.0 != read.reader {
+            return Some(format!("action {} is enacted by {}", read.via, action.id.0));
+        } else if !read.access {
+            return Some(format!("{} could not access {}", read.reader, read.dataset));
+        }
+        let violation = self.violation(read.via)?;
+        Some(format!("action {} is invalid: {}", read.via, violation))
+    }
+
+    /// Prints a verdict for each read, after those of the actions.
+    pub(crate) fn audit_reads(&self) {
+        if self.reads.is_empty() {
+            return;
+        }
+        println!("___reader__|__dataset__|_via_|___verdict___ READS");
+        for (i, read) in self.reads.iter().enumerate() {
+            let verdict = match self.unauthorized(i) {
+                None => "authorized".to_string(),
+                Some(why) => format!("UNAUTHORIZED: {}", why),
+            };
+            println!(
+                "{: >10} | {: <9} | {: >3} | {}",
+                read.reader, read.dataset, read.via, verdict
+            );
+        }
+    }
+}
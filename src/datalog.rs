@@ -0,0 +1,296 @@
+//! A small, positive Datalog dialect for statement payloads, e.g.,
+//! `parent(amy, bob). ancestor(X, Y) :- parent(X, Y).`, with `%` line comments.
+//! Variables start with an uppercase letter or `_`.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Term {
+    Var(String),
+    Const(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Atom {
+    pub pred: String,
+    pub args: Vec<Term>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub head: Atom,
+    pub body: Vec<Atom>,
+}
+
+type Bindings = HashMap<String, String>;
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Var(v) => write!(f, "{}", v),
+            // Constants that would read as variables are quoted as well
+            Term::Const(c)
+                if c.starts_with(|c: char| c.is_alphanumeric() && !c.is_uppercase())
+                    && c.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                write!(f, "{}", c)
+            }
+            Term::Const(c) => write!(f, "{:?}", c),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Term::Const(self.pred.clone()))?;
+        if let [first, rest @ ..] = &self.args[..] {
+            write!(f, "({}", first)?;
+            for arg in rest {
+                write!(f, ", {}", arg)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Atom {
+    fn is_ground(&self) -> bool {
+        self.args.iter().all(|t| matches!(t, Term::Const(_)))
+    }
+
+    /// Extends the bindings such that this atom equals the given ground atom, if possible.
+    fn unify(&self, fact: &Atom, bindings: &Bindings) -> Option<Bindings> {
+        if self.pred != fact.pred || self.args.len() != fact.args.len() {
+            return None;
+        }
+        let mut bindings = bindings.clone();
+        for (t, f) in self.args.iter().zip(&fact.args) {
+            let Term::Const(f) = f else { unreachable!("facts are ground") };
+            match t {
+                Term::Const(c) if c == f => {}
+                Term::Const(_) => return None,
+                Term::Var(v) if v == "_" => {}
+                Term::Var(v) => match bindings.get(v) {
+                    Some(bound) if bound != f => return None,
+                    Some(_) => {}
+                    None => {
+                        bindings.insert(v.clone(), f.clone());
+                    }
+                },
+            }
+        }
+        Some(bindings)
+    }
+
+    /// The ground atom with the variables bound, if all of them are.
+    fn substitute(&self, bindings: &Bindings) -> Option<Atom> {
+        let args = self.args.iter().map(|t| match t {
+            Term::Var(v) => bindings.get(v).map(|c| Term::Const(c.clone())),
+            Term::Const(c) => Some(Term::Const(c.clone())),
+        });
+        Some(Atom { pred: self.pred.clone(), args: args.collect::<Option<_>>()? })
+    }
+
+    fn vars(&self) -> impl Iterator<Item = &str> {
+        self.args.iter().filter_map(|t| match t {
+            Term::Var(v) if v != "_" => Some(v.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// Computes all facts derivable from the given rules.
+pub fn closure(rules: &[Rule]) -> BTreeSet<Atom> {
+    let mut facts = BTreeSet::new();
+    loop {
+        let mut derived = vec![];
+        for rule in rules {
+            let mut matches = vec![Bindings::new()];
+            for atom in &rule.body {
+                matches = matches
+                    .iter()
+                    .flat_map(|b| facts.iter().filter_map(move |f| atom.unify(f, b)))
+                    .collect();
+            }
+            derived.extend(matches.iter().filter_map(|b| rule.head.substitute(b)));
+        }
+        let before = facts.len();
+        facts.extend(derived);
+        if facts.len() == before {
+            return facts;
+        }
+    }
+}
+
+/// Parses a sequence of `.`-terminated facts and rules.
+pub fn parse(src: &str) -> Result<Vec<Rule>, String> {
+    let mut parser = Parser { src, pos: 0 };
+    let mut rules = vec![];
+    loop {
+        parser.skip_whitespace();
+        if parser.pos == src.len() {
+            return Ok(rules);
+        }
+        let head = parser.atom()?;
+        let mut body = vec![];
+        if parser.eat(":-") {
+            body.push(parser.atom()?);
+            while parser.eat(",") {
+                body.push(parser.atom()?);
+            }
+        }
+        parser.expect(".")?;
+        if body.is_empty() && !head.is_ground() {
+            return Err(format!("fact {} contains variables", head));
+        }
+        if head.args.iter().any(|t| *t == Term::Var("_".to_string())) {
+            return Err(format!("head {} contains the anonymous variable _", head));
+        }
+        if let Some(v) = head.vars().find(|v| !body.iter().any(|a| a.vars().any(|w| w == *v))) {
+            return Err(format!("variable {} of {} does not occur in the body", v, head));
+        }
+        rules.push(Rule { head, body });
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with('%') {
+                return;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            return Ok(());
+        }
+        Err(format!("expected {:?} at byte {}", token, self.pos))
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end =
+                quoted.find('"').ok_or(format!("unterminated string at byte {}", self.pos))?;
+            self.pos += end + 2;
+            return Ok(quoted[..end].to_string());
+        }
+        let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        if end == 0 {
+            return Err(format!("expected identifier at byte {}", self.pos));
+        }
+        self.pos += end;
+        Ok(rest[..end].to_string())
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        self.skip_whitespace();
+        let quoted = self.rest().starts_with('"');
+        let ident = self.ident()?;
+        Ok(if !quoted && ident.starts_with(|c: char| c.is_uppercase() || c == '_') {
+            Term::Var(ident)
+        } else {
+            Term::Const(ident)
+        })
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        let pred = match self.term()? {
+            Term::Const(pred) => pred,
+            Term::Var(v) => return Err(format!("expected predicate, found variable {}", v)),
+        };
+        let mut args = vec![];
+        if self.eat("(") {
+            args.push(self.term()?);
+            while self.eat(",") {
+                args.push(self.term()?);
+            }
+            self.expect(")")?;
+        }
+        Ok(Atom { pred, args })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive(src: &str) -> Vec<String> {
+        closure(&parse(src).unwrap()).iter().map(Atom::to_string).collect()
+    }
+
+    #[test]
+    fn derives_transitively() {
+        let src = "parent(amy, bob). parent(bob, cho).
+                   ancestor(X, Y) :- parent(X, Y).
+                   ancestor(X, Z) :- parent(X, Y), ancestor(Y, Z).";
+        let facts = derive(src);
+        assert!(facts.contains(&"ancestor(amy, cho)".to_string()));
+        assert!(!facts.contains(&"ancestor(cho, amy)".to_string()));
+    }
+
+    #[test]
+    fn matches_anonymous_variables_independently() {
+        assert_eq!(derive("p(a, b). q :- p(_, _)."), ["p(a, b)", "q"]);
+    }
+
+    #[test]
+    fn rejects_unsafe_rules() {
+        assert!(parse("p(X).").is_err());
+        assert!(parse("q(X) :- p(a).").is_err());
+        assert!(parse("q(_) :- p(a).").is_err());
+        assert!(parse("q(a, _) :- p(a).").is_err());
+    }
+
+    #[test]
+    fn reports_malformed_programs() {
+        assert!(parse("p(a").is_err());
+        assert!(parse("p(\"a).").is_err());
+        assert!(parse("X(a).").is_err());
+        assert!(parse("p(a) :- .").is_err());
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(derive("% a comment\np(a). % another\n"), ["p(a)"]);
+    }
+
+    #[test]
+    fn quotes_constants_that_would_read_as_variables() {
+        for src in ["p(\"Amy\").", "p(\"_a\").", "p(\"a b\").", "\"P\"(a).", "p(\"\")."] {
+            let facts = derive(src);
+            assert_eq!(derive(&format!("{}.", facts[0])), facts, "{}", src);
+        }
+        assert_eq!(derive("p(\"Amy\")."), ["p(\"Amy\")"]);
+        assert_eq!(derive("p(\"amy\")."), ["p(amy)"]);
+    }
+}
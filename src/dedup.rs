@@ -0,0 +1,39 @@
+//! Detecting duplicate statements, i.e., statements with the same sayer and payload, which
+//! are indistinguishable but for their indices.
+
+use crate::{query::Section, Config, StmtIdx};
+use std::collections::HashMap;
+
+impl Config {
+    /// The first unretracted statement said by the sayer with the payload, if any.
+    pub fn duplicated(&self, sayer: &str, payload: &str) -> Option<StmtIdx> {
+        self.with_payload(payload)
+            .find(|i| !self.retracted.contains(i) && self.statements[*i].id.0 == sayer)
+    }
+
+    /// The groups of unretracted statements with the same sayer and payload, ordered by
+    /// their first statements.
+    pub fn duplicate_groups(&self) -> Vec<Vec<StmtIdx>> {
+        let mut groups: HashMap<(&str, &str), Vec<StmtIdx>> = HashMap::new();
+        for (i, s) in self.statements.iter().enumerate() {
+            if !self.retracted.contains(&i) {
+                groups.entry((&s.id.0, &s.payload)).or_default().push(i);
+            }
+        }
+        let mut groups: Vec<Vec<StmtIdx>> =
+            groups.into_values().filter(|group| group.len() > 1).collect();
+        groups.sort();
+        groups
+    }
+
+    pub fn print_duplicates(&self) {
+        let groups = self.duplicate_groups();
+        if groups.is_empty() {
+            println!("No duplicate statements");
+        }
+        for group in groups {
+            println!("{} statements said by {}:", group.len(), self.statements[group[0]].id.0);
+            self.print_rows(Section::Statements, &group);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Writing the trace of a session in one of several formats.
+//!
+//! Traces as JSON lines are streamed, one event at a time, such that dumping a session of
+//! millions of statements takes no memory for the whole trace. Files ending in `.gz` are
+//! written and read gzip-compressed.
+
+use crate::{inspector::EventFilter, json, query::Section, signing::SignatureRecord, Config, Time};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use justact_prototype::auditing::{Event, EventControl};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// An event of a trace, along with the signature of the statement it states, if any.
+#[derive(Serialize)]
+struct Record<'a> {
+    #[serde(flatten)]
+    event: Event<'a>,
+    /// Absent for events not stating statements, and null for unsigned statements.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<Option<SignatureRecord>>,
+    /// The time of withdrawal, for events enacting withdrawn actions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    withdrawn: Option<Time>,
+    /// The label of the statement or agreement, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// The last time at which the agreement applies, for agreements not only at their time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    until: Option<Time>,
+    /// The agreement that the agreement supersedes, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supersedes: Option<AgreementKey>,
+}
+
+/// An agreement as traces refer to it, by the statement agreed on and the time it is at.
+#[derive(Serialize)]
+struct AgreementKey {
+    id: (String, u32),
+    at: Time,
+}
+
+/// What dumps record next to the events, which the JustAct prototype does not know of.
+#[derive(Debug, Default)]
+pub struct Annotations {
+    pub signature: Option<Value>,
+    pub withdrawn: Option<Time>,
+    pub label: Option<String>,
+    pub until: Option<Time>,
+    /// The statement and time of the agreement superseded.
+    pub supersedes: Option<((String, u32), Time)>,
+}
+
+/// Removes the annotations from a record written by `dump`, leaving the bare event.
+pub fn strip_annotations(record: &mut Value) -> Annotations {
+    let Some(record) = record.as_object_mut() else {
+        return Annotations::default();
+    };
+    let supersedes = record.remove("supersedes").and_then(|key| {
+        let id = key.get("id")?;
+        let (sayer, n) = (id.get(0)?.as_str()?, id.get(1)?.as_u64()?);
+        Some(((sayer.to_string(), u32::try_from(n).ok()?), key.get("at")?.as_u64()?))
+    });
+    Annotations {
+        signature: record.remove("signature"),
+        withdrawn: record.remove("withdrawn").and_then(|at| at.as_u64()),
+        label: record.remove("label").and_then(|label| label.as_str().map(String::from)),
+        until: record.remove("until").and_then(|until| until.as_u64()),
+        supersedes,
+    }
+}
+
+/// A statement that was retracted, and is therefore not stated in the trace, as recorded
+/// by `dump` before the events.
+#[derive(Debug)]
+pub struct Retracted {
+    pub id: (String, u32),
+    pub payload: String,
+    pub label: Option<String>,
+    /// Stated to all agents if `None`.
+    pub recipients: Option<Vec<String>>,
+}
+
+/// The retracted statement that the record is of, if it is of one.
+pub fn retracted(record: &Value) -> Option<Retracted> {
+    let msg = record.get("retracted")?;
+    let id = msg.get("id")?;
+    let (sayer, n) = (id.get(0)?.as_str()?, id.get(1)?.as_u64()?);
+    let strings = |v: &Value| -> Option<Vec<String>> {
+        v.as_array()?.iter().map(|s| s.as_str().map(String::from)).collect()
+    };
+    Some(Retracted {
+        id: (sayer.to_string(), u32::try_from(n).ok()?),
+        payload: msg.get("payload")?.as_str()?.to_string(),
+        label: record.get("label").and_then(|label| label.as_str()).map(String::from),
+        recipients: record.get("to").and_then(strings),
+    })
+}
+
+/// Whether the record precedes the events rather than being one, i.e., records the seed or a
+/// retracted statement.
+pub fn is_preamble(record: &Value) -> bool {
+    record.get("seed").is_some() || record.get("retracted").is_some()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One event per line, as piped into the inspector.
+    #[default]
+    JsonLines,
+    /// A single, pretty-printed array, for reading and diffing.
+    Json,
+    Yaml,
+    /// One bare event per line, with payloads as text, as the JustAct inspector and
+    /// prototype read and write traces.
+    Upstream,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "jsonl" => Some(Self::JsonLines),
+            "json" => Some(Self::Json),
+            "yaml" => Some(Self::Yaml),
+            "upstream" => Some(Self::Upstream),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the records of a trace as written by `dump`, as JSON lines or a JSON array, along
+/// with their line or entry numbers.
+pub fn read_records(text: &str) -> Result<Vec<(usize, serde_json::Value)>, String> {
+    if text.trim_start().starts_with('[') {
+        let records: Vec<serde_json::Value> =
+            serde_json::from_str(text).map_err(|e| e.to_string())?;
+        Ok(records.into_iter().enumerate().map(|(i, record)| (i + 1, record)).collect())
+    } else {
+        let lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        lines
+            .map(|(i, line)| {
+                let record = serde_json::from_str(line);
+                record.map(|record| (i + 1, record)).map_err(|e| format!("line {}: {}", i + 1, e))
+            })
+            .collect()
+    }
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// Reads the trace in the file, decompressing it if it ends in `.gz`.
+pub fn read_trace(path: &Path) -> std::io::Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut text = String::new();
+    if is_gzip(path) {
+        // Appending to a compressed dump adds another gzip member
+        MultiGzDecoder::new(BufReader::new(file)).read_to_string(&mut text)?;
+    } else {
+        BufReader::new(file).read_to_string(&mut text)?;
+    }
+    Ok(text)
+}
+
+impl Config {
+    pub(crate) fn write_dump<W: Write>(
+        &self,
+        mut w: W,
+        format: Format,
+        filter: &EventFilter,
+    ) -> std::io::Result<()> {
+        let events = self.inspection_events(filter);
+        if let Format::Upstream = format {
+            for event in events {
+                writeln!(w, "{}", serde_json::to_string(&event)?)?;
+            }
+            return w.flush();
+        }
+        let withdrawn: HashMap<_, Time> =
+            self.withdrawn.iter().map(|(&i, &at)| (&self.enacted[i].id, at)).collect();
+        // Indexed once, rather than searched per event
+        let invert = |labels: &HashMap<String, usize>| -> HashMap<usize, String> {
+            labels.iter().map(|(label, &i)| (i, label.clone())).collect()
+        };
+        let (stmt_labels, agree_labels) = (invert(&self.stmt_labels), invert(&self.agree_labels));
+        let supersedes_of: HashMap<_, _> =
+            self.superseded_by.iter().map(|(&old, &by)| (by, old)).collect();
+        let record = |event: Event| {
+            let signature = match &event {
+                // Marked unsigned only if some agent signs at all
+                Event::Control(EventControl::StateMessage { msg, .. }) if self.signing() => {
+                    Some(self.stmt_idx(msg).and_then(|idx| self.signature_record(idx)))
+                }
+                _ => None,
+            };
+            let withdrawn = match &event {
+                Event::Control(EventControl::EnactAction { action, .. }) => {
+                    withdrawn.get(&action.id).copied()
+                }
+                _ => None,
+            };
+            let (mut label, mut until, mut supersedes) = (None, None, None);
+            match &event {
+                Event::Control(EventControl::StateMessage { msg, .. }) => {
+                    label = self.stmt_idx(msg).and_then(|idx| stmt_labels.get(&idx).cloned());
+                }
+                Event::Control(EventControl::AddAgreement { agree }) => {
+                    if let Some(idx) = self.agree_idx(agree) {
+                        label = agree_labels.get(&idx).cloned();
+                        until = self.expires_at.get(&idx).copied();
+                        supersedes = (supersedes_of.get(&idx))
+                            .map(|&old| &self.agreements[old])
+                            .map(|old| AgreementKey { id: old.message.id.clone(), at: old.at });
+                    }
+                }
+                _ => {}
+            }
+            let record = Record { event, signature, withdrawn, label, until, supersedes };
+            let mut record = serde_json::to_value(record)?;
+            // Signatures are over the exact payloads, which need to be kept as text
+            if !self.signing() {
+                json::structure(&mut record);
+            }
+            Ok::<_, serde_json::Error>(record)
+        };
+        // The seed of the simulation, if any, precedes the events to reproduce them with
+        let seed = self.rng.used_seed().map(|seed| Ok(serde_json::json!({ "seed": seed })));
+        // Retracted statements are not stated, but recorded to import them along with the
+        // actions they justify
+        let mut retracted: Vec<_> = (self.retracted.iter().copied())
+            .filter(|&i| filter.keeps(Section::Statements, i, self.stated_at[i]))
+            .collect();
+        retracted.sort_unstable();
+        let retracted = retracted.into_iter().map(|i| {
+            let msg = &self.statements[i];
+            let retracted = serde_json::json!({ "id": msg.id, "payload": msg.payload });
+            let mut record = serde_json::json!({ "retracted": retracted });
+            if let Some(label) = stmt_labels.get(&i) {
+                record["label"] = label.clone().into();
+            }
+            if let Some(recipients) = self.recipients.get(&i) {
+                record["to"] = recipients.clone().into();
+            }
+            Ok(record)
+        });
+        let mut records = seed.into_iter().chain(retracted).chain(events.map(record));
+        match format {
+            Format::JsonLines => records
+                .try_for_each(|record| writeln!(w, "{}", serde_json::to_string(&record?)?))?,
+            Format::Json => {
+                let records = records.collect::<serde_json::Result<Vec<_>>>()?;
+                serde_json::to_writer_pretty(&mut w, &records)?;
+                writeln!(w)?;
+            }
+            Format::Yaml => {
+                let records = records.collect::<serde_json::Result<Vec<_>>>()?;
+                serde_yaml::to_writer(&mut w, &records).map_err(std::io::Error::other)?
+            }
+            Format::Upstream => unreachable!(),
+        }
+        w.flush()
+    }
+
+    pub(crate) fn dump(&self, format: Format, filter: &EventFilter) -> std::io::Result<()> {
+        self.write_dump(BufWriter::new(std::io::stdout().lock()), format, filter)
+    }
+
+    /// Writes the trace to the file, after its existing contents if appending, compressed
+    /// if the file ends in `.gz`.
+    pub(crate) fn dump_to(
+        &self,
+        path: &Path,
+        append: bool,
+        format: Format,
+        filter: &EventFilter,
+    ) -> std::io::Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        if !is_gzip(path) {
+            return self.write_dump(BufWriter::new(file), format, filter);
+        }
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        self.write_dump(&mut encoder, format, filter)?;
+        encoder.finish()?.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cmd;
+
+    #[test]
+    fn appends_json_lines_only() {
+        assert!(Cmd::parse("dump --append trace.jsonl", 0).is_ok());
+        assert!(Cmd::parse("dump --format json --append trace.json", 0).is_err());
+        assert!(Cmd::parse("dump --append --format yaml trace.yaml", 0).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//! The effects of enacted actions on simulated resources, e.g., datasets that are created,
+//! transferred or shared by enacting actions, as interpreted by a pluggable interpreter.
+//!
+//! Effects are applied as their actions are enacted, all or nothing: an action whose effect
+//! does not apply is not enacted. Withdrawing an action does not undo its effect.
+
+use crate::{tokens::Tokens, Config};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A simulated resource, e.g., a dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    pub owner: String,
+    /// The agents other than the owner that may access the resource.
+    pub access: BTreeSet<String>,
+}
+
+/// The simulated resources by name.
+pub type Resources = BTreeMap<String, Resource>;
+
+/// Interprets the effects of enacted actions as changes to the simulated resources.
+pub trait EffectInterpreter: Send {
+    fn name(&self) -> &'static str;
+
+    /// Applies the effect of an action by the actor to the resources. Fails if the effect
+    /// cannot be interpreted, or does not apply, e.g., to a resource that does not exist.
+    fn apply(&self, resources: &mut Resources, actor: &str, effect: &str) -> Result<(), String>;
+}
+
+/// Interprets `;`-separated effects of the forms:
+/// - `create <resource>`, owned by the actor;
+/// - `transfer <resource> to <agent>`;
+/// - `grant <resource> to <agent>`;
+/// - `revoke <resource> from <agent>`;
+/// - `delete <resource>`.
+pub struct ResourceInterpreter;
+
+impl ResourceInterpreter {
+    fn apply_one(resources: &mut Resources, actor: &str, effect: &str) -> Result<(), String> {
+        let mut tokens = Tokens::new(effect);
+        let mut next = |what: &str| tokens.next().ok_or(format!("expected {}", what));
+        let verb = next("create, transfer, grant, revoke or delete")?;
+        let name = next("a resource")?.into_owned();
+        let agent = match &*verb {
+            "create" | "delete" => None,
+            "transfer" | "grant" | "revoke" => {
+                let preposition = if verb == "revoke" { "from" } else { "to" };
+                if next(preposition)? != preposition {
+                    return Err(format!("expected {} after {}", preposition, name));
+                }
+                Some(next("an agent")?.into_owned())
+            }
+            other => return Err(format!("unknown effect {:?}", other)),
+        };
+        if let Ok(extra) = next("nothing") {
+            return Err(format!("unexpected {:?} after {}", extra, effect));
+        }
+        if verb == "create" {
+            if resources.contains_key(&name) {
+                return Err(format!("resource {} exists already", name));
+            }
+            let owner = actor.to_string();
+            resources.insert(name, Resource { owner, access: BTreeSet::new() });
+            return Ok(());
+        }
+        let resource = resources.get_mut(&name).ok_or(format!("there is no resource {}", name))?;
+        match (&*verb, agent) {
+            ("transfer", Some(agent)) => {
+                resource.access.remove(&agent);
+                resource.owner = agent;
+            }
+            ("grant", Some(agent)) => {
+                resource.access.insert(agent);
+            }
+            ("revoke", Some(agent)) => {
+                resource.access.remove(&agent);
+            }
+            _ => {
+                resources.remove(&name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EffectInterpreter for ResourceInterpreter {
+    fn name(&self) -> &'static str {
+        "resources"
+    }
+
+    fn apply(&self, resources: &mut Resources, actor: &str, effect: &str) -> Result<(), String> {
+        for effect in effect.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            Self::apply_one(resources, actor, effect)?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Applies the effect of an action by the actor, as the interpreter interprets it.
+    pub(crate) fn apply_effect(&mut self, actor: &str, effect: &str) -> Result<(), String> {
+        let interpreter = self.interpreter.as_deref().unwrap_or(&ResourceInterpreter);
+        // Applies the effect to a copy, such that interpreters that fail halfway change nothing
+        let mut resources = self.resources.clone();
+        interpreter
+            .apply(&mut resources, actor, effect)
+            .map_err(|e| format!("Cannot apply effect {:?}: {}", effect, e))?;
+        self.resources = resources;
+        Ok(())
+    }
+
+    pub fn show_resources(&self) {
+        if self.resources.is_empty() {
+            println!("No resources");
+            return;
+        }
+        println!("__resource_|___owner___|___access___ RESOURCES");
+        for (name, resource) in &self.resources {
+            let access: Vec<&str> = resource.access.iter().map(String::as_str).collect();
+            println!("{: >10} | {: <9} | {}", name, resource.owner, access.join(", "));
+        }
+    }
+}
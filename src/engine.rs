@@ -0,0 +1,217 @@
+//! The public API of the engine, for embedding it in other programs.
+
+use crate::{
+    dump, inspector::EventFilter, rpc::State, AgreeIdx, Cmd, Config, EffectInterpreter, IdScheme,
+    Resources, StmtIdx, Time, UpdateCmd,
+};
+use justact_prototype::wire::{Action, Agreement, Message};
+use std::{fmt, sync::Arc};
+
+/// A JustAct scenario being played out by applying updates.
+pub struct Engine {
+    config: Config,
+}
+
+/// What applying an update changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Stated(StmtIdx),
+    Agreed(AgreeIdx),
+    Amended { superseded: AgreeIdx, by: AgreeIdx },
+    Enacted(usize),
+    TimeAdvanced { from: Time, to: Time },
+    Retracted(StmtIdx),
+    Withdrawn(usize),
+    Scheduled(Time),
+    AgentAdded(String),
+    AgentRemoved(String),
+    StrictSet(bool),
+    EnforceSet(bool),
+    IdSchemeSet(IdScheme),
+    KeyAdded(String),
+    Trusted { truster: String, trustee: String },
+    Synced { from: String, to: String },
+    Partitioned([Vec<String>; 2]),
+    Healed,
+    Linked { from: String, to: String },
+    DatasetAdded(String),
+    Read(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineError {
+    /// The command is not an update in the REPL grammar.
+    Parse(String),
+    /// The update does not apply to the current state.
+    Rejected(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Parse(command) => write!(f, "cannot parse update {:?}", command),
+            EngineError::Rejected(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self { config: Config { branch: "main".to_string(), ..Config::default() } }
+    }
+
+    pub fn apply(&mut self, update_cmd: UpdateCmd) -> Result<Effect, EngineError> {
+        let before = self.config.current;
+        let effect = match &update_cmd {
+            UpdateCmd::Say { .. } => Effect::Stated(self.config.statements.len()),
+            UpdateCmd::Agree { .. } => Effect::Agreed(self.config.agreements.len()),
+            UpdateCmd::Amend { supersedes, .. } => Effect::Amended {
+                superseded: supersedes
+                    .resolve(&self.config.agree_labels)
+                    .map_err(EngineError::Rejected)?,
+                by: self.config.agreements.len(),
+            },
+            UpdateCmd::Enact { .. } => Effect::Enacted(self.config.enacted.len()),
+            UpdateCmd::Now { now, .. } => Effect::TimeAdvanced { from: before, to: *now },
+            UpdateCmd::Retract { stmt, .. } => {
+                let idx = stmt.resolve(&self.config.stmt_labels).map_err(EngineError::Rejected)?;
+                Effect::Retracted(idx)
+            }
+            UpdateCmd::Withdraw { action, .. } => Effect::Withdrawn(*action),
+            UpdateCmd::Schedule { at, .. } => Effect::Scheduled(*at),
+            UpdateCmd::AddAgent { name, .. } => Effect::AgentAdded(name.clone()),
+            UpdateCmd::RemoveAgent { name } => Effect::AgentRemoved(name.clone()),
+            UpdateCmd::Strict { strict } => Effect::StrictSet(*strict),
+            UpdateCmd::Enforce { enforce } => Effect::EnforceSet(*enforce),
+            UpdateCmd::IdScheme { scheme } => Effect::IdSchemeSet(*scheme),
+            UpdateCmd::AddKey { agent, .. } => Effect::KeyAdded(agent.clone()),
+            UpdateCmd::Trust { truster, trustee } => {
+                Effect::Trusted { truster: truster.clone(), trustee: trustee.clone() }
+            }
+            UpdateCmd::Sync { from, to } => Effect::Synced { from: from.clone(), to: to.clone() },
+            UpdateCmd::Partition { groups } => Effect::Partitioned(groups.clone()),
+            UpdateCmd::Heal => Effect::Healed,
+            UpdateCmd::AddDataset { name, .. } => Effect::DatasetAdded(name.clone()),
+            UpdateCmd::Read { .. } => Effect::Read(self.config.reads.len()),
+            UpdateCmd::Link { from, to, .. } => {
+                Effect::Linked { from: from.clone(), to: to.clone() }
+            }
+        };
+        // Time is set as by the REPL, never going back
+        match update_cmd {
+            UpdateCmd::Now { now, .. } => self.config.set_time(now, false),
+            update_cmd => self.config.try_update(update_cmd),
+        }
+        .map_err(EngineError::Rejected)?;
+        Ok(effect)
+    }
+
+    /// Parses and applies an update written in the REPL grammar, e.g., `say amy "ok."`.
+    pub fn apply_line(&mut self, command: &str) -> Result<Effect, EngineError> {
+        match Cmd::parse(command, self.config.current) {
+            Ok(Cmd::Update(update_cmd)) => self.apply(update_cmd),
+            _ => Err(EngineError::Parse(command.to_string())),
+        }
+    }
+
+    /// Interprets the effects of actions with the given interpreter rather than the
+    /// built-in one, re-applying the effects of the actions enacted so far.
+    pub fn set_interpreter(&mut self, interpreter: Box<dyn EffectInterpreter>) {
+        self.config.interpreter = Some(interpreter);
+        self.config.replay();
+    }
+
+    /// The simulated resources, as changed by the effects of actions.
+    pub fn resources(&self) -> &Resources {
+        &self.config.resources
+    }
+
+    /// Reverts the most recently applied update. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        if self.config.journal.iter().all(|u| matches!(u, UpdateCmd::Now { tick: true, .. })) {
+            return false;
+        }
+        self.config.undo();
+        true
+    }
+
+    pub fn current(&self) -> Time {
+        self.config.current
+    }
+
+    pub fn statements(&self) -> &[Arc<Message>] {
+        &self.config.statements
+    }
+
+    pub fn agreements(&self) -> &[Agreement] {
+        &self.config.agreements
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.config.enacted
+    }
+
+    pub fn is_retracted(&self, idx: StmtIdx) -> bool {
+        self.config.retracted.contains(&idx)
+    }
+
+    /// Why the given action is invalid, if it is.
+    pub fn violation(&self, idx: usize) -> Option<String> {
+        self.config.violation(idx)
+    }
+
+    /// The updates applied so far, oldest first.
+    pub fn journal(&self) -> &[UpdateCmd] {
+        &self.config.journal
+    }
+
+    /// The state in a serializable form.
+    pub fn state(&self) -> State<'_> {
+        self.config.state()
+    }
+
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Writes the state as a trace of control events, one JSON object per line, as
+    /// `dump --format upstream` does, i.e., without the labels, signatures and other
+    /// records that `dump` adds to them.
+    pub fn write_trace<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        self.config.write_dump(w, dump::Format::Upstream, &EventFilter::default())
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Effect, Engine, EngineError};
+    use crate::inspector::EventFilter;
+
+    #[test]
+    fn does_not_go_back_in_time() {
+        let mut engine = Engine::new();
+        assert!(matches!(engine.apply_line("now 5"), Ok(Effect::TimeAdvanced { from: 0, to: 5 })));
+        assert!(matches!(engine.apply_line("now 2"), Err(EngineError::Rejected(_))));
+        assert_eq!(engine.current(), 5);
+    }
+
+    #[test]
+    fn traces_control_events_only() {
+        let mut engine = Engine::new();
+        for line in ["say amy a. as rule", "agree rule 0 until 3", "retract amy rule"] {
+            engine.apply_line(line).unwrap();
+        }
+        let mut trace = vec![];
+        engine.write_trace(&mut trace).unwrap();
+        let events = engine.config().inspection_events(&EventFilter::default()).count();
+        assert_eq!(String::from_utf8(trace).unwrap().lines().count(), events);
+    }
+}
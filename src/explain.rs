@@ -0,0 +1,70 @@
+//! Explaining the validity of an enacted action condition by condition, with, for each
+//! condition that fails, the smallest change to the scenario that would make it hold.
+
+use crate::{audit::Fix, justify, Config, StmtIdx};
+
+impl Config {
+    /// The smallest change to a forbidden justification that the policy engine permits, as
+    /// the fewest statements to leave out, other than the statement of the basis.
+    fn smallest_removal(
+        &self,
+        justification: impl Iterator<Item = StmtIdx>,
+        on: Option<StmtIdx>,
+    ) -> String {
+        let stated: Vec<StmtIdx> = justification.collect();
+        let removable: Vec<StmtIdx> = stated.iter().copied().filter(|&i| Some(i) != on).collect();
+        let permitted = |removed: &[StmtIdx]| {
+            let kept: Vec<StmtIdx> =
+                stated.iter().copied().filter(|i| !removed.contains(i)).collect();
+            self.licenses(&kept, None)
+        };
+        match justify::smallest_subset(&[], &removable, permitted) {
+            Ok(Some(removed)) if !removed.is_empty() => {
+                let noun = if removed.len() == 1 { "statement" } else { "statements" };
+                let removed: Vec<String> = removed.iter().map(|i| i.to_string()).collect();
+                format!("{} {} must be left out of the justification", noun, removed.join(", "))
+            }
+            Ok(_) => "no statements can be left out to permit it".to_string(),
+            Err(e) => format!("no smallest change found: {}", e),
+        }
+    }
+
+    /// Prints whether each validity condition of the action holds, and for those that
+    /// fail, the smallest change that would make them hold.
+    pub fn explain(&self, idx: usize) -> Result<(), String> {
+        let action =
+            self.enacted.get(idx).ok_or(format!("Cannot explain unenacted action {}", idx))?;
+        let checks: Vec<_> = self.checks(idx).collect();
+        let on = self.stmt_idx(&action.basis.message);
+        let fixes: Vec<String> = (checks.iter())
+            .filter_map(|check| check.failure.as_ref())
+            .map(|(_, fix)| match fix {
+                Fix::Change(fix) => fix.clone(),
+                Fix::LeaveOut => self.smallest_removal(
+                    action.justification.iter().filter_map(|m| self.stmt_idx(m)),
+                    on,
+                ),
+            })
+            .collect();
+        let verdict = if fixes.is_empty() { "valid" } else { "INVALID" };
+        println!("action {} by {} at {} is {}", idx, action.id.0, self.enacted_at[idx], verdict);
+        for check in &checks {
+            let mark = if check.failure.is_none() { "holds" } else { "FAILS" };
+            println!("  {}: {}", mark, check.description);
+        }
+        if let Some(by) = self.superseded_basis(idx) {
+            println!(
+                "  warning: the basis was superseded by agreement {} at {}, which enacting \
+                 before then, or basing the action on agreement {}, avoids",
+                by, self.agreements[by].at, by
+            );
+        }
+        if !fixes.is_empty() {
+            println!("to be valid:");
+            for fix in fixes {
+                println!("  - {}", fix);
+            }
+        }
+        Ok(())
+    }
+}
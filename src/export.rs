@@ -0,0 +1,320 @@
+//! Renders the state in formats for use outside of this tool.
+
+use crate::{Config, UpdateCmd};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Dot,
+    Mermaid,
+    Html,
+    /// Into a directory, rather than a single file.
+    Csv,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dot" => Some(Self::Dot),
+            "mermaid" => Some(Self::Mermaid),
+            "html" => Some(Self::Html),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    pub fn export(&self, format: Format, path: &Path) -> std::io::Result<()> {
+        if let Format::Csv = format {
+            return self.write_csv(path);
+        }
+        let mut w = BufWriter::new(std::fs::File::create(path)?);
+        match format {
+            Format::Dot => self.write_dot(&mut w)?,
+            Format::Mermaid => self.write_mermaid(&mut w)?,
+            Format::Html => self.write_html(&mut w)?,
+            Format::Csv => unreachable!(),
+        }
+        w.flush()
+    }
+
+    /// Writes `statements.csv`, `agreements.csv` and `actions.csv` into the given directory.
+    fn write_csv(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let create = |name: &str| std::fs::File::create(dir.join(name)).map(BufWriter::new);
+
+        let mut w = create("statements.csv")?;
+        writeln!(w, "stmt_id,label,sayer,recipients,retracted,payload")?;
+        for (i, s) in self.statements.iter().enumerate() {
+            let recipients = self.recipients.get(&i).map_or(String::new(), |rs| rs.join(" "));
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                i,
+                csv_escape(label_of(&self.stmt_labels, i)),
+                csv_escape(&s.id.0),
+                csv_escape(&recipients),
+                self.retracted.contains(&i),
+                csv_escape(&s.payload)
+            )?;
+        }
+        w.flush()?;
+
+        let mut w = create("agreements.csv")?;
+        writeln!(w, "ag_id,label,stmt_id,at")?;
+        for (i, a) in self.agreements.iter().enumerate() {
+            let on = self.stmt_idx(&a.message).map_or(String::new(), |s| s.to_string());
+            let label = csv_escape(label_of(&self.agree_labels, i));
+            writeln!(w, "{},{},{},{}", i, label, on, a.at)?;
+        }
+        w.flush()?;
+
+        let mut w = create("actions.csv")?;
+        writeln!(w, "act_id,actor,basis,justification,enacted_at,violation")?;
+        for (i, e) in self.enacted.iter().enumerate() {
+            let basis = self.basis_idx(i);
+            let mut justification: Vec<_> =
+                e.justification.iter().filter_map(|m| self.stmt_idx(m)).collect();
+            justification.sort();
+            let justification: Vec<_> = justification.iter().map(|s| s.to_string()).collect();
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                i,
+                csv_escape(&e.id.0),
+                basis,
+                justification.join(" "),
+                self.enacted_at[i],
+                csv_escape(&self.violation(i).unwrap_or_default())
+            )?;
+        }
+        w.flush()
+    }
+
+    /// Writes the justification graph, with statements, agreements and actions as nodes.
+    /// Agreements point to the statement agreed upon, and actions to their basis and
+    /// justification.
+    fn write_dot<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "digraph justification {{")?;
+        writeln!(w, "  rankdir=BT;")?;
+        for (i, s) in self.statements.iter().enumerate() {
+            let style = if self.retracted.contains(&i) { ", style=dashed" } else { "" };
+            let label = format!("{} {}\n{}", i, s.id.0, trucated_line(&s.payload));
+            writeln!(w, "  s{} [shape=note, label={:?}{}];", i, label, style)?;
+        }
+        for (i, a) in self.agreements.iter().enumerate() {
+            writeln!(w, "  g{} [shape=box, label=\"agreement {}\\nat {}\"];", i, i, a.at)?;
+            if let Some(s) = self.stmt_idx(&a.message) {
+                writeln!(w, "  g{} -> s{} [label=\"on\"];", i, s)?;
+            }
+        }
+        for (i, e) in self.enacted.iter().enumerate() {
+            let color = if self.violation(i).is_some() { ", color=red" } else { "" };
+            writeln!(w, "  a{} [shape=ellipse, label=\"action {}\\n{}\"{}];", i, i, e.id.0, color)?;
+            writeln!(w, "  a{} -> g{} [label=\"based-on\"];", i, self.basis_idx(i))?;
+            for s in e.justification.iter().filter_map(|m| self.stmt_idx(m)) {
+                writeln!(w, "  a{} -> s{} [label=\"justified-by\", style=dotted];", i, s)?;
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+impl Config {
+    /// Writes a sequence diagram of the updates in the journal, with a lane per agent.
+    /// Statements to all agents are sent to a separate `all` lane.
+    fn write_mermaid<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        let mut agents = BTreeSet::new();
+        for update_cmd in &self.journal {
+            match update_cmd {
+                UpdateCmd::Say { sayer, recipients, .. } => {
+                    agents.insert(sayer);
+                    agents.extend(recipients.iter().flatten());
+                }
+                UpdateCmd::Enact { actor, .. } => {
+                    agents.insert(actor);
+                }
+                _ => {}
+            }
+        }
+        writeln!(w, "sequenceDiagram")?;
+        for agent in &agents {
+            writeln!(w, "    participant {}", mermaid_escape(agent))?;
+        }
+        writeln!(w, "    participant all")?;
+        let span = match agents.first() {
+            Some(first) => format!("{},all", mermaid_escape(first)),
+            None => "all".to_string(),
+        };
+
+        // The journal only contains applied updates, so indices can be counted along
+        let (mut stmt, mut agree, mut act) = (0, 0, 0);
+        for update_cmd in &self.journal {
+            match update_cmd {
+                UpdateCmd::Say { sayer, payload, recipients, .. } => {
+                    let text = mermaid_escape(&format!("{}: {}", stmt, trucated_line(payload)));
+                    let sayer = mermaid_escape(sayer);
+                    match recipients {
+                        Some(recipients) => {
+                            for r in recipients {
+                                writeln!(w, "    {}->>{}: say {}", sayer, mermaid_escape(r), text)?;
+                            }
+                        }
+                        None => writeln!(w, "    {}->>all: say {}", sayer, text)?,
+                    }
+                    stmt += 1;
+                }
+                UpdateCmd::Agree { .. } | UpdateCmd::Amend { .. } => {
+                    let a = &self.agreements[agree];
+                    let on = self.stmt_idx(&a.message).map_or("?".to_string(), |s| s.to_string());
+                    let amends = self.superseded_by.iter().find(|&(_, &by)| by == agree);
+                    let amends =
+                        amends.map_or(String::new(), |(old, _)| format!(", amending {}", old));
+                    writeln!(
+                        w,
+                        "    Note over {}: agreement {} on {} at {}{}",
+                        span, agree, on, a.at, amends
+                    )?;
+                    agree += 1;
+                }
+                UpdateCmd::Enact { actor, .. } => {
+                    let e = &self.enacted[act];
+                    let mut justification: Vec<_> =
+                        e.justification.iter().filter_map(|m| self.stmt_idx(m)).collect();
+                    justification.sort();
+                    let basis = self.basis_idx(act);
+                    let text = format!("enact {} on {} by {:?}", act, basis, justification);
+                    writeln!(w, "    {}->>all: {}", mermaid_escape(actor), mermaid_escape(&text))?;
+                    act += 1;
+                }
+                UpdateCmd::Now { now, .. } => writeln!(w, "    Note over {}: time {}", span, now)?,
+                UpdateCmd::Retract { retractor, stmt } => {
+                    let text = mermaid_escape(&format!("retract {}", stmt));
+                    writeln!(w, "    {}->>all: {}", mermaid_escape(retractor), text)?;
+                }
+                UpdateCmd::Withdraw { actor, action } => {
+                    let text = mermaid_escape(&format!("withdraw {}", action));
+                    writeln!(w, "    {}->>all: {}", mermaid_escape(actor), text)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #eee; }
+pre { margin: 0.3em 0 0 0; }
+.bad { color: #b00; }";
+
+impl Config {
+    /// Writes a standalone HTML page with the state and the journal as a timeline.
+    fn write_html<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(w, "<html><head><meta charset=\"utf-8\"><title>JustAct PDX report</title>")?;
+        writeln!(w, "<style>{}</style></head><body>", HTML_STYLE)?;
+        writeln!(w, "<h1>JustAct PDX report</h1>")?;
+        writeln!(w, "<p>Current time: {}</p>", self.current)?;
+
+        writeln!(w, "<h2>Statements</h2>")?;
+        writeln!(w, "<table><tr><th>stmt.id</th><th>sayer</th><th>to</th><th>payload</th></tr>")?;
+        for (i, s) in self.statements.iter().enumerate() {
+            let to = self.recipients.get(&i).map_or("all".to_string(), |rs| rs.join(", "));
+            let class = if self.retracted.contains(&i) { " class=\"bad\"" } else { "" };
+            let retracted = if self.retracted.contains(&i) { " (retracted)" } else { "" };
+            writeln!(
+                w,
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td><details><summary>{}{}</summary><pre>{}</pre></details></td></tr>",
+                class,
+                html_escape(&crate::labelled(&self.stmt_labels, i)),
+                html_escape(&s.id.0),
+                html_escape(&to),
+                html_escape(&trucated_line(&s.payload)),
+                retracted,
+                html_escape(&s.payload),
+            )?;
+        }
+        writeln!(w, "</table>")?;
+
+        writeln!(w, "<h2>Agreements</h2>")?;
+        writeln!(w, "<table><tr><th>ag.id</th><th>stmt.id</th><th>time</th></tr>")?;
+        for (i, a) in self.agreements.iter().enumerate() {
+            let on = self.stmt_idx(&a.message).map_or("?".to_string(), |s| s.to_string());
+            let i = html_escape(&crate::labelled(&self.agree_labels, i));
+            writeln!(w, "<tr><td>{}</td><td>{}</td><td>{}</td></tr>", i, on, a.at)?;
+        }
+        writeln!(w, "</table>")?;
+
+        writeln!(w, "<h2>Enacted actions</h2>")?;
+        writeln!(
+            w,
+            "<table><tr><th>act.id</th><th>actor</th><th>basis</th><th>justification</th><th>verdict</th></tr>"
+        )?;
+        for (i, e) in self.enacted.iter().enumerate() {
+            let basis = self.basis_idx(i);
+            let mut justification: Vec<_> =
+                e.justification.iter().filter_map(|m| self.stmt_idx(m)).collect();
+            justification.sort();
+            let verdict = match self.violation(i) {
+                None => "valid".to_string(),
+                Some(violation) => {
+                    format!("<span class=\"bad\">{}</span>", html_escape(&violation))
+                }
+            };
+            writeln!(
+                w,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:?}</td><td>{}</td></tr>",
+                i,
+                html_escape(&e.id.0),
+                basis,
+                justification,
+                verdict
+            )?;
+        }
+        writeln!(w, "</table>")?;
+
+        writeln!(w, "<h2>Timeline</h2>")?;
+        writeln!(w, "<ol>")?;
+        for update_cmd in &self.journal {
+            writeln!(w, "<li><code>{}</code></li>", html_escape(&format!("{:?}", update_cmd)))?;
+        }
+        writeln!(w, "</ol>")?;
+        writeln!(w, "</body></html>")
+    }
+}
+
+/// Quotes fields containing separators, quotes or line breaks, as per RFC 4180.
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn label_of(labels: &HashMap<String, usize>, idx: usize) -> &str {
+    labels.iter().find(|&(_, &i)| i == idx).map_or("", |(label, _)| label)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Mermaid ends statements at `;` and line breaks, and treats `#` as an entity code.
+fn mermaid_escape(s: &str) -> String {
+    s.replace('#', "#35;").replace(';', "#59;").replace(['\n', '\r'], " ")
+}
+
+/// The payload, truncated like in `show`, on a single line.
+fn trucated_line(payload: &str) -> String {
+    let [a, b] = crate::trucated(payload);
+    format!("{}{}", a, b).split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,108 @@
+//! A C ABI for embedding the engine in non-Rust hosts. Building with the `ffi` feature
+//! generates the corresponding header at `include/justact_pdx.h`.
+//!
+//! Strings are NUL-terminated UTF-8. Strings returned by the engine are owned by the
+//! caller, who must free them with `engine_string_free`, unless documented otherwise.
+
+use crate::Engine;
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    ptr,
+};
+
+/// An engine, along with the error of the last failed call, if any.
+pub struct PdxEngine {
+    engine: Engine,
+    last_error: Option<CString>,
+}
+
+/// Converts a string for returning to C, replacing any interior NUL characters.
+fn to_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', "\u{FFFD}")).expect("NULs are replaced").into_raw()
+}
+
+/// Creates an engine with an empty state. Free it with `engine_free`.
+#[unsafe(no_mangle)]
+pub extern "C" fn engine_new() -> *mut PdxEngine {
+    Box::into_raw(Box::new(PdxEngine { engine: Engine::new(), last_error: None }))
+}
+
+/// Frees the engine. Does nothing if it is NULL.
+///
+/// # Safety
+/// The engine must have been created with `engine_new`, and not have been freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_free(engine: *mut PdxEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Parses and applies an update written in the REPL grammar, e.g., `say amy "ok."`.
+/// Returns 0 on success, or -1 on failure, after which `engine_last_error` describes it.
+///
+/// # Safety
+/// The engine must be valid, and the line a valid NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_apply_line(engine: *mut PdxEngine, line: *const c_char) -> c_int {
+    let engine = unsafe { &mut *engine };
+    let result = match unsafe { CStr::from_ptr(line) }.to_str() {
+        Ok(line) => engine.engine.apply_line(line).map_err(|e| e.to_string()),
+        Err(e) => Err(format!("line is not UTF-8: {}", e)),
+    };
+    match result {
+        Ok(_) => {
+            engine.last_error = None;
+            0
+        }
+        Err(e) => {
+            engine.last_error = CString::new(e.replace('\0', "\u{FFFD}")).ok();
+            -1
+        }
+    }
+}
+
+/// The error of the last failed call, or NULL if the last call succeeded. The string
+/// is owned by the engine, and valid until the next call with the engine.
+///
+/// # Safety
+/// The engine must be valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_last_error(engine: *const PdxEngine) -> *const c_char {
+    let engine = unsafe { &*engine };
+    engine.last_error.as_ref().map_or(ptr::null(), |e| e.as_ptr())
+}
+
+/// The state as a JSON object, like the `state` method of `--rpc` reports it.
+///
+/// # Safety
+/// The engine must be valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_state_json(engine: *const PdxEngine) -> *mut c_char {
+    let engine = unsafe { &*engine };
+    to_c_string(serde_json::to_string(&engine.engine.state()).expect("state is serializable"))
+}
+
+/// The trace of the state as control events, one JSON object per line, as written by
+/// `dump --format upstream`.
+///
+/// # Safety
+/// The engine must be valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_dump_json(engine: *const PdxEngine) -> *mut c_char {
+    let engine = unsafe { &*engine };
+    let mut trace = vec![];
+    engine.engine.write_trace(&mut trace).expect("writing to memory does not fail");
+    to_c_string(String::from_utf8(trace).expect("trace is UTF-8"))
+}
+
+/// Frees a string returned by the engine. Does nothing if it is NULL.
+///
+/// # Safety
+/// The string must have been returned by the engine, and not have been freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn engine_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
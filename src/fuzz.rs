@@ -0,0 +1,27 @@
+//! Support for fuzzing the command parser and the engine, which must not panic on any input.
+
+use crate::{Cmd, Config};
+
+/// Feeds arbitrary input, one command per line, through the command parser and the updates
+/// of a fresh engine, after which it derives the verdicts and the trace. Commands other than
+/// updates, undo and redo are only parsed, as they may, e.g., write files.
+pub fn fuzz_apply(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut config = Config::default();
+    for line in text.lines() {
+        match config.parse(line) {
+            Ok(Cmd::Update(update_cmd) | Cmd::Let { update_cmd, .. }) => {
+                let _ = config.try_update(update_cmd);
+            }
+            Ok(Cmd::Undo) => config.undo(),
+            Ok(Cmd::Redo) => config.redo(),
+            _ => {}
+        }
+    }
+    for idx in 0..config.enacted.len() {
+        let _ = config.violation(idx);
+    }
+    let _ = config.write_inspection(std::io::sink());
+}
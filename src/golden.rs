@@ -0,0 +1,75 @@
+//! Golden traces: checking that a script still produces the trace it was once dumped with,
+//! to catch regressions in scenarios.
+
+use crate::{dump, Config};
+use std::path::Path;
+
+/// The non-empty lines of a trace.
+fn lines(text: &str) -> Vec<&str> {
+    text.lines().filter(|line| !line.trim().is_empty()).collect()
+}
+
+/// The lines removed from `expected` and added in `actual`, prefixed by `-` and `+` as in
+/// a unified diff, by their longest common subsequence.
+fn diff(expected: &[&str], actual: &[&str]) -> Vec<String> {
+    let (n, m) = (expected.len(), actual.len());
+    // common[i][j] is the length of the LCS of expected[i..] and actual[j..]
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut lines) = (0, 0, vec![]);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            (i, j) = (i + 1, j + 1);
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("-{}: {}", i + 1, expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}: {}", j + 1, actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+impl Config {
+    /// Runs the script in a fresh session, failing fast, and compares the trace it dumps
+    /// with the expected one, line by line. Returns the differing lines, if any.
+    pub fn check_golden(&self, script: &str, expected: &str) -> Result<Vec<String>, String> {
+        let expected_path = self.relative_path(Path::new(expected));
+        let expected = dump::read_trace(&expected_path)
+            .map_err(|e| format!("Cannot read {:?}: {}", expected_path, e))?;
+        let mut fresh = Config { branch: "main".to_string(), fail_fast: true, ..Config::default() };
+        fresh.load(&self.relative_path(Path::new(script)))?;
+        let mut actual = vec![];
+        fresh.write_inspection(&mut actual).map_err(|e| e.to_string())?;
+        let actual = String::from_utf8_lossy(&actual);
+        Ok(diff(&lines(&expected), &lines(&actual)))
+    }
+
+    /// Prints the differences of the trace of the script with the expected one, failing if
+    /// there are any.
+    pub fn check(&self, script: &str, expected: &str) -> Result<(), String> {
+        let differences = self.check_golden(script, expected)?;
+        if differences.is_empty() {
+            println!("The trace of {} matches {}", script, expected);
+            return Ok(());
+        }
+        for line in &differences {
+            println!("{}", line);
+        }
+        Err(format!(
+            "The trace of {} differs from {} in {} lines",
+            script,
+            expected,
+            differences.len()
+        ))
+    }
+}
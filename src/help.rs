@@ -0,0 +1,710 @@
+//! Documentation of the commands, as shown by `help`.
+
+/// Documents a command, which may have multiple forms.
+pub struct CommandDoc {
+    pub keyword: &'static str,
+    /// Other keywords for the same command.
+    pub aliases: &'static [&'static str],
+    pub usage: &'static [&'static str],
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// All commands, in the order in which `help` lists them.
+pub const COMMANDS: &[CommandDoc] = &[
+    CommandDoc {
+        keyword: "say",
+        aliases: &[],
+        usage: &[
+            "say <name> <payload> [as <label>]",
+            "say <name> @<template> (<param>=<value>)* [as <label>]",
+        ],
+        description: "States the payload as the named agent, to all agents. A payload \
+                      @<template> is the template with the values for its parameters.",
+        example: "say amy \"offer (a b) at shop for 3.\" as offer",
+    },
+    CommandDoc {
+        keyword: "sayto",
+        aliases: &[],
+        usage: &["sayto <name> <name>[,<name>]* <payload> [as <label>]"],
+        description: "States the payload as the first named agent, only to the listed recipients.",
+        example: "sayto amy bob,cho \"secret.\"",
+    },
+    CommandDoc {
+        keyword: "agree",
+        aliases: &[],
+        usage: &["agree <stmt.id> <time> [until <time>] [as <label>]"],
+        description:
+            "Makes the statement an agreement, which actions at the given time can be based \
+                      on, or at any time up to and including the until time, if given.",
+        example: "agree 0 1 until 5 as rules",
+    },
+    CommandDoc {
+        keyword: "amend",
+        aliases: &[],
+        usage: &["amend <ag.id> <stmt.id> <time> [until <time>] [as <label>]"],
+        description:
+            "Agrees on the statement like agree, superseding the given agreement from the \
+                      given time on. Actions based on superseded agreements are warned about.",
+        example: "amend rules 3 4",
+    },
+    CommandDoc {
+        keyword: "enact",
+        aliases: &[],
+        usage: &["enact <name> <ag.id> <stmt.id>* [--effect <effect>] [--check]"],
+        description: "Enacts an action as the named agent, based on the agreement and justified \
+                      by the statements. The effect, e.g., \"transfer patients to bob\", changes \
+                      the resources that show resources shows. With --check, only tells whether \
+                      the action would be valid now. With set enforce on, actions that the policy \
+                      engine does not license, or that are invalid otherwise, are refused.",
+        example: "enact bob rules 1 2",
+    },
+    CommandDoc {
+        keyword: "now",
+        aliases: &[],
+        usage: &["now [--force] <time>"],
+        description: "Sets the current time, at which actions are enacted. Time only goes back \
+                      if forced. Any time may also be given as now, or relative to it, e.g., +3.",
+        example: "now +1",
+    },
+    CommandDoc {
+        keyword: "tick",
+        aliases: &[],
+        usage: &["tick [<duration>]"],
+        description: "Advances the current time by the duration, or 1 if omitted.",
+        example: "tick 2",
+    },
+    CommandDoc {
+        keyword: "schedule",
+        aliases: &[],
+        usage: &["schedule <time> enact <name> <ag.id> <stmt.id>*"],
+        description:
+            "Queues the enactment, which is committed at the given time once now advances \
+                      that far.",
+        example: "schedule 3 enact bob rules 1",
+    },
+    CommandDoc {
+        keyword: "scheduled",
+        aliases: &[],
+        usage: &["scheduled"],
+        description: "Lists the queued enactments.",
+        example: "scheduled",
+    },
+    CommandDoc {
+        keyword: "sayfile",
+        aliases: &[],
+        usage: &["sayfile <name> <file> [as <label>]"],
+        description: "Says the contents of the file as the named agent, recording the file as \
+                      the source of the statement. The file is relative to the script being \
+                      loaded, if any.",
+        example: "sayfile amy policies/amy.slick as rules",
+    },
+    CommandDoc {
+        keyword: "retract",
+        aliases: &[],
+        usage: &["retract <name> <stmt.id>"],
+        description:
+            "Retracts a statement said by the named agent, which then no longer counts as stated.",
+        example: "retract amy offer",
+    },
+    CommandDoc {
+        keyword: "withdraw",
+        aliases: &[],
+        usage: &["withdraw <name> <act.id>"],
+        description: "Withdraws an action enacted by the named agent at the current time. The \
+                      action remains in the trace, marked withdrawn.",
+        example: "withdraw bob 0",
+    },
+    CommandDoc {
+        keyword: "audit",
+        aliases: &[],
+        usage: &["audit"],
+        description: "Checks the validity of the enacted actions, including that their \
+                      justifications are closed, i.e., include the statements defining what they \
+                      use, and the permissibility of their justifications if a policy engine is \
+                      selected.",
+        example: "audit",
+    },
+    CommandDoc {
+        keyword: "eval",
+        aliases: &[],
+        usage: &["eval <stmt.id>*"],
+        description:
+            "Evaluates the payloads of the statements as Datalog, printing all derived facts. \
+                      Evaluates all unretracted statements if none are given.",
+        example: "eval 0 1",
+    },
+    CommandDoc {
+        keyword: "inspect",
+        aliases: &[],
+        usage: &[
+            "inspect [--only <section>(,<section>)*] [--since <time>] [--live] \
+             [[stmt] <stmt.id>|agreement <ag.id>|action <act.id>]",
+            "inspect --stop",
+        ],
+        description: "Pipes the trace into the inspector. With --live, the inspector keeps \
+                      running, and is sent the events of each command as it happens, until \
+                      inspect --stop. The trace can be restricted as with dump, or to a single \
+                      statement, agreement or action, along with the statement of an \
+                      agreement, or the basis and justification of an action.",
+        example: "inspect action 0",
+    },
+    CommandDoc {
+        keyword: "show",
+        aliases: &[],
+        usage: &[
+            "show [stmts|agreements|actions] [by <name>] [since <time>] [as <name>] \
+             [--head <n>|--tail <n>]",
+            "show trust",
+            "show resources",
+        ],
+        description: "Shows the state, optionally restricted to a section, an agent, or recent \
+                      entries, or as seen by the given agent. --head and --tail show only the \
+                      first or last n rows of each section. Output that does not fit on the \
+                      terminal is paged, with $PAGER if set. show trust shows which agents \
+                      trust which, and show resources who owns and may access which resources.",
+        example: "show stmts by amy since 2",
+    },
+    CommandDoc {
+        keyword: "current-agreements",
+        aliases: &["current-agreement"],
+        usage: &["current-agreements"],
+        description: "Shows the agreements that apply at the current time, and are not \
+                      superseded, which show marks as current. enact warns about bases that are \
+                      not current, and refuses them in strict mode.",
+        example: "current-agreements",
+    },
+    CommandDoc {
+        keyword: "stats",
+        aliases: &[],
+        usage: &["stats [--memory]"],
+        description: "Summarizes the session: the statements, agreements and actions per agent, \
+                      the average size of justifications, the time span covered, and the \
+                      largest payload. With --memory, reports the bytes of the payloads of \
+                      statements and of updates instead.",
+        example: "stats",
+    },
+    CommandDoc {
+        keyword: "cat",
+        aliases: &[],
+        usage: &["cat [stmt] <stmt.id>", "cat agreement <ag.id>", "cat action <act.id>"],
+        description: "Prints the full payload of a statement, agreement or action, which show \
+                      truncates, along with its sayer or actor, time, label, recipients, and \
+                      signature. JSON payloads are pretty-printed.",
+        example: "cat offer",
+    },
+    CommandDoc {
+        keyword: "tui",
+        aliases: &[],
+        usage: &["tui"],
+        description: "Runs a full-screen interface, with panes showing the statements, \
+                      agreements and actions as commands are entered, until quit or escape. \
+                      Requires the tui feature.",
+        example: "tui",
+    },
+    CommandDoc {
+        keyword: "uses",
+        aliases: &[],
+        usage: &["uses <stmt.id>"],
+        description: "Lists the agreements on the statement, and the actions based on or \
+                      justified by it, e.g., before retracting or amending it.",
+        example: "uses 0",
+    },
+    CommandDoc {
+        keyword: "sync",
+        aliases: &[],
+        usage: &["sync <from> <to>"],
+        description: "Delivers the statements that the first agent knows of, i.e., its own and \
+                      those in its inbox, to the second, as gossip. The trace states them anew, \
+                      by the first agent, such that the inspector shows knowledge spreading.",
+        example: "sync bob cho",
+    },
+    CommandDoc {
+        keyword: "partition",
+        aliases: &[],
+        usage: &["partition", "partition <agent>... | <agent>..."],
+        description: "Partitions the agents into two groups, between which statements are not \
+                      delivered, neither as they are said nor by sync, or shows the partition. \
+                      Agents in neither group reach both.",
+        example: "partition amy bob | cho",
+    },
+    CommandDoc {
+        keyword: "heal",
+        aliases: &[],
+        usage: &["heal"],
+        description: "Ends the partition. Statements that were not delivered across it stay so, \
+                      until an agent that knows of them syncs with those cut off.",
+        example: "heal",
+    },
+    CommandDoc {
+        keyword: "link",
+        aliases: &[],
+        usage: &["link", "link <from> <to> [delay <n>] [drop <probability>]"],
+        description: "Makes statements from the first agent to the second arrive the given time \
+                      later, if ever, as the link drops them with the given probability, or \
+                      lists such links. A link without either delivers statements as said.",
+        example: "link amy bob delay 2 drop 0.1",
+    },
+    CommandDoc {
+        keyword: "inbox",
+        aliases: &[],
+        usage: &["inbox <agent>"],
+        description: "Lists the statements of other agents delivered to the agent, with the times \
+                      at which it received them. Statements to particular recipients are \
+                      delivered to those only.",
+        example: "inbox bob",
+    },
+    CommandDoc {
+        keyword: "explain",
+        aliases: &[],
+        usage: &["explain <act.id>"],
+        description: "Lists whether each validity condition of the action holds, and for those \
+                      that fail, the smallest change that would make them hold, e.g., which \
+                      statements to leave out of a justification the policy engine forbids.",
+        example: "explain 0",
+    },
+    CommandDoc {
+        keyword: "minimize",
+        aliases: &[],
+        usage: &["minimize <act.id> [for <fact>]"],
+        description: "Finds the smallest subset of the justification of the action, including \
+                      the basis, that the policy engine still permits, and derives the fact \
+                      from, if given, and lists the redundant statements.",
+        example: "minimize 0 for \"may(amy)\"",
+    },
+    CommandDoc {
+        keyword: "autojustify",
+        aliases: &[],
+        usage: &["autojustify <name> <ag.id> [for <fact>] [--enact]"],
+        description: "Searches the smallest set of statements that the agent received, including \
+                      the basis, that the policy engine permits, and derives the fact from, if \
+                      given. Prints it, or enacts an action justified by it with --enact.",
+        example: "autojustify amy 0 for \"may(amy)\" --enact",
+    },
+    CommandDoc {
+        keyword: "why",
+        aliases: &[],
+        usage: &["why <act.id>"],
+        description: "Explains whether the action is valid, with a tree of its basis agreement, \
+                      its justification statements, and the facts that the policy engine, if \
+                      any, derives from them.",
+        example: "why 0",
+    },
+    CommandDoc {
+        keyword: "dataset",
+        aliases: &[],
+        usage: &["dataset add <name> owner=<agent>"],
+        description: "Adds a dataset, as a resource owned by the agent, which effects of actions \
+                      can transfer or grant access to, and agents can read from.",
+        example: "dataset add patients.csv owner=hospital",
+    },
+    CommandDoc {
+        keyword: "read",
+        aliases: &[],
+        usage: &["read <agent> <dataset> via <act.id>"],
+        description: "Reads from the dataset as the agent, enabled by the action, which the trace \
+                      states as a data event. audit finds reads via actions of other agents, \
+                      withdrawn or invalid actions, or without access unauthorized.",
+        example: "read amy patients.csv via 0",
+    },
+    CommandDoc {
+        keyword: "trust",
+        aliases: &[],
+        usage: &["trust <truster> <trustee>"],
+        description: "Makes the truster accept statements by the trustee as justification. An \
+                      agent that trusts anyone accepts only statements by those it trusts, and \
+                      its own, while audit finds actions justified otherwise invalid.",
+        example: "trust amy bob",
+    },
+    CommandDoc {
+        keyword: "dedup",
+        aliases: &[],
+        usage: &["dedup"],
+        description: "Lists the groups of unretracted statements with the same sayer and payload.",
+        example: "dedup",
+    },
+    CommandDoc {
+        keyword: "verify",
+        aliases: &[],
+        usage: &["verify [<file> [--keys <key file>]]"],
+        description: "Verifies the signatures of the statements in the trace dumped to the file, \
+                      or of the session, reporting forged or tampered entries. Agents that were \
+                      given keys in the session, or in the key file, must have signed with one \
+                      of those. Given a key file, other keys are not trusted, while otherwise, \
+                      each agent must sign with the key it signs with first in the trace.",
+        example: "verify trace.jsonl",
+    },
+    CommandDoc {
+        keyword: "check",
+        aliases: &[],
+        usage: &["check <script> <expected-dump>"],
+        description: "Runs the script in a fresh session, failing at its first failing command, \
+                      and compares the trace it dumps with the expected one, line by line. \
+                      Fails if they differ, after showing the removed and added lines.",
+        example: "check scenario.txt scenario.jsonl",
+    },
+    CommandDoc {
+        keyword: "compare",
+        aliases: &[],
+        usage: &["compare <dump1> <dump2>"],
+        description: "Compares the dumped traces by their events, listing those removed from \
+                      the first, added in the second, and changed in between. Events are \
+                      compared along with the time at which they happen, ignoring their order \
+                      at the same time, the format of the dump, and the formatting of payloads. \
+                      Fails if the traces differ.",
+        example: "compare before.jsonl after.jsonl",
+    },
+    CommandDoc {
+        keyword: "find",
+        aliases: &[],
+        usage: &["find [--regex] <pattern>"],
+        description: "Shows the statements with payloads containing the pattern.",
+        example: "find --regex offer \\(.*\\)",
+    },
+    CommandDoc {
+        keyword: "query",
+        aliases: &[],
+        usage: &["query <stmts|agreements|actions> [where <field> <op> <value> (and ...)*]"],
+        description: "Shows the entries of a section whose fields satisfy all conditions. \
+                      The operators are =, !=, <, <=, >, >= and ~ (contains). Fields of JSON \
+                      payloads are named by their path, e.g., payload.grant.role.",
+        example: "query actions where actor = bob and at >= 2",
+    },
+    CommandDoc {
+        keyword: "dump",
+        aliases: &[],
+        usage: &[
+            "dump [--format jsonl|json|yaml|upstream] [--only <section>(,<section>)*] [--since <time>] \
+             [[--append] <file>]",
+        ],
+        description: "Prints the trace, as piped into the inspector, or writes it to the file, \
+                      possibly after the traces dumped before, as JSON lines. The trace has \
+                      one JSON event per line, unless formatted as a single JSON array or as \
+                      YAML. It can be restricted to the statements, agreements or actions, \
+                      and to those made since a time. JSON payloads are written as JSON \
+                      values, unless any agent signs its statements. The upstream format has \
+                      bare events only, as read by the JustAct inspector.",
+        example: "dump --format yaml trace.yaml",
+    },
+    CommandDoc {
+        keyword: "export",
+        aliases: &[],
+        usage: &["export <dot|mermaid|html> <file>", "export csv <dir>"],
+        description: "Writes the state as a graph, a web page, or a CSV file per section.",
+        example: "export dot state.dot",
+    },
+    CommandDoc {
+        keyword: "load",
+        aliases: &["source"],
+        usage: &["load <file>"],
+        description: "Executes the commands in the script, relative to the loading script, if any.",
+        example: "load justact_pdx_script1.txt",
+    },
+    CommandDoc {
+        keyword: "scenario",
+        aliases: &[],
+        usage: &["scenario list", "scenario load <name>"],
+        description: "Lists the example scenarios built into the tool, or executes the commands \
+                      of one, as if loading its script.",
+        example: "scenario load medical",
+    },
+    CommandDoc {
+        keyword: "save",
+        aliases: &[],
+        usage: &["save <file>"],
+        description: "Saves the session, including its undone updates, to the file.",
+        example: "save session.json",
+    },
+    CommandDoc {
+        keyword: "restore",
+        aliases: &[],
+        usage: &["restore <file>"],
+        description: "Replaces the session by the one saved in the file.",
+        example: "restore session.json",
+    },
+    CommandDoc {
+        keyword: "import",
+        aliases: &[],
+        usage: &["import <file>", "import csv|tsv <file> [--columns <column>(,<column>)*]"],
+        description: "Applies the updates of a trace, as written by dump or the JustAct \
+                      prototype, ignoring events other than control events, or says a statement \
+                      for every row of a CSV or TSV file. Its columns are sayer, payload, label, \
+                      recipients or _ (ignored), named by the first row unless given.",
+        example: "import csv messages.csv --columns sayer,_,payload",
+    },
+    CommandDoc {
+        keyword: "set",
+        aliases: &[],
+        usage: &[
+            "set inspector <cmd> <arg>*",
+            "set inspector <tcp://host:port|unix://path>",
+            "set policy <slick|datalog|eflint|none>",
+            "set schema <datalog|json|<file>|none>",
+            "set strict <on|off>",
+            "set enforce <on|off>",
+            "set clock <real [s|ms]|manual>",
+            "set idscheme <hash|counter>",
+            "set watch <on|off>",
+            "set audit <continuous|off>",
+        ],
+        description: "Configures the inspector, as a program to run or a socket to connect to, \
+                      the policy engine used by audit, the grammar or JSON schema that payloads \
+                      are validated against, whether only registered agents may act, \
+                      whether only actions that the policy engine licenses may be enacted, \
+                      whether time follows the system clock, whether the messages of new \
+                      statements are identified by a hash of their sayer and payload, whether \
+                      the state is shown anew after every update, or whether actions are \
+                      audited as they are enacted or invalidated.",
+        example: "set policy datalog",
+    },
+    CommandDoc {
+        keyword: "agent",
+        aliases: &[],
+        usage: &[
+            "agent add <name> [role <role>] [desc <description>]",
+            "agent remove <name>",
+            "agent list",
+            "agent keygen <name>",
+            "agent key <name> <public key>",
+            "agent run <name> <script>",
+            "agent stop <name>",
+        ],
+        description: "Registers, unregisters or lists agents, generates a key with which an \
+                      agent signs its later statements, gives an agent a public key of which it \
+                      holds the secret key elsewhere, e.g., to log in over RPC, or runs or stops an agent that reacts to \
+                      statements, agreements and actions by the rules of a script.",
+        example: "agent add amy role seller desc \"sells pairs\"",
+    },
+    CommandDoc {
+        keyword: "step",
+        aliases: &[],
+        usage: &["step [<n>] [--seed <seed>]"],
+        description: "Advances the time by one, n times or once, each time firing the rules of \
+                      the agents run by scripts that react to steps, possibly by chance. A seed \
+                      makes the chances reproducible, and is recorded in the dump.",
+        example: "step 100",
+    },
+    CommandDoc {
+        keyword: "history",
+        aliases: &[],
+        usage: &["history", "history search [--regex] <pattern>"],
+        description: "Lists the accepted commands of the session, and of earlier sessions kept \
+                      in the history file, or only those containing the pattern.",
+        example: "history",
+    },
+    CommandDoc {
+        keyword: "replay",
+        aliases: &[],
+        usage: &["replay [--step] <file>"],
+        description: "Re-executes a history file or applies a trace, showing the changes, \
+                      after each step if stepping.",
+        example: "replay --step history.txt",
+    },
+    CommandDoc {
+        keyword: "snapshot",
+        aliases: &[],
+        usage: &["snapshot <name>"],
+        description: "Remembers the current state under the name, to compare against with diff.",
+        example: "snapshot before",
+    },
+    CommandDoc {
+        keyword: "diff",
+        aliases: &[],
+        usage: &["diff <snapshot|branch|file> [<snapshot|branch|file>]"],
+        description:
+            "Shows the changes between two states, the latter being the current one if omitted.",
+        example: "diff before",
+    },
+    CommandDoc {
+        keyword: "checkpoint",
+        aliases: &[],
+        usage: &["checkpoint <name>"],
+        description: "Remembers the session under the name, to roll back to.",
+        example: "checkpoint start",
+    },
+    CommandDoc {
+        keyword: "checkpoints",
+        aliases: &[],
+        usage: &["checkpoints"],
+        description: "Lists the checkpoints.",
+        example: "checkpoints",
+    },
+    CommandDoc {
+        keyword: "rollback",
+        aliases: &[],
+        usage: &["rollback <name>"],
+        description: "Restores the session remembered by the checkpoint.",
+        example: "rollback start",
+    },
+    CommandDoc {
+        keyword: "branch",
+        aliases: &[],
+        usage: &["branch <name>"],
+        description: "Creates a branch starting out as the current one, and switches to it.",
+        example: "branch alternative",
+    },
+    CommandDoc {
+        keyword: "branches",
+        aliases: &[],
+        usage: &["branches"],
+        description: "Lists the branches, marking the current one.",
+        example: "branches",
+    },
+    CommandDoc {
+        keyword: "switch",
+        aliases: &[],
+        usage: &["switch <branch>"],
+        description: "Switches to another branch.",
+        example: "switch main",
+    },
+    CommandDoc {
+        keyword: "macro",
+        aliases: &[],
+        usage: &["macro <name> = <command>(; <command>)*"],
+        description: "Defines a macro, which is invoked as `<name> <arg>*`, with $1, $2, ... \
+                      replaced by the arguments.",
+        example: "macro deal = say amy $1; agree $last_stmt $2",
+    },
+    CommandDoc {
+        keyword: "macros",
+        aliases: &[],
+        usage: &["macros"],
+        description: "Lists the macros.",
+        example: "macros",
+    },
+    CommandDoc {
+        keyword: "template",
+        aliases: &[],
+        usage: &["template <name> = <payload>"],
+        description: "Defines a payload template, which is said as `say <name> @<template> \
+                      (<param>=<value>)*`, with each $<param> or ${<param>} replaced by its value. \
+                      Write ${<param>} for parameters named like commands in unquoted \
+                      payloads, e.g., ${say}.",
+        example: "template grant = \"$grantor grants $grantee access to $resource.\"",
+    },
+    CommandDoc {
+        keyword: "templates",
+        aliases: &[],
+        usage: &["templates"],
+        description: "Lists the payload templates.",
+        example: "templates",
+    },
+    CommandDoc {
+        keyword: "let",
+        aliases: &[],
+        usage: &["let <name> = <say|sayto|agree|amend|enact command>"],
+        description: "Executes the command, binding the index it creates to the variable $<name>.",
+        example: "let offer = say amy ok.",
+    },
+    CommandDoc {
+        keyword: "vars",
+        aliases: &[],
+        usage: &["vars"],
+        description: "Lists the variables.",
+        example: "vars",
+    },
+    CommandDoc {
+        keyword: "undo",
+        aliases: &[],
+        usage: &["undo"],
+        description: "Reverts the most recent update.",
+        example: "undo",
+    },
+    CommandDoc {
+        keyword: "redo",
+        aliases: &[],
+        usage: &["redo"],
+        description: "Re-applies the most recently undone update.",
+        example: "redo",
+    },
+    CommandDoc {
+        keyword: "comment",
+        aliases: &[],
+        usage: &["comment <text>"],
+        description: "Does nothing. Lines starting with # are comments too.",
+        example: "comment the DMI agent states the rules",
+    },
+    CommandDoc {
+        keyword: "help",
+        aliases: &[],
+        usage: &["help [<command>]"],
+        description: "Lists the commands, or explains one.",
+        example: "help enact",
+    },
+    CommandDoc {
+        keyword: "quit",
+        aliases: &[],
+        usage: &["quit"],
+        description: "Ends the session.",
+        example: "quit",
+    },
+];
+
+/// Whether the word is the keyword of a command.
+pub fn is_keyword(word: &str) -> bool {
+    find(word).is_some()
+}
+
+/// The keywords of all commands, including aliases.
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().flat_map(|doc| std::iter::once(doc.keyword).chain(doc.aliases.iter().copied()))
+}
+
+/// The number of single-character insertions, deletions and substitutions that turn one
+/// word into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The keyword most similar to the unknown word, if any is similar enough to be a typo.
+pub fn suggest(word: &str) -> Option<&'static str> {
+    keywords()
+        .map(|keyword| (edit_distance(word, keyword), keyword))
+        .filter(|&(distance, keyword)| distance <= 2 && distance < keyword.len())
+        .min()
+        .map(|(_, keyword)| keyword)
+}
+
+pub fn print_help() {
+    println!("Commands:");
+    for usage in COMMANDS.iter().flat_map(|doc| doc.usage) {
+        println!("- {}", usage);
+    }
+    println!("- <macro> <arg>*");
+    println!("Lines starting with # are comments. Use help <command> for details.");
+}
+
+fn find(keyword: &str) -> Option<&'static CommandDoc> {
+    COMMANDS.iter().find(|doc| doc.keyword == keyword || doc.aliases.contains(&keyword))
+}
+
+pub fn print_usage(keyword: &str) {
+    for usage in find(keyword).into_iter().flat_map(|doc| doc.usage) {
+        println!("Usage: {}", usage);
+    }
+}
+
+/// Explains the command with the given keyword.
+pub fn print_command_help(keyword: &str) -> Result<(), String> {
+    let doc = find(keyword).ok_or(format!("Unknown command {:?}", keyword))?;
+    for usage in doc.usage {
+        println!("{}", usage);
+    }
+    if !doc.aliases.is_empty() {
+        println!("Also: {}", doc.aliases.join(", "));
+    }
+    println!("{}", doc.description);
+    println!("Example: {}", doc.example);
+    Ok(())
+}
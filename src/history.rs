@@ -0,0 +1,190 @@
+//! The log of accepted commands, as entered by the operator. Unlike the journal,
+//! it also records commands that do not change the state, and is never undone.
+
+use crate::{query::Pattern, Config};
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct Entry {
+    /// Seconds since the UNIX epoch.
+    pub at: u64,
+    pub command: String,
+}
+
+/// Formats seconds since the UNIX epoch as a UTC date and time, e.g., `2025-01-31 12:00:00`.
+fn format_utc(secs: u64) -> String {
+    // Converts days since the epoch to a civil date, after Howard Hinnant
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parses a UTC date and time as formatted by `format_utc` into seconds since the epoch.
+fn parse_utc(s: &str) -> Option<u64> {
+    let (date, time) = s.split_once(' ')?;
+    let date: Vec<i64> = date.splitn(3, '-').map(str::parse).collect::<Result<_, _>>().ok()?;
+    let time: Vec<u64> = time.splitn(3, ':').map(str::parse).collect::<Result<_, _>>().ok()?;
+    let ([year, month, day], [h, m, s]) = (date.as_slice(), time.as_slice()) else {
+        return None;
+    };
+    // Converts a civil date to days since the epoch, after Howard Hinnant
+    let (year, month, day) = (year - (*month <= 2) as i64, *month, *day);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146097 + doe - 719468).ok()?;
+    Some(days * 86400 + h * 3600 + m * 60 + s)
+}
+
+/// Escapes the newlines of a command, e.g., in a heredoc, and thereby backslashes, such that
+/// it takes a single line.
+fn escape(command: &str) -> Cow<'_, str> {
+    if !command.contains(['\\', '\n']) {
+        return Cow::Borrowed(command);
+    }
+    Cow::Owned(command.replace('\\', "\\\\").replace('\n', "\\n"))
+}
+
+/// Reverts `escape`.
+fn unescape(line: &str) -> Cow<'_, str> {
+    if !line.contains('\\') {
+        return Cow::Borrowed(line);
+    }
+    let (mut command, mut chars) = (String::new(), line.chars().peekable());
+    while let Some(c) = chars.next() {
+        let unescaped = match (c, chars.peek()) {
+            ('\\', Some('\\')) => '\\',
+            ('\\', Some('n')) => '\n',
+            _ => {
+                command.push(c);
+                continue;
+            }
+        };
+        chars.next();
+        command.push(unescaped);
+    }
+    Cow::Owned(command)
+}
+
+impl Entry {
+    fn line(&self) -> String {
+        format!("{} | {}", format_utc(self.at), escape(&self.command))
+    }
+
+    /// The entry of a line of a history file, dated at the epoch if it has no timestamp.
+    fn parse(line: &str) -> Self {
+        let command = command_of(line);
+        let at = match command.len() < line.len() {
+            true => parse_utc(&line[..19]).unwrap_or(0),
+            false => 0,
+        };
+        Self { at, command: command.into_owned() }
+    }
+}
+
+impl Config {
+    /// Loads the entries of earlier sessions from the given file, if it exists, and
+    /// additionally appends the entries to it, one per line, from now on.
+    pub fn open_history(&mut self, path: &Path) -> std::io::Result<()> {
+        let file = File::options().create(true).read(true).append(true).open(path)?;
+        let mut earlier = vec![];
+        for line in BufReader::new(&file).lines() {
+            let line = line?;
+            // Line editing kept its own history in the same file before, with this header
+            if !line.trim().is_empty() && line != "#V2" {
+                earlier.push(Entry::parse(&line));
+            }
+        }
+        self.history.splice(0..0, earlier);
+        self.history_file = Some(file);
+        Ok(())
+    }
+
+    pub fn record(&mut self, command: &str) {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let entry = Entry { at, command: command.to_string() };
+        if let Some(file) = &mut self.history_file
+            && let Err(e) = writeln!(file, "{}", entry.line())
+        {
+            println!("Cannot write history, no longer writing it: {}", e);
+            self.history_file = None;
+        }
+        self.history.push(entry);
+    }
+
+    /// The entries of the history, oldest first.
+    pub fn history(&self) -> &[Entry] {
+        &self.history
+    }
+
+    pub fn print_history(&self) {
+        for entry in &self.history {
+            println!("{}", entry.line());
+        }
+    }
+
+    /// Shows the entries with commands matching the pattern, oldest first.
+    pub fn search_history(&self, pattern: &Pattern) {
+        let found: Vec<&Entry> =
+            self.history.iter().filter(|entry| pattern.find(&entry.command).is_some()).collect();
+        if found.is_empty() {
+            println!("No matching commands");
+        }
+        for entry in found {
+            println!("{}", entry.line());
+        }
+    }
+}
+
+/// The command of a line of a history file, or the line itself if it has no timestamp.
+pub fn command_of(line: &str) -> Cow<'_, str> {
+    match line.split_once(" | ") {
+        Some((at, command)) if at.len() == 19 && at.starts_with(|c: char| c.is_ascii_digit()) => {
+            unescape(command)
+        }
+        _ => Cow::Borrowed(line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+
+    #[test]
+    fn keeps_multi_line_commands_on_one_line() {
+        let command = "say amy <<END\nsay \\\"not a command\\\"\nEND";
+        let entry = Entry { at: 1_700_000_000, command: command.to_string() };
+        let line = entry.line();
+        assert!(!line.contains('\n'));
+        let parsed = Entry::parse(&line);
+        assert_eq!((parsed.at, &*parsed.command), (entry.at, command));
+    }
+
+    #[test]
+    fn reads_lines_without_timestamps_verbatim() {
+        let parsed = Entry::parse(r"say amy a\nb.");
+        assert_eq!((parsed.at, &*parsed.command), (0, r"say amy a\nb."));
+    }
+}
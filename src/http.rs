@@ -0,0 +1,177 @@
+//! A REST interface over HTTP/1.1, for web frontends and scripts that cannot speak
+//! the REPL grammar. It exposes the methods of the JSON-RPC interface as resources:
+//! - `GET /state`, `/statements`, `/agreements` and `/actions` return (part of) the state;
+//! - `GET /dump` returns the trace;
+//! - `POST /<update>`, e.g., `POST /say`, applies an update given as a JSON object.
+//!
+//! Requests are not authenticated, and act as the operator, i.e., as any agent. The server
+//! therefore listens on the loopback interface only, unless given another host explicitly.
+
+use crate::{
+    rpc::{self, Caller},
+    Config,
+};
+use serde_json::Value;
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Bodies are small JSON objects, so anything larger is refused.
+const MAX_BODY_BYTES: usize = 1 << 20;
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_request(r: &mut impl BufRead) -> Result<Request, String> {
+    let mut line = String::new();
+    r.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(format!("malformed request line {:?}", line.trim_end()));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut len = 0;
+    loop {
+        line.clear();
+        r.read_line(&mut line).map_err(|e| e.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            len = value.trim().parse().map_err(|_| format!("bad content length {:?}", value))?;
+        }
+    }
+    if len > MAX_BODY_BYTES {
+        return Err(format!("body of {} bytes is too large", len));
+    }
+    let mut body = vec![0; len];
+    r.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(Request { method, path, body })
+}
+
+/// Handles the request, returning the status code and the body of the response.
+fn handle(config: &Mutex<Config>, request: &Request) -> (u16, Value) {
+    // A request that panicked leaves the state as it was left, which later ones can still use
+    let mut config = config.lock().unwrap_or_else(PoisonError::into_inner);
+    let result = match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("GET", "/state") => config.call("state", Value::Null, &mut Caller::Operator),
+        ("GET", path @ ("/statements" | "/agreements" | "/actions")) => {
+            let state = config.call("state", Value::Null, &mut Caller::Operator);
+            state.map(|state| state.get(&path[1..]).cloned().unwrap_or(Value::Null))
+        }
+        ("GET", "/dump") => config.call("dump", Value::Null, &mut Caller::Operator),
+        ("POST", path) if path.len() > 1 => match serde_json::from_slice(&request.body) {
+            Ok(params) => config.call(&path[1..], params, &mut Caller::Operator),
+            Err(e) => return (400, Value::String(format!("Invalid JSON body: {}", e))),
+        },
+        _ => return (404, Value::String("No such resource".to_string())),
+    };
+    match result {
+        Ok(Value::Null) => (204, Value::Null),
+        Ok(value) => (200, value),
+        Err(e) => {
+            let status = match e.code {
+                rpc::METHOD_NOT_FOUND => 404,
+                rpc::INVALID_PARAMS => 400,
+                rpc::REJECTED => 409,
+                _ => 500,
+            };
+            (status, Value::String(e.message))
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = match status {
+        204 => String::new(),
+        200 => serde_json::to_string(body).expect("values are serializable"),
+        _ => serde_json::to_string(&serde_json::json!({ "error": body }))
+            .expect("values are serializable"),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// The address to listen on, on the loopback interface if only a port is given, e.g.,
+/// `8080` or `:8080`.
+fn bind_addr(addr: &str) -> String {
+    match addr.strip_prefix(':').unwrap_or(addr).parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => addr.to_string(),
+    }
+}
+
+/// Serves one request per connection, with concurrent connections sharing the state.
+pub fn serve(config: Config, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(bind_addr(addr))?;
+    let local = listener.local_addr()?;
+    println!("Serving HTTP on {}", local);
+    if !local.ip().is_loopback() {
+        println!(
+            "Warning: requests are not authenticated, so anyone who can reach {} can act as \
+             any agent",
+            local
+        );
+    }
+    let config = Arc::new(Mutex::new(config));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Cannot accept connection: {}", e);
+                continue;
+            }
+        };
+        let config = config.clone();
+        std::thread::spawn(move || {
+            let request = stream
+                .try_clone()
+                .map_err(|e| e.to_string())
+                .and_then(|input| read_request(&mut BufReader::new(input)));
+            let (status, body) = match &request {
+                Ok(request) => handle(&config, request),
+                Err(e) => (400, Value::String(e.clone())),
+            };
+            if let Err(e) = respond(&mut stream, status, &body) {
+                println!("Cannot respond: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bind_addr;
+
+    #[test]
+    fn binds_to_loopback_unless_given_a_host() {
+        assert_eq!(bind_addr("8080"), "127.0.0.1:8080");
+        assert_eq!(bind_addr(":8080"), "127.0.0.1:8080");
+        assert_eq!(bind_addr("0.0.0.0:8080"), "0.0.0.0:8080");
+        assert_eq!(bind_addr("localhost:8080"), "localhost:8080");
+    }
+}
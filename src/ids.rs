@@ -0,0 +1,90 @@
+//! Schemes for identifying statements, i.e., for the numbers in the identifiers of the
+//! messages that agents state.
+//!
+//! Hashed numbers have the highest bit set, and counted ones do not, such that they never
+//! clash. As hashes of 31 bits do clash in large sessions, statements whose identifier is
+//! taken already are refused.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// Numbers statements in the order in which they are said, like their indices.
+    #[default]
+    Counter,
+    /// Derives the number from the sayer and payload, such that the same statement has the
+    /// same identifier in every session, and traces of different sessions can be merged.
+    Hash,
+}
+
+/// The bit that is set in hashed numbers only.
+const HASHED: u32 = 1 << 31;
+
+impl IdScheme {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "counter" => Some(Self::Counter),
+            "hash" => Some(Self::Hash),
+            _ => None,
+        }
+    }
+
+    /// The number identifying the statement with the given index, sayer and payload, if
+    /// there are numbers left.
+    pub fn message_id(self, idx: usize, sayer: &str, payload: &str) -> Option<u32> {
+        match self {
+            Self::Counter => u32::try_from(idx).ok().filter(|id| id & HASHED == 0),
+            Self::Hash => {
+                let hash = fnv1a_64(&[sayer.as_bytes(), &[0], payload.as_bytes()].concat());
+                Some(HASHED | ((hash >> 32) ^ hash) as u32)
+            }
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, which, unlike the standard hasher, is stable across releases.
+pub(crate) fn fnv1a_64(bytes: &[u8]) -> u64 {
+    (bytes.iter())
+        .fold(0xcbf29ce484222325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, UpdateCmd};
+
+    fn say(config: &mut Config, sayer: &str, payload: &str) -> Result<(), String> {
+        let (sayer, payload) = (sayer.to_string(), payload.into());
+        config.try_update(UpdateCmd::Say {
+            sayer,
+            payload,
+            label: None,
+            recipients: None,
+            source: None,
+        })
+    }
+
+    #[test]
+    fn hashes_apart_from_counters() {
+        let hashed = IdScheme::Hash.message_id(0, "amy", "ok.").unwrap();
+        assert_ne!(hashed & HASHED, 0);
+        assert_eq!(IdScheme::Hash.message_id(7, "amy", "ok."), Some(hashed));
+        assert_ne!(IdScheme::Hash.message_id(0, "bob", "ok."), Some(hashed));
+        assert_eq!(IdScheme::Counter.message_id(7, "amy", "ok."), Some(7));
+        assert_eq!(IdScheme::Counter.message_id(HASHED as usize, "amy", "ok."), None);
+    }
+
+    #[test]
+    fn refuses_statements_with_taken_identifiers() {
+        let mut config = Config::default();
+        config.try_update(UpdateCmd::IdScheme { scheme: IdScheme::Hash }).unwrap();
+        say(&mut config, "amy", "ok.").unwrap();
+        assert!(say(&mut config, "amy", "ok.").is_err());
+        say(&mut config, "bob", "ok.").unwrap();
+        config.try_update(UpdateCmd::IdScheme { scheme: IdScheme::Counter }).unwrap();
+        say(&mut config, "amy", "ok.").unwrap();
+        assert_eq!(config.statements.len(), 3);
+        assert_eq!(config.stmt_idx(&config.statements[2]), Some(2));
+    }
+}
@@ -0,0 +1,345 @@
+//! The inboxes of agents: the statements delivered to them, which are the statements they
+//! can know of besides their own. Statements to all agents are delivered to every agent as
+//! they are said, while statements to particular recipients are delivered to those only,
+//! until agents forward what they know to others by syncing with them.
+//!
+//! While the agents are partitioned, statements are not delivered across the partition,
+//! neither as they are said nor by syncing. Statements that were not delivered stay so
+//! after healing, until an agent that knows of them syncs with the agents cut off.
+//!
+//! Links from one agent to another may delay the statements said over them, which are then
+//! delivered once due, or drop them. Whether a link drops a statement is drawn from the
+//! seed, the statement and the recipient, such that replaying the state drops the same.
+
+use crate::{ids, labelled, render, trucated, Config, StmtIdx, Time};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A statement delivered by an agent other than its sayer.
+#[derive(Debug, Clone)]
+pub struct Forward {
+    pub stmt: StmtIdx,
+    pub from: String,
+    pub to: String,
+    pub at: Time,
+}
+
+/// How statements are delivered from one agent to another. The default delivers them
+/// as they are said.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Link {
+    pub delay: Time,
+    /// The probability with which statements are dropped.
+    pub drop: f64,
+}
+
+/// The key of the draw of whether the statement is dropped on its way to the recipient.
+fn drop_key(stmt: StmtIdx, to: &str) -> u64 {
+    ids::fnv1a_64(&[&(stmt as u64).to_be_bytes(), to.as_bytes()].concat())
+}
+
+impl Config {
+    /// Delivers the statement to the agent at the given time, unless it has been earlier.
+    fn deliver(&mut self, stmt: StmtIdx, to: &str, at: Time) {
+        let inbox = self.inboxes.entry(to.to_string()).or_default();
+        inbox.entry(stmt).and_modify(|t| *t = at.min(*t)).or_insert(at);
+    }
+
+    /// The group of agents partitioned from the agent, if any.
+    fn partitioned_from(&self, agent: &str) -> Option<&BTreeSet<String>> {
+        let [a, b] = self.partition.as_ref()?;
+        match (a.contains(agent), b.contains(agent)) {
+            (true, _) => Some(b),
+            (_, true) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Whether statements can be delivered from one agent to the other.
+    pub fn reachable(&self, from: &str, to: &str) -> bool {
+        self.partitioned_from(from).is_none_or(|group| !group.contains(to))
+    }
+
+    /// Delivers the last said statement to its recipients, except those partitioned from
+    /// its sayer or that the links to drop it, which are cut off from it, and those that
+    /// the links to delay it, which it is delivered to once due.
+    pub(crate) fn deliver_said(&mut self) {
+        let stmt = self.statements.len() - 1;
+        let sayer = self.statements[stmt].id.0.clone();
+        let partitioned = self.partitioned_from(&sayer).cloned().unwrap_or_default();
+        let targeted = self.recipients.contains_key(&stmt);
+        // Statements to all agents only need delivering to those partitioned or linked
+        let recipients: BTreeSet<String> = match self.recipients.get(&stmt) {
+            Some(recipients) => recipients.iter().cloned().collect(),
+            None => (self.links.keys())
+                .filter(|(from, _)| *from == sayer)
+                .map(|(_, to)| to.clone())
+                .chain(partitioned.iter().cloned())
+                .collect(),
+        };
+        let (mut cut_off, mut delayed) = (BTreeSet::new(), BTreeMap::new());
+        for recipient in recipients {
+            let link = self.links.get(&(sayer.clone(), recipient.clone()));
+            let Link { delay, drop } = link.copied().unwrap_or_default();
+            if partitioned.contains(&recipient)
+                || self.rng.chance_of(drop_key(stmt, &recipient), drop)
+            {
+                cut_off.insert(recipient);
+            } else if delay > 0 {
+                // Delays beyond the end of time deliver never, in effect
+                let due = self.current.saturating_add(delay);
+                self.deliver(stmt, &recipient, due);
+                delayed.insert(recipient, due);
+            } else if targeted {
+                self.deliver(stmt, &recipient, self.current);
+            }
+        }
+        if !cut_off.is_empty() {
+            self.cut_off.insert(stmt, cut_off);
+        }
+        if !delayed.is_empty() {
+            self.delayed.insert(stmt, delayed);
+        }
+    }
+
+    /// Whether the statement was not delivered to the agent as it was said, and will not be
+    /// unless forwarded.
+    pub fn is_cut_off(&self, agent: &str, stmt: StmtIdx) -> bool {
+        self.cut_off.get(&stmt).is_some_and(|agents| agents.contains(agent))
+    }
+
+    /// Whether the statement is delivered to the agent later than it was said.
+    pub fn is_delayed(&self, agent: &str, stmt: StmtIdx) -> bool {
+        self.delayed.get(&stmt).is_some_and(|agents| agents.contains_key(agent))
+    }
+
+    /// The time at which the agent received the statement, if it did by now.
+    pub fn received_at(&self, agent: &str, stmt: StmtIdx) -> Option<Time> {
+        let held = self.recipients.contains_key(&stmt)
+            || self.is_cut_off(agent, stmt)
+            || self.is_delayed(agent, stmt);
+        match held {
+            true => self.inboxes.get(agent)?.get(&stmt).copied().filter(|&at| at <= self.current),
+            false => Some(self.stated_at[stmt]),
+        }
+    }
+
+    /// The recipients that the statement was delivered to as it was said, or `None` if it
+    /// was delivered to all agents. A statement to all agents that some were cut off from,
+    /// or delayed to, was delivered to the other agents that take part in the state.
+    pub fn delivered_to(&self, stmt: StmtIdx) -> Option<Vec<String>> {
+        let sayer = &self.statements[stmt].id.0;
+        let immediate = |a: &String| !self.is_cut_off(a, stmt) && !self.is_delayed(a, stmt);
+        match self.recipients.get(&stmt) {
+            Some(recipients) => Some(recipients.iter().filter(|r| immediate(r)).cloned().collect()),
+            None if !self.cut_off.contains_key(&stmt) && !self.delayed.contains_key(&stmt) => None,
+            None => Some(
+                (self.participants().into_iter()).filter(|a| a != sayer && immediate(a)).collect(),
+            ),
+        }
+    }
+
+    /// The delayed deliveries that are due by now, as statements and their recipients, in
+    /// the order of the statements. Deliveries that were preceded by forwards are left out.
+    pub fn due(&self) -> impl Iterator<Item = (StmtIdx, &str, Time)> {
+        (self.delayed.iter())
+            .flat_map(|(&stmt, agents)| agents.iter().map(move |(to, &at)| (stmt, to.as_str(), at)))
+            .filter(|&(stmt, to, at)| self.received_at(to, stmt) == Some(at))
+    }
+
+    /// Sets how statements are delivered from one agent to the other. A link without delay
+    /// or drop probability delivers them as they are said, as if there was none.
+    pub(crate) fn link(
+        &mut self,
+        from: &str,
+        to: &str,
+        delay: Time,
+        drop: f64,
+    ) -> Result<(), String> {
+        self.check_agent(from)?;
+        self.check_agent(to)?;
+        if from == to {
+            return Err(format!("Agent {} cannot link to itself", from));
+        }
+        let key = (from.to_string(), to.to_string());
+        match (Link { delay, drop }) {
+            link if link == Link::default() => self.links.remove(&key),
+            link => self.links.insert(key, link),
+        };
+        Ok(())
+    }
+
+    pub fn print_links(&self) {
+        if self.links.is_empty() {
+            println!("No links delay or drop statements");
+            return;
+        }
+        println!("___from____|____to_____|__delay___|___drop___ LINKS");
+        for ((from, to), link) in &self.links {
+            println!("{: >10} | {: <9} | {: >8} | {}", from, to, link.delay, link.drop);
+        }
+    }
+
+    /// The agents that take part in the state: those registered, partitioned, or that
+    /// said, received or enacted anything.
+    fn participants(&self) -> BTreeSet<String> {
+        let mut agents: BTreeSet<String> = self.agents.keys().cloned().collect();
+        agents.extend(self.partition.iter().flatten().flatten().cloned());
+        agents.extend(self.statements.iter().map(|s| s.id.0.clone()));
+        agents.extend(self.recipients.values().flatten().cloned());
+        agents.extend(self.cut_off.values().flatten().cloned());
+        agents.extend(self.links.keys().flat_map(|(from, to)| [from.clone(), to.clone()]));
+        agents.extend(self.enacted.iter().map(|e| e.id.0.clone()));
+        agents
+    }
+
+    /// Partitions the agents into the two groups, replacing any partition before.
+    pub(crate) fn partition(&mut self, groups: &[Vec<String>; 2]) -> Result<(), String> {
+        for agent in groups.iter().flatten() {
+            self.check_agent(agent)?;
+        }
+        let [a, b] = groups.clone().map(|group| group.into_iter().collect::<BTreeSet<_>>());
+        if let Some(agent) = a.intersection(&b).next() {
+            return Err(format!("Agent {} cannot be on both sides of the partition", agent));
+        }
+        self.partition = Some([a, b]);
+        Ok(())
+    }
+
+    pub fn print_partition(&self) {
+        match &self.partition {
+            Some([a, b]) => println!(
+                "{} | {}",
+                a.iter().cloned().collect::<Vec<_>>().join(" "),
+                b.iter().cloned().collect::<Vec<_>>().join(" ")
+            ),
+            None => println!("The agents are not partitioned"),
+        }
+    }
+
+    /// The statements of other agents that the agent received, with the times at which it
+    /// received them, in the order in which they were said.
+    pub fn inbox(&self, agent: &str) -> Vec<(StmtIdx, Time)> {
+        (0..self.statements.len())
+            .filter(|&i| self.statements[i].id.0 != agent)
+            .filter_map(|i| self.received_at(agent, i).map(|at| (i, at)))
+            .collect()
+    }
+
+    /// Delivers the unretracted statements that `from` knows of to `to`, as gossip, which
+    /// fails if there are none that `to` has not received yet.
+    pub(crate) fn sync(&mut self, from: &str, to: &str) -> Result<(), String> {
+        self.check_agent(from)?;
+        self.check_agent(to)?;
+        if from == to {
+            return Err(format!("Agent {} cannot sync with itself", from));
+        } else if !self.reachable(from, to) {
+            return Err(format!("Agent {} cannot reach {} across the partition", from, to));
+        }
+        let news: Vec<StmtIdx> = (0..self.statements.len())
+            .filter(|&i| !self.retracted.contains(&i))
+            .filter(|&i| {
+                let sayer = &self.statements[i].id.0;
+                (sayer == from || self.received_at(from, i).is_some())
+                    && sayer != to
+                    && self.received_at(to, i).is_none()
+            })
+            .collect();
+        if news.is_empty() {
+            return Err(format!("{} knows of no statements that {} has not received", from, to));
+        }
+        for stmt in news {
+            self.deliver(stmt, to, self.current);
+            let (from, to, at) = (from.to_string(), to.to_string(), self.current);
+            self.forwarded.push(Forward { stmt, from, to, at });
+        }
+        Ok(())
+    }
+
+    pub fn print_inbox(&self, agent: &str) {
+        let inbox = self.inbox(agent);
+        if inbox.is_empty() {
+            println!("{} has received no statements", agent);
+            return;
+        }
+        println!("__stmt.id__|___sayer___|_received_|___payload___ INBOX OF {}", agent);
+        for (i, at) in inbox {
+            let s = &self.statements[i];
+            let [a, b] = trucated(&s.payload);
+            let retracted = if self.retracted.contains(&i) { " (retracted)" } else { "" };
+            let row = format!(
+                "{: >8} | {: <9} | {: >8} | {:?}{}{}",
+                labelled(&self.stmt_labels, i),
+                s.id.0,
+                at,
+                a,
+                b,
+                retracted
+            );
+            println!("{}", render::agent(&s.id.0, &row));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UpdateCmd;
+
+    fn say(config: &mut Config, sayer: &str, payload: &str) {
+        let (sayer, payload) = (sayer.to_string(), payload.into());
+        let say = UpdateCmd::Say { sayer, payload, label: None, recipients: None, source: None };
+        config.try_update(say).unwrap();
+    }
+
+    fn link(config: &mut Config, delay: Time, drop: f64) {
+        let (from, to) = ("amy".to_string(), "bob".to_string());
+        config.try_update(UpdateCmd::Link { from, to, delay, drop }).unwrap();
+    }
+
+    #[test]
+    fn delivers_delayed_statements_once_due() {
+        let mut config = Config::default();
+        link(&mut config, 2, 0.0);
+        say(&mut config, "amy", "ok.");
+        assert_eq!(config.received_at("bob", 0), None);
+        config.try_update(UpdateCmd::Now { now: 2, tick: false }).unwrap();
+        assert_eq!(config.received_at("bob", 0), Some(2));
+        assert_eq!(config.due().collect::<Vec<_>>(), [(0, "bob", 2)]);
+    }
+
+    #[test]
+    fn saturates_delays_beyond_the_end_of_time() {
+        let mut config = Config::default();
+        link(&mut config, Time::MAX, 0.0);
+        config.try_update(UpdateCmd::Now { now: 1, tick: false }).unwrap();
+        say(&mut config, "amy", "ok.");
+        assert!(config.is_delayed("bob", 0));
+        assert_eq!(config.received_at("bob", 0), None);
+    }
+
+    #[test]
+    fn drops_certainly_and_never() {
+        let mut config = Config::default();
+        link(&mut config, 0, 1.0);
+        say(&mut config, "amy", "ok.");
+        assert!(config.is_cut_off("bob", 0));
+        link(&mut config, 0, 0.0);
+        say(&mut config, "amy", "ok.");
+        assert!(!config.is_cut_off("bob", 1));
+    }
+
+    #[test]
+    fn cuts_off_across_partitions() {
+        let mut config = Config::default();
+        let groups = [vec!["amy".to_string()], vec!["bob".to_string()]];
+        config.try_update(UpdateCmd::Partition { groups }).unwrap();
+        say(&mut config, "amy", "ok.");
+        assert!(config.is_cut_off("bob", 0));
+        assert!(!config.reachable("amy", "bob"));
+    }
+
+    #[test]
+    fn draws_drops_by_stable_keys() {
+        assert_eq!(drop_key(3, "bob"), 0x104dbec3a8cf08e7);
+    }
+}
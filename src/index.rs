@@ -0,0 +1,90 @@
+//! Secondary indices of the state, such that looking statements up by their message, sayer
+//! or payload, or what uses them, takes no scan of all statements, agreements and actions,
+//! as on imported traces of tens of thousands of statements.
+//!
+//! The indices are maintained as statements, agreements and actions are added, and cleared
+//! along with them when the state is replayed. Retracted statements stay indexed.
+
+use crate::{AgreeIdx, Config, StmtIdx};
+use justact_prototype::wire::{Action, Message};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+#[derive(Debug, Default)]
+pub struct Index {
+    /// The statement of each message identifier, as no two statements share one.
+    by_id: HashMap<(String, u32), StmtIdx>,
+    /// The statements of each sayer, in order.
+    by_sayer: HashMap<String, Vec<StmtIdx>>,
+    /// The statements with each hash of a payload, in order.
+    by_payload: HashMap<u64, Vec<StmtIdx>>,
+    /// The agreements on each message, in order.
+    agreements_on: HashMap<(String, u32), Vec<AgreeIdx>>,
+    /// The actions based on or justified by each message, in order.
+    actions_using: HashMap<(String, u32), Vec<usize>>,
+}
+
+fn payload_hash(payload: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    payload.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Index {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn add_statement(&mut self, idx: StmtIdx, msg: &Message) {
+        self.by_id.entry(msg.id.clone()).or_insert(idx);
+        self.by_sayer.entry(msg.id.0.clone()).or_default().push(idx);
+        self.by_payload.entry(payload_hash(&msg.payload)).or_default().push(idx);
+    }
+
+    pub fn add_agreement(&mut self, idx: AgreeIdx, on: &Message) {
+        self.agreements_on.entry(on.id.clone()).or_default().push(idx);
+    }
+
+    pub fn add_action(&mut self, idx: usize, action: &Action) {
+        let uses = std::iter::once(&action.basis.message).chain(action.justification.iter());
+        for msg in uses {
+            let actions = self.actions_using.entry(msg.id.clone()).or_default();
+            // A message may be both the basis and part of the justification
+            if actions.last() != Some(&idx) {
+                actions.push(idx);
+            }
+        }
+    }
+}
+
+impl Config {
+    /// The index of the statement with the given message identifier, if any.
+    pub fn stmt_idx_by_id(&self, id: &(String, u32)) -> Option<StmtIdx> {
+        self.index.by_id.get(id).copied()
+    }
+
+    /// The statements said by the agent, in order.
+    pub fn said_by(&self, sayer: &str) -> &[StmtIdx] {
+        self.index.by_sayer.get(sayer).map_or(&[], Vec::as_slice)
+    }
+
+    /// The statements with the given payload, in order.
+    pub fn with_payload<'a>(&'a self, payload: &'a str) -> impl Iterator<Item = StmtIdx> + 'a {
+        let candidates = self.index.by_payload.get(&payload_hash(payload));
+        // Payloads of the same hash are not necessarily the same
+        (candidates.into_iter().flatten().copied())
+            .filter(move |&i| self.statements[i].payload == payload)
+    }
+
+    /// The agreements on the message, in order.
+    pub fn agreements_on(&self, msg: &Message) -> &[AgreeIdx] {
+        self.index.agreements_on.get(&msg.id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The actions based on or justified by the message, in order.
+    pub fn actions_using(&self, msg: &Message) -> &[usize] {
+        self.index.actions_using.get(&msg.id).map_or(&[], Vec::as_slice)
+    }
+}
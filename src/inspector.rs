@@ -0,0 +1,370 @@
+//! Connecting to inspectors, which are either programs reading the trace on stdin or
+//! listening on a socket, and keeping a live one that is sent the events of each update
+//! as they happen, rather than the whole trace whenever inspecting.
+
+use crate::{query::Section, AgreeIdx, Config, Ref, StmtIdx, Time};
+use justact_prototype::{
+    auditing::{Event, EventControl, EventData},
+    spec::collections::Recipient,
+};
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    io::{ErrorKind, Write},
+    net::TcpStream,
+    process::{Child, Command, Stdio},
+};
+
+/// Where an inspector listens, if it is not a program to run.
+enum Address<'a> {
+    Tcp(&'a str),
+    Unix(&'a str),
+}
+
+impl<'a> Address<'a> {
+    /// Parses inspector commands of the form `tcp://<host>:<port>` or `unix://<path>`.
+    fn parse(command: &'a [String]) -> Option<Self> {
+        let [target] = command else {
+            return None;
+        };
+        if let Some(addr) = target.strip_prefix("tcp://") {
+            Some(Self::Tcp(addr))
+        } else {
+            target.strip_prefix("unix://").map(Self::Unix)
+        }
+    }
+}
+
+/// A connection to an inspector, which digests the events written to it.
+pub(crate) struct Connection {
+    /// The inspector process, unless connected over a socket.
+    child: Option<Child>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Connection {
+    /// Ends the trace, waiting for the inspector process to exit, if any.
+    fn finish(self) -> std::io::Result<()> {
+        drop(self.writer);
+        if let Some(mut child) = self.child {
+            child.wait()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the events as JSON lines, as the inspector reads them.
+fn write_events<'a>(
+    w: &mut dyn Write,
+    events: impl IntoIterator<Item = Event<'a>>,
+) -> std::io::Result<()> {
+    for event in events {
+        writeln!(w, "{}", serde_json::to_string(&event)?)?;
+    }
+    w.flush()
+}
+
+#[cfg(unix)]
+fn connect_unix(path: &str) -> std::io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+}
+
+#[cfg(not(unix))]
+fn connect_unix(_: &str) -> std::io::Result<Box<dyn Write + Send>> {
+    Err(std::io::Error::new(ErrorKind::Unsupported, "no Unix sockets on this platform"))
+}
+
+/// How much of the state has been sent to an inspector.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Progress {
+    /// The time last sent, if any.
+    at: Option<Time>,
+    statements: usize,
+    agreements: usize,
+    actions: usize,
+    retracted: usize,
+    forwarded: usize,
+    reads: usize,
+}
+
+/// Selects the events of a trace. The default selects all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    /// Only selects the events of these sections, if any.
+    pub only: Option<Vec<Section>>,
+    /// Only selects statements said, agreements made, and actions enacted at or after
+    /// this time.
+    pub since: Option<Time>,
+    /// Only selects the events of these statements, agreements and actions, if any.
+    pub focus: Option<Focus>,
+}
+
+/// A statement, agreement or action, along with those it depends on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Focus {
+    statements: BTreeSet<StmtIdx>,
+    agreements: BTreeSet<AgreeIdx>,
+    actions: BTreeSet<usize>,
+}
+
+impl EventFilter {
+    pub(crate) fn keeps(&self, section: Section, idx: usize, at: Time) -> bool {
+        self.only.as_ref().is_none_or(|only| only.contains(&section))
+            && self.since.is_none_or(|t| at >= t)
+            && self.focus.as_ref().is_none_or(|focus| match section {
+                Section::Statements => focus.statements.contains(&idx),
+                Section::Agreements => focus.agreements.contains(&idx),
+                Section::Actions => focus.actions.contains(&idx),
+            })
+    }
+}
+
+pub(crate) struct Live {
+    connection: Connection,
+    filter: EventFilter,
+    sent: Progress,
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.connection.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Config {
+    /// The given statement, agreement or action, along with the statement of an agreement,
+    /// or the basis agreement and justification statements of an action.
+    pub(crate) fn focus(&self, section: Section, target: &Ref) -> Result<Focus, String> {
+        let mut focus = Focus::default();
+        match (section, target) {
+            (Section::Statements, target) => {
+                let idx = target.resolve(&self.stmt_labels)?;
+                if idx >= self.statements.len() {
+                    return Err(format!("Cannot inspect unsaid message {}", idx));
+                }
+                focus.statements.insert(idx);
+            }
+            (Section::Agreements, target) => {
+                let idx = target.resolve(&self.agree_labels)?;
+                let a = (self.agreements.get(idx))
+                    .ok_or(format!("Cannot inspect unmade agreement {}", idx))?;
+                focus.agreements.insert(idx);
+                focus.statements.extend(self.stmt_idx(&a.message));
+            }
+            (Section::Actions, Ref::Idx(idx)) => {
+                let e = (self.enacted.get(*idx))
+                    .ok_or(format!("Cannot inspect unenacted action {}", idx))?;
+                focus.actions.insert(*idx);
+                focus.agreements.insert(self.basis_idx(*idx));
+                focus.statements.extend(self.stmt_idx(&e.basis.message));
+                focus.statements.extend(e.justification.iter().filter_map(|m| self.stmt_idx(m)));
+            }
+            (Section::Actions, Ref::Label(label)) => {
+                return Err(format!("Cannot inspect action {}, as actions have no labels", label));
+            }
+        }
+        Ok(focus)
+    }
+
+    /// The selected events of the state that were not sent yet, as far as it has
+    /// progressed. The time is not filtered, as the other events happen at it.
+    pub(crate) fn events_after<'a>(
+        &'a self,
+        sent: &Progress,
+        filter: &'a EventFilter,
+    ) -> impl Iterator<Item = Event<'static>> + use<'a> {
+        let stated = (sent.statements..self.statements.len())
+            .filter(|i| !self.retracted.contains(i))
+            .filter(|&i| filter.keeps(Section::Statements, i, self.stated_at[i]))
+            .map(|i| (i, &self.statements[i]));
+        // Statements to multiple recipients are stated to each of them separately
+        let stated = stated.flat_map(|(i, s)| {
+            let to: Vec<Recipient<_>> = match self.delivered_to(i) {
+                // Statements delivered to no one but their sayers are stated to those
+                Some(recipients) if recipients.is_empty() => {
+                    vec![Recipient::One(s.id.0.clone().into())]
+                }
+                Some(recipients) => {
+                    recipients.into_iter().map(|r| Recipient::One(r.into())).collect()
+                }
+                None => vec![Recipient::All],
+            };
+            to.into_iter().map(|to| EventControl::StateMessage {
+                who: s.id.clone().0.into(),
+                to,
+                msg: s.clone(),
+            })
+        });
+        // Statements forwarded by agents other than their sayers are stated by those agents
+        let forwarded = (sent.forwarded..self.forwarded.len())
+            .map(|i| &self.forwarded[i])
+            .filter(|f| !self.retracted.contains(&f.stmt))
+            .filter(|f| filter.keeps(Section::Statements, f.stmt, f.at))
+            .map(|f| EventControl::StateMessage {
+                who: f.from.clone().into(),
+                to: Recipient::One(f.to.clone().into()),
+                msg: self.statements[f.stmt].clone(),
+            });
+        // Delayed statements are stated to their recipients anew, by their sayers, once due
+        let (sent_statements, sent_at) = (sent.statements, sent.at);
+        let delivered = self
+            .due()
+            .filter(move |&(stmt, _, at)| {
+                stmt >= sent_statements || sent_at.is_none_or(|sent_at| at > sent_at)
+            })
+            .filter(|&(stmt, _, _)| !self.retracted.contains(&stmt))
+            .filter(|&(stmt, _, _)| filter.keeps(Section::Statements, stmt, self.stated_at[stmt]))
+            .map(|(stmt, to, _)| EventControl::StateMessage {
+                who: self.statements[stmt].id.0.clone().into(),
+                to: Recipient::One(to.to_string().into()),
+                msg: self.statements[stmt].clone(),
+            });
+        let advanced = (sent.at != Some(self.current))
+            .then_some(EventControl::AdvanceTime { timestamp: self.current });
+        let iter = advanced
+            .into_iter()
+            .chain(stated)
+            .chain(delivered)
+            .chain(forwarded)
+            .chain(
+                (sent.agreements..self.agreements.len())
+                    .map(|i| (i, &self.agreements[i]))
+                    .filter(|(i, a)| filter.keeps(Section::Agreements, *i, a.at))
+                    .map(|(_, a)| EventControl::AddAgreement { agree: a.clone() }),
+            )
+            .chain(
+                (sent.actions..self.enacted.len())
+                    .filter(|&i| filter.keeps(Section::Actions, i, self.enacted_at[i]))
+                    .map(|i| &self.enacted[i])
+                    .map(|e| EventControl::EnactAction {
+                        who: e.id.0.clone().into(),
+                        to: Recipient::All,
+                        action: e.clone(),
+                    }),
+            );
+        // Reads are data events, selected along with the actions enabling them
+        let reads = (sent.reads..self.reads.len())
+            .map(|i| &self.reads[i])
+            .filter(|r| filter.keeps(Section::Actions, r.via, r.at))
+            .map(|r| EventData::Read {
+                who: r.reader.clone().into(),
+                id: Cow::Owned((r.owner.clone(), r.dataset.clone())),
+                context: Cow::Owned(self.enacted[r.via].id.clone()),
+                contents: None,
+            });
+        iter.map(Event::Control).chain(reads.map(Event::Data))
+    }
+
+    fn progress(&self) -> Progress {
+        Progress {
+            at: Some(self.current),
+            statements: self.statements.len(),
+            agreements: self.agreements.len(),
+            actions: self.enacted.len(),
+            retracted: self.retracted.len(),
+            forwarded: self.forwarded.len(),
+            reads: self.reads.len(),
+        }
+    }
+
+    /// Runs the inspector, or connects to it if it listens on a socket.
+    fn connect_inspector(&self) -> std::io::Result<Connection> {
+        let writer = match Address::parse(&self.inspector) {
+            Some(Address::Tcp(addr)) => Box::new(TcpStream::connect(addr)?),
+            Some(Address::Unix(path)) => connect_unix(path)?,
+            None => {
+                let [program, args @ ..] = &self.inspector[..] else {
+                    return Err(std::io::Error::other("no inspector configured"));
+                };
+                let mut child = Command::new(program).args(args).stdin(Stdio::piped()).spawn()?;
+                let stdin = child.stdin.take().ok_or_else(|| std::io::Error::other("no stdin"))?;
+                return Ok(Connection { child: Some(child), writer: Box::new(stdin) });
+            }
+        };
+        Ok(Connection { child: None, writer })
+    }
+
+    /// Sends the selected trace to the inspector, and waits for it to finish if it is a
+    /// program.
+    pub(crate) fn run_inspection(&self, filter: &EventFilter) -> std::io::Result<()> {
+        let mut connection = self.connect_inspector()?;
+        write_events(&mut connection.writer, self.inspection_events(filter))?;
+        connection.finish()?;
+        println!("ok, let's continue");
+        Ok(())
+    }
+
+    /// (Re)starts the live inspector, sending it the selected trace.
+    pub(crate) fn start_live_inspector(&mut self, filter: EventFilter) -> std::io::Result<()> {
+        // Stops any running inspector first
+        self.live_inspector = None;
+        let connection = self.connect_inspector()?;
+        let mut live = Live { connection, filter, sent: Progress::default() };
+        write_events(&mut live.connection.writer, self.events_after(&live.sent, &live.filter))?;
+        live.sent = self.progress();
+        self.live_inspector = Some(live);
+        Ok(())
+    }
+
+    pub(crate) fn stop_live_inspector(&mut self) -> Result<(), String> {
+        match self.live_inspector.take() {
+            Some(_) => Ok(()),
+            None => Err("There is no live inspector".into()),
+        }
+    }
+
+    /// Sends the live inspector, if any, the events since it was last sent any. Restarts it
+    /// if it exited, or if the state went back in a way that the events cannot express,
+    /// e.g., by undoing or retracting.
+    pub(crate) fn feed_live_inspector(&mut self) -> std::io::Result<()> {
+        let Some(mut live) = self.live_inspector.take() else {
+            return Ok(());
+        };
+        if let Some(status) =
+            live.connection.child.as_mut().map(Child::try_wait).transpose()?.flatten()
+        {
+            println!("Inspector exited ({}), restarting it", status);
+            return self.restart_live_inspector(live);
+        }
+        let (sent, now) = (&live.sent, self.progress());
+        let regressed = now.at < sent.at
+            || now.statements < sent.statements
+            || now.agreements < sent.agreements
+            || now.actions < sent.actions
+            || now.forwarded < sent.forwarded
+            || now.reads < sent.reads
+            || now.retracted != sent.retracted;
+        if regressed {
+            return self.restart_live_inspector(live);
+        }
+        match write_events(&mut live.connection.writer, self.events_after(sent, &live.filter)) {
+            Ok(()) => {
+                live.sent = now;
+                self.live_inspector = Some(live);
+                Ok(())
+            }
+            // The inspector may have exited since it was checked, or closed its socket
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::BrokenPipe
+                        | ErrorKind::ConnectionReset
+                        | ErrorKind::ConnectionAborted
+                ) =>
+            {
+                println!("Inspector disconnected, reconnecting");
+                self.restart_live_inspector(live)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn restart_live_inspector(&mut self, live: Live) -> std::io::Result<()> {
+        let filter = live.filter.clone();
+        drop(live);
+        self.start_live_inspector(filter)
+    }
+}
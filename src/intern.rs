@@ -0,0 +1,45 @@
+//! Interning the payloads of updates, such that the journal, the undone updates, and the
+//! checkpoints and branches copied from them share a single copy of each distinct payload,
+//! of which generated scenarios repeat a lot.
+//!
+//! The statements themselves keep their own copies, as the messages of the prototype own
+//! their payloads.
+
+use std::{collections::HashSet, sync::Arc};
+
+#[derive(Debug, Default)]
+pub struct Pool {
+    payloads: HashSet<Arc<str>>,
+}
+
+impl Pool {
+    /// The pooled copy of the payload, pooling it if it is not yet.
+    pub fn intern(&mut self, payload: &Arc<str>) -> Arc<str> {
+        match self.payloads.get(payload) {
+            Some(pooled) => pooled.clone(),
+            None => {
+                self.payloads.insert(payload.clone());
+                payload.clone()
+            }
+        }
+    }
+
+    /// Drops the payloads that no update refers to anymore.
+    pub fn prune(&mut self) {
+        self.payloads.retain(|payload| Arc::strong_count(payload) > 1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.payloads.len()
+    }
+
+    /// The number of bytes of the pooled payloads.
+    pub fn bytes(&self) -> usize {
+        self.payloads.iter().map(|payload| payload.len()).sum()
+    }
+
+    /// The number of references to pooled payloads, not counting those of the pool itself.
+    pub fn references(&self) -> usize {
+        self.payloads.iter().map(|payload| Arc::strong_count(payload) - 1).sum()
+    }
+}
@@ -0,0 +1,101 @@
+//! Payloads that are JSON objects or arrays, which are shown, queried and dumped structurally.
+
+use serde_json::Value;
+
+/// The JSON value of a payload, if it is a JSON object or array. Payloads that are other
+/// JSON values, e.g., `3` or `true`, are more likely meant as plain text.
+pub fn parse(payload: &str) -> Option<Value> {
+    match serde_json::from_str(payload) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => Some(value),
+        _ => None,
+    }
+}
+
+/// The values at a dot-separated path into a JSON value, e.g., `grant.roles.0`, as compared
+/// by `query`. An array at the end of the path yields each of its elements.
+pub fn lookup(value: &Value, path: &str) -> Vec<String> {
+    let mut value = value;
+    for key in path.split('.') {
+        let next = match value {
+            Value::Object(fields) => fields.get(key),
+            Value::Array(items) => key.parse().ok().and_then(|i: usize| items.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(next) => value = next,
+            None => return vec![],
+        }
+    }
+    match value {
+        Value::Array(items) => items.iter().map(text).collect(),
+        value => vec![text(value)],
+    }
+}
+
+/// A JSON value as text, with strings unquoted.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Replaces the payloads in a serialized trace event by their JSON values, for those that
+/// are JSON objects or arrays written as `destructure` writes them back, i.e., compactly
+/// with sorted keys. Others stay text, as their bytes, and so their ids, would change.
+pub fn structure(event: &mut Value) {
+    match event {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if key == "payload"
+                    && let Value::String(payload) = value
+                {
+                    if let Some(parsed) = parse(payload).filter(|v| v.to_string() == *payload) {
+                        *value = parsed;
+                    }
+                } else {
+                    structure(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(structure),
+        _ => {}
+    }
+}
+
+/// Undoes `structure`, turning the structured payloads in a trace event into compact text.
+pub fn destructure(event: &mut Value) {
+    match event {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if key == "payload" {
+                    if !matches!(value, Value::String(_)) {
+                        *value = Value::String(value.to_string());
+                    }
+                } else {
+                    destructure(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(destructure),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{destructure, structure};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn structures_only_payloads_that_destructure_to_the_same_text() {
+        for payload in [r#"{"a":1,"b":[2]}"#, r#"{"b": 1, "a": 2}"#, "[1.00]", "[1e0]"] {
+            let mut event = json!({ "payload": payload });
+            structure(&mut event);
+            let structured = !matches!(event["payload"], Value::String(_));
+            assert_eq!(structured, payload == r#"{"a":1,"b":[2]}"#);
+            destructure(&mut event);
+            assert_eq!(event["payload"], Value::String(payload.to_string()));
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Constructing and minimizing justifications with the policy engine: searching the
+//! statements, or those of a justification, for the smallest sets that it permits, and that
+//! derive the fact the action is meant for, if any.
+//!
+//! The search tries sets in order of size, such that the first found is minimal, and gives
+//! up after trying `MAX_CANDIDATE_SETS`, as the number of sets grows exponentially.
+
+use crate::{policy::Verdict, AgreeIdx, Config, Ref, StmtIdx, UpdateCmd};
+use std::collections::HashSet;
+
+/// The number of candidate sets that a search evaluates at most.
+const MAX_CANDIDATE_SETS: usize = 10_000;
+
+/// A fact without whitespace or a trailing period, to compare facts as derived by any
+/// policy engine.
+fn normalize(fact: &str) -> String {
+    fact.trim().trim_end_matches('.').chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// The smallest subset of the candidates that, along with the required elements, is
+/// accepted, trying subsets in order of size. Fails if acceptance fails, or no subset is
+/// found among the first `MAX_CANDIDATE_SETS`.
+pub(crate) fn smallest_subset(
+    required: &[StmtIdx],
+    candidates: &[StmtIdx],
+    mut accept: impl FnMut(&[StmtIdx]) -> Result<bool, String>,
+) -> Result<Option<Vec<StmtIdx>>, String> {
+    let mut tried = 0;
+    for size in 0..=candidates.len() {
+        // The indices into the candidates of the current subset, in increasing order
+        let mut chosen: Vec<usize> = (0..size).collect();
+        loop {
+            tried += 1;
+            if tried > MAX_CANDIDATE_SETS {
+                return Err(format!("Gave up after trying {} sets", MAX_CANDIDATE_SETS));
+            }
+            let set: Vec<StmtIdx> =
+                required.iter().copied().chain(chosen.iter().map(|&i| candidates[i])).collect();
+            if accept(&set)? {
+                return Ok(Some(set));
+            }
+            // Advances to the next subset of the same size, if any
+            let Some(i) = (0..size).rev().find(|&i| chosen[i] < candidates.len() - size + i) else {
+                break;
+            };
+            chosen[i] += 1;
+            for j in i + 1..size {
+                chosen[j] = chosen[j - 1] + 1;
+            }
+        }
+    }
+    Ok(None)
+}
+
+impl Config {
+    /// Whether the policy engine permits the statements together, and derives the goal
+    /// from them, if any. Fails without a policy engine.
+    pub(crate) fn licenses(&self, stmts: &[StmtIdx], goal: Option<&str>) -> Result<bool, String> {
+        let policy = self.policy.as_ref().ok_or("No policy engine is set, see set policy")?;
+        let payloads: Vec<&str> = stmts.iter().map(|&i| &*self.statements[i].payload).collect();
+        if let Verdict::Forbidden(_) = policy.check(&payloads)? {
+            return Ok(false);
+        }
+        let Some(goal) = goal else {
+            return Ok(true);
+        };
+        let facts = (policy.derive(&payloads))
+            .ok_or(format!("{} does not report derived facts", policy.name()))??;
+        Ok(facts.iter().any(|fact| normalize(fact) == normalize(goal)))
+    }
+
+    /// The statements that the actor could justify an action with: those it received and
+    /// has not seen retracted, said by agents it trusts, besides the basis statement.
+    fn justification_candidates(&self, actor: &str, basis_stmt: StmtIdx) -> Vec<StmtIdx> {
+        (0..self.statements.len())
+            .filter(|&i| i != basis_stmt && !self.retracted.contains(&i))
+            .filter(|&i| self.is_visible_to(i, actor))
+            .filter(|&i| self.trusts(actor, &self.statements[i].id.0))
+            .collect()
+    }
+
+    /// Searches the smallest justification of an action by the actor on the agreement that
+    /// the policy engine licenses, deriving the goal, if any. Enacts the action with it if
+    /// asked to, or prints it otherwise.
+    pub fn autojustify(
+        &mut self,
+        actor: &str,
+        basis: &Ref,
+        goal: Option<&str>,
+        enact: bool,
+    ) -> Result<(), String> {
+        let basis_idx: AgreeIdx = basis.resolve(&self.agree_labels)?;
+        let agreement = (self.agreements.get(basis_idx))
+            .ok_or(format!("Cannot justify using unmade agreement {}", basis_idx))?;
+        let basis_stmt = (self.stmt_idx(&agreement.message))
+            .ok_or(format!("The statement of agreement {} was never stated", basis_idx))?;
+        let candidates = self.justification_candidates(actor, basis_stmt);
+        let found = smallest_subset(&[basis_stmt], &candidates, |set| self.licenses(set, goal))?;
+        let Some(mut justification) = found else {
+            return Err(format!(
+                "No set of the {} statements available to {} licenses the action",
+                candidates.len() + 1,
+                actor
+            ));
+        };
+        justification.sort();
+        let listed: Vec<String> = justification.iter().map(|i| i.to_string()).collect();
+        println!("Justification: statements {}", listed.join(", "));
+        if !enact {
+            return Ok(());
+        }
+        let justification: HashSet<Ref> = justification.into_iter().map(Ref::Idx).collect();
+        let (actor, basis) = (actor.to_string(), basis.clone());
+        let enact = UpdateCmd::Enact { actor, basis, justification, effect: None };
+        self.update_and_bind(enact, None)
+    }
+
+    /// Searches the smallest subset of the justification of the action, including the
+    /// statement of its basis, that the policy engine still licenses, deriving the goal, if
+    /// any, and prints it along with the redundant statements.
+    pub fn minimize(&self, idx: usize, goal: Option<&str>) -> Result<(), String> {
+        let action =
+            self.enacted.get(idx).ok_or(format!("Cannot minimize unenacted action {}", idx))?;
+        let mut justification: Vec<StmtIdx> =
+            action.justification.iter().filter_map(|m| self.stmt_idx(m)).collect();
+        justification.sort();
+        if !self.licenses(&justification, goal)? {
+            return Err(match goal {
+                Some(goal) => {
+                    format!("The justification of action {} does not derive {}", idx, goal)
+                }
+                None => format!("The justification of action {} is not permitted", idx),
+            });
+        }
+        let basis: Vec<StmtIdx> = (self.stmt_idx(&action.basis.message).into_iter())
+            .filter(|i| justification.contains(i))
+            .collect();
+        let candidates: Vec<StmtIdx> =
+            justification.iter().copied().filter(|i| !basis.contains(i)).collect();
+        let mut minimal = smallest_subset(&basis, &candidates, |set| self.licenses(set, goal))?
+            .unwrap_or(justification.clone());
+        minimal.sort();
+        let redundant: Vec<String> = (justification.iter())
+            .filter(|i| !minimal.contains(i))
+            .map(|i| i.to_string())
+            .collect();
+        let minimal: Vec<String> = minimal.iter().map(|i| i.to_string()).collect();
+        println!("Minimal justification: statements {}", minimal.join(", "));
+        match redundant.is_empty() {
+            true => println!("No statements are redundant"),
+            false => println!("Redundant: statements {}", redundant.join(", ")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+
+    /// Statement 0, agreed on, derives `ok` from 1 and 2, while 3 is irrelevant and 4 is
+    /// forbidden.
+    fn grants() -> Config {
+        config(&[
+            "set policy datalog",
+            r#"say amy "ok :- member(bob), grant(bob).""#,
+            "say amy member(bob).",
+            "say cho grant(bob).",
+            "say cho noise.",
+            "say dan error.",
+            "agree 0 0",
+        ])
+    }
+
+    #[test]
+    fn finds_the_smallest_subset_first() {
+        let both =
+            smallest_subset(&[9], &[1, 2, 3], |set| Ok(set.contains(&2) && set.contains(&3)));
+        assert_eq!(both, Ok(Some(vec![9, 2, 3])));
+        let any_two = smallest_subset(&[9], &[1, 2, 3], |set| Ok(set.len() > 2));
+        assert_eq!(any_two, Ok(Some(vec![9, 1, 2])));
+        assert_eq!(smallest_subset(&[9], &[1, 2], |_| Ok(false)), Ok(None));
+        assert!(smallest_subset(&[], &[1], |_| Err("no".to_string())).is_err());
+    }
+
+    #[test]
+    fn licenses_permitted_sets_deriving_the_goal() {
+        let config = grants();
+        assert_eq!(config.licenses(&[0, 1, 2], Some("ok.")), Ok(true));
+        assert_eq!(config.licenses(&[0, 1, 2], None), Ok(true));
+        assert_eq!(config.licenses(&[0, 1], Some("ok")), Ok(false));
+        assert_eq!(config.licenses(&[0, 1, 2, 4], Some("ok")), Ok(false));
+        assert!(Config::default().licenses(&[], None).is_err());
+    }
+
+    #[test]
+    fn justifies_actions_minimally() {
+        let mut config = grants();
+        config.autojustify("bob", &Ref::Idx(0), Some("ok"), true).unwrap();
+        let justification = &config.enacted[0].justification;
+        let mut justification: Vec<StmtIdx> =
+            justification.iter().filter_map(|m| config.stmt_idx(m)).collect();
+        justification.sort();
+        assert_eq!(justification, [0, 1, 2]);
+        assert!(config.autojustify("bob", &Ref::Idx(0), Some("nope"), false).is_err());
+        assert!(config.autojustify("bob", &Ref::Idx(1), None, false).is_err());
+    }
+
+    #[test]
+    fn minimizes_only_licensed_justifications() {
+        let mut config = grants();
+        let cmd = config.parse("enact bob 0 0 1 2 3").unwrap();
+        config.execute(cmd).unwrap();
+        assert_eq!(config.minimize(0, Some("ok")), Ok(()));
+        assert!(config.minimize(0, Some("nope")).is_err());
+        assert!(config.minimize(1, None).is_err());
+    }
+}
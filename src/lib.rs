@@ -0,0 +1,2274 @@
+use justact_prototype::{
+    auditing::Event,
+    spec::collections::map::InfallibleMap,
+    wire::{Action, Agreement, Message},
+};
+use policy::PolicyEngine;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokens::Tokens;
+
+mod audit;
+mod behaviors;
+mod cat;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+mod clock;
+mod compare;
+mod csv;
+mod data;
+mod datalog;
+mod dedup;
+mod dump;
+mod effects;
+mod engine;
+mod explain;
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fuzz;
+mod golden;
+mod help;
+mod history;
+mod http;
+mod ids;
+mod inbox;
+mod index;
+mod inspector;
+mod intern;
+mod json;
+mod justify;
+mod macros;
+mod pager;
+mod policy;
+#[cfg(feature = "python")]
+mod python;
+mod query;
+mod render;
+#[cfg(not(target_arch = "wasm32"))]
+mod repl;
+mod replay;
+mod rng;
+mod rpc;
+mod scenarios;
+mod schedule;
+mod schema;
+mod session;
+mod signing;
+mod snapshot;
+mod stats;
+mod templates;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod tokens;
+mod trust;
+#[cfg(feature = "tui")]
+mod tui;
+mod uses;
+mod validate;
+mod variables;
+mod verify;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+mod why;
+
+pub use effects::{EffectInterpreter, Resource, ResourceInterpreter, Resources};
+pub use engine::{Effect, Engine, EngineError};
+pub use fuzz::fuzz_apply;
+pub use ids::IdScheme;
+pub use rpc::State;
+
+pub type Time = u64;
+pub type StmtIdx = usize;
+pub type AgreeIdx = usize;
+
+#[derive(Default)]
+struct Config {
+    current: Time,
+    statements: Vec<Arc<Message>>,
+    agreements: Vec<Agreement>,
+    enacted: Vec<Action>,
+    /// The times at which the statements were said.
+    stated_at: Vec<Time>,
+    /// The times at which the enacted actions were enacted.
+    enacted_at: Vec<Time>,
+    /// The agreements on which the enacted actions were based.
+    enacted_basis: Vec<AgreeIdx>,
+    /// The effects of the enacted actions that have any.
+    effects: HashMap<usize, String>,
+    /// The simulated resources, as changed by the effects of actions.
+    resources: effects::Resources,
+    /// Interprets the effects of actions, if not the built-in resource interpreter.
+    interpreter: Option<Box<dyn EffectInterpreter>>,
+    /// The reads of agents from datasets, in order.
+    reads: Vec<data::Read>,
+    /// Recipients of the statements that are not stated to all agents.
+    recipients: HashMap<StmtIdx, Vec<String>>,
+    /// The statements delivered to particular agents, mapped to the times of delivery.
+    inboxes: BTreeMap<String, BTreeMap<StmtIdx, Time>>,
+    /// Statements delivered by agents other than their sayers, in order of delivery.
+    forwarded: Vec<inbox::Forward>,
+    /// The two groups of agents that cannot deliver statements to each other, if any.
+    partition: Option<[BTreeSet<String>; 2]>,
+    /// The agents that statements were not delivered to as they were said, as those were
+    /// partitioned from their sayers, or the links to those dropped them.
+    cut_off: HashMap<StmtIdx, BTreeSet<String>>,
+    /// The delays and drop probabilities of the links from agents to others, if any.
+    links: BTreeMap<(String, String), inbox::Link>,
+    /// The agents that statements were delivered to later than they were said, over the
+    /// links to those, mapped to the times at which they are due.
+    delayed: BTreeMap<StmtIdx, BTreeMap<String, Time>>,
+    /// The files that the payloads of statements were read from.
+    sources: HashMap<StmtIdx, String>,
+    /// The JSON values of the payloads of statements that are JSON objects or arrays.
+    structured: HashMap<StmtIdx, serde_json::Value>,
+    /// Statements retracted by their sayers, which no longer count as stated.
+    retracted: HashSet<StmtIdx>,
+    /// Enacted actions withdrawn by their actors, mapped to the times of withdrawal.
+    withdrawn: HashMap<usize, Time>,
+    /// Labels of statements, mapped to their indices.
+    stmt_labels: HashMap<String, StmtIdx>,
+    /// Labels of agreements, mapped to their indices.
+    agree_labels: HashMap<String, AgreeIdx>,
+    /// The last times at which agreements with a validity window apply.
+    expires_at: HashMap<AgreeIdx, Time>,
+    /// Agreements superseded by amendments, mapped to the agreements amending them.
+    superseded_by: HashMap<AgreeIdx, AgreeIdx>,
+    /// Explicitly registered agents.
+    agents: BTreeMap<String, AgentInfo>,
+    /// The agents run by scripts, reacting to what happens.
+    behaviors: BTreeMap<String, behaviors::Behavior>,
+    /// How much of the state the agents run by scripts have reacted to.
+    seen: behaviors::Seen,
+    /// Decides which rules of the agents run by scripts fire, if they fire by chance.
+    rng: rng::Rng,
+    /// Whether only registered agents may say, enact, retract, or receive statements.
+    strict: bool,
+    /// Whether only actions that the policy engine licenses, and that are valid otherwise,
+    /// may be enacted.
+    enforce: bool,
+    /// The agents that agents trust, for those that declared any.
+    trust: BTreeMap<String, BTreeSet<String>>,
+    /// How the messages of new statements are identified.
+    id_scheme: ids::IdScheme,
+    /// The keys with which agents sign their statements.
+    keys: BTreeMap<String, ed25519_dalek::SigningKey>,
+    /// The secret keys generated in this session, by their public keys in hex. They are kept
+    /// out of the journal, and thereby out of saved sessions, checkpoints and branches.
+    secrets: HashMap<String, ed25519_dalek::SigningKey>,
+    /// The signatures of signed statements, with the public keys of their signers.
+    signatures: HashMap<StmtIdx, (ed25519_dalek::VerifyingKey, ed25519_dalek::Signature)>,
+    /// Successfully applied updates, oldest first.
+    journal: Vec<UpdateCmd>,
+    /// Undone updates, most recently undone last.
+    undone: Vec<UpdateCmd>,
+    /// How often the state was rebuilt from the journal, e.g., as another replaced it, after
+    /// which the journal is no longer an extension of what it was before.
+    replays: usize,
+    /// Validates the payloads of new statements, if any.
+    schema: Option<schema::Schema>,
+    /// Judges the justifications of actions when auditing, if any.
+    policy: Option<Box<dyn PolicyEngine>>,
+    /// Program and arguments of the inspector that `inspect` pipes the trace into.
+    inspector: Vec<String>,
+    /// The inspector kept running to be sent the events of each update, if any.
+    live_inspector: Option<inspector::Live>,
+    /// Canonical paths of the scripts currently being loaded, outermost first.
+    loading: Vec<PathBuf>,
+    /// Whether loading a script stops at the first command that fails.
+    fail_fast: bool,
+    /// Accepted commands, oldest first.
+    history: Vec<history::Entry>,
+    /// File to which accepted commands are appended, if any.
+    history_file: Option<std::fs::File>,
+    /// Named snapshots of the state, to compare against with `diff`.
+    snapshots: BTreeMap<String, snapshot::Snapshot>,
+    /// Named sessions to roll back to.
+    checkpoints: BTreeMap<String, session::Session>,
+    /// Name of the current branch.
+    branch: String,
+    /// Sessions of the other branches, by name.
+    branches: BTreeMap<String, session::Session>,
+    /// User-defined macros, mapping names to bodies of `;`-separated commands.
+    macros: BTreeMap<String, String>,
+    /// Payload templates, mapping names to payloads with `$<name>` parameters.
+    templates: BTreeMap<String, String>,
+    /// Names of the macros currently being expanded, outermost first.
+    expanding: Vec<String>,
+    /// Whether time is set manually or follows the system clock.
+    clock: clock::Clock,
+    /// Whether the state is shown anew after every command that changes it.
+    watch: bool,
+    /// Whether actions are audited as they are enacted, or invalidated by later updates.
+    continuous_audit: bool,
+    /// Updates to apply once time reaches their scheduled times, in order of those times,
+    /// and of scheduling for updates at the same time.
+    scheduled: Vec<(Time, UpdateCmd)>,
+    /// Indices bound to variables, which commands refer to as `$<name>`.
+    variables: BTreeMap<String, usize>,
+    /// Secondary indices of the statements, agreements and actions.
+    index: index::Index,
+    /// The payloads of updates, shared by all updates with the same payload.
+    pool: intern::Pool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentInfo {
+    pub role: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateCmd {
+    Say {
+        sayer: String,
+        /// Interned, such that updates saying the same payload share it.
+        payload: Arc<str>,
+        #[serde(default)]
+        label: Option<String>,
+        /// Stated to all agents if `None`.
+        #[serde(default)]
+        recipients: Option<Vec<String>>,
+        /// The file the payload was read from, if any.
+        #[serde(default)]
+        source: Option<String>,
+    },
+    Agree {
+        on_idx: Ref,
+        at: Time,
+        /// Applies at `at` only if `None`, or until and including this time otherwise.
+        #[serde(default)]
+        until: Option<Time>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    /// Agrees on a statement, superseding an earlier agreement.
+    Amend {
+        supersedes: Ref,
+        on_idx: Ref,
+        at: Time,
+        #[serde(default)]
+        until: Option<Time>,
+        #[serde(default)]
+        label: Option<String>,
+    },
+    Enact {
+        actor: String,
+        basis: Ref,
+        justification: HashSet<Ref>,
+        /// What enacting the action changes, as the effect interpreter interprets it.
+        #[serde(default)]
+        effect: Option<String>,
+    },
+    Now {
+        now: Time,
+        /// Whether the system clock set the time, rather than the user, such that undo
+        /// passes over it.
+        #[serde(default)]
+        tick: bool,
+    },
+    Retract {
+        retractor: String,
+        stmt: Ref,
+    },
+    /// Withdraws an enacted action, which remains in the trace, marked withdrawn.
+    Withdraw {
+        actor: String,
+        action: usize,
+    },
+    /// Queues an action, to be enacted once time reaches the given time.
+    Schedule {
+        at: Time,
+        update_cmd: Box<UpdateCmd>,
+    },
+    AddAgent {
+        name: String,
+        info: AgentInfo,
+    },
+    RemoveAgent {
+        name: String,
+    },
+    Strict {
+        strict: bool,
+    },
+    /// Refuses actions that the policy engine does not license if `enforce`.
+    Enforce {
+        enforce: bool,
+    },
+    IdScheme {
+        scheme: ids::IdScheme,
+    },
+    /// Makes the truster accept statements by the trustee as justification.
+    Trust {
+        truster: String,
+        trustee: String,
+    },
+    /// Delivers the statements that one agent knows of, i.e., its own and those it
+    /// received, to another, which has not received them yet.
+    Sync {
+        from: String,
+        to: String,
+    },
+    /// Partitions the agents into two groups, between which no statements are delivered.
+    Partition {
+        groups: [Vec<String>; 2],
+    },
+    /// Ends the partition, such that statements are delivered across groups again.
+    Heal,
+    /// Adds a dataset, as a resource owned by the agent.
+    AddDataset {
+        name: String,
+        owner: String,
+    },
+    /// Reads from a dataset as the agent, enabled by the enacted action.
+    Read {
+        reader: String,
+        dataset: String,
+        via: usize,
+    },
+    /// Makes statements from one agent to another arrive the given time later, if ever, as
+    /// the link between them drops them with the given probability.
+    Link {
+        from: String,
+        to: String,
+        delay: Time,
+        drop: f64,
+    },
+    /// Gives the agent a key, with which its later statements are signed if the secret half
+    /// was generated in this session, and left unsigned otherwise, as it is held elsewhere.
+    AddKey {
+        agent: String,
+        /// The public key, in hex.
+        public: String,
+    },
+}
+
+/// Refers to a statement or agreement, either by its index or by its label.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Ref {
+    Idx(usize),
+    Label(String),
+}
+
+#[derive(Debug)]
+enum Cmd {
+    Update(UpdateCmd),
+    Inspect {
+        filter: inspector::EventFilter,
+        /// Whether the inspector keeps running, to be sent the events of later commands.
+        live: bool,
+        /// Only inspects this statement, agreement or action, and what it depends on.
+        target: Option<(query::Section, Ref)>,
+    },
+    /// Stops the live inspector.
+    StopInspect,
+    Comment,
+    Quit,
+    Show {
+        viewer: Option<String>,
+        filter: query::Filter,
+    },
+    /// Dumps to stdout if `path` is `None`.
+    Dump {
+        path: Option<String>,
+        append: bool,
+        format: dump::Format,
+        filter: inspector::EventFilter,
+    },
+    Load {
+        path: String,
+    },
+    Undo,
+    Redo,
+    Save {
+        path: String,
+    },
+    Restore {
+        path: String,
+    },
+    Import {
+        path: String,
+    },
+    ListScenarios,
+    LoadScenario {
+        name: String,
+    },
+    /// Names the columns by the first row if `columns` is `None`.
+    ImportCsv {
+        path: String,
+        delimiter: char,
+        columns: Option<Vec<csv::Column>>,
+    },
+    SetInspector {
+        command: Vec<String>,
+    },
+    SetPolicy {
+        name: String,
+    },
+    /// Stops validating payloads if `name` is `none`.
+    SetSchema {
+        name: String,
+    },
+    /// Makes time manual if `real` is `None`.
+    SetClock {
+        real: Option<clock::Unit>,
+    },
+    SetWatch {
+        watch: bool,
+    },
+    /// Audits actions on every update if `continuous`, or on `audit` only otherwise.
+    SetAudit {
+        continuous: bool,
+    },
+    ListAgents,
+    /// Generates a new key for the agent.
+    Keygen {
+        name: String,
+    },
+    RunAgent {
+        name: String,
+        script: String,
+    },
+    StopAgent {
+        name: String,
+    },
+    /// Reseeds the random number generator first if `seed` is given.
+    Step {
+        n: u64,
+        seed: Option<u64>,
+    },
+    Audit,
+    Eval {
+        stmts: HashSet<Ref>,
+    },
+    Export {
+        format: export::Format,
+        path: String,
+    },
+    Find {
+        pattern: String,
+        regex: bool,
+    },
+    Query(query::Query),
+    History,
+    /// Shows the accepted commands matching the pattern, as a regular expression if `regex`.
+    HistorySearch {
+        pattern: String,
+        regex: bool,
+    },
+    Replay {
+        path: String,
+        step: bool,
+    },
+    Snapshot {
+        name: String,
+    },
+    /// Compares against the current state if `new` is `None`.
+    Diff {
+        old: String,
+        new: Option<String>,
+    },
+    Checkpoint {
+        name: String,
+    },
+    Rollback {
+        name: String,
+    },
+    ListCheckpoints,
+    Branch {
+        name: String,
+    },
+    Switch {
+        name: String,
+    },
+    ListBranches,
+    DefineMacro {
+        name: String,
+        body: String,
+    },
+    ListMacros,
+    DefineTemplate {
+        name: String,
+        body: String,
+    },
+    ListTemplates,
+    /// Binds the index created by the update to the variable.
+    Let {
+        name: String,
+        update_cmd: UpdateCmd,
+    },
+    ListVariables,
+    CurrentAgreements,
+    /// Lists the groups of duplicate statements.
+    Dedup,
+    Stats,
+    /// Reports the memory taken by the payloads of statements and updates.
+    MemoryStats,
+    /// Says the contents of the file.
+    SayFile {
+        sayer: String,
+        path: String,
+        label: Option<String>,
+    },
+    /// Prints a statement, agreement or action in full.
+    Cat {
+        section: query::Section,
+        target: Ref,
+    },
+    /// Runs the full-screen interface.
+    Tui,
+    ShowTrust,
+    ShowResources,
+    /// Lists the agreements and actions referencing the statement.
+    Uses {
+        stmt: Ref,
+    },
+    /// Lists the statements delivered to the agent.
+    Inbox {
+        agent: String,
+    },
+    /// Lists the groups of the partition, if any.
+    ShowPartition,
+    /// Lists the links that delay or drop statements.
+    ShowLinks,
+    /// Checks whether the enactment would be valid at the current time, without enacting it.
+    CheckEnact {
+        enact: UpdateCmd,
+    },
+    /// Searches the smallest justification that the policy engine licenses for an action,
+    /// and enacts it if asked to.
+    Autojustify {
+        actor: String,
+        basis: Ref,
+        goal: Option<String>,
+        enact: bool,
+    },
+    /// Finds the smallest subset of the justification of the action with the given index
+    /// that the policy engine still licenses.
+    Minimize {
+        action: usize,
+        goal: Option<String>,
+    },
+    /// Lists the validity conditions of the action with the given index, and the smallest
+    /// changes that make those that fail hold.
+    Explain {
+        action: usize,
+    },
+    /// Explains the (in)validity of the action with the given index.
+    Why {
+        action: usize,
+    },
+    /// Runs the script in a fresh session and compares its trace with the expected one.
+    Check {
+        script: String,
+        expected: String,
+    },
+    /// Compares the traces at the paths by their events.
+    Compare {
+        old: String,
+        new: String,
+    },
+    /// Verifies the signatures in the trace at the path, or of the session if `None`,
+    /// trusting only the keys in the key file, if any, besides those of the session.
+    Verify {
+        path: Option<String>,
+        keys: Option<String>,
+    },
+    ListScheduled,
+    /// Advances time by the given amount.
+    Tick {
+        by: Time,
+    },
+    /// Sets the time, even if that means going back in time.
+    ForceNow {
+        now: Time,
+    },
+    Help {
+        command: Option<String>,
+    },
+    /// Not parsed by [`Cmd::parse`], which does not know the defined macros.
+    Invoke {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+/// Why a command could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+enum ParseError {
+    Empty,
+    UnknownCommand {
+        keyword: String,
+        suggestion: Option<&'static str>,
+    },
+    /// The keyword is known, but its arguments are malformed.
+    Malformed {
+        keyword: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty command"),
+            ParseError::UnknownCommand { keyword, suggestion: Some(suggestion) } => {
+                write!(f, "unknown command {:?}, did you mean {:?}?", keyword, suggestion)
+            }
+            ParseError::UnknownCommand { keyword, suggestion: None } => {
+                write!(f, "unknown command {:?}, see help for all commands", keyword)
+            }
+            ParseError::Malformed { keyword, reason } => write!(f, "{}: {}", keyword, reason),
+        }
+    }
+}
+
+/// Whether the session continues after executing a command.
+enum Flow {
+    Continue,
+    Quit,
+}
+
+impl UpdateCmd {
+    /// The agent acting by this update, or on whose behalf it is made, e.g., the agent that
+    /// is given a key, if it is not a global one like advancing time.
+    fn actor(&self) -> Option<&str> {
+        match self {
+            UpdateCmd::Say { sayer: agent, .. }
+            | UpdateCmd::Enact { actor: agent, .. }
+            | UpdateCmd::Retract { retractor: agent, .. }
+            | UpdateCmd::Withdraw { actor: agent, .. }
+            | UpdateCmd::Read { reader: agent, .. }
+            | UpdateCmd::AddKey { agent, .. }
+            | UpdateCmd::Trust { truster: agent, .. }
+            | UpdateCmd::Sync { from: agent, .. }
+            | UpdateCmd::AddDataset { owner: agent, .. }
+            | UpdateCmd::AddAgent { name: agent, .. }
+            | UpdateCmd::RemoveAgent { name: agent } => Some(agent),
+            UpdateCmd::Schedule { update_cmd, .. } => update_cmd.actor(),
+            _ => None,
+        }
+    }
+
+    /// Whether this update configures the session as a whole, which only its operator may.
+    fn administrative(&self) -> bool {
+        matches!(
+            self,
+            UpdateCmd::Strict { .. }
+                | UpdateCmd::Enforce { .. }
+                | UpdateCmd::IdScheme { .. }
+                | UpdateCmd::Partition { .. }
+                | UpdateCmd::Heal
+                | UpdateCmd::Link { .. }
+        )
+    }
+}
+
+impl Ref {
+    fn parse(token: &str) -> Option<Self> {
+        match token.parse() {
+            Ok(idx) => Some(Ref::Idx(idx)),
+            Err(_) => is_label(token).then(|| Ref::Label(token.to_string())),
+        }
+    }
+
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Result<usize, String> {
+        match self {
+            Ref::Idx(idx) => Ok(*idx),
+            Ref::Label(label) => {
+                labels.get(label).copied().ok_or(format!("Unknown label {}", label))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Ref {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Ref::Idx(idx) => write!(f, "{}", idx),
+            Ref::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+/// Labels start with a letter or `_`, so that they are never mistaken for indices,
+/// and consist of alphanumerics, `_` and `-`.
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The settings that `set` configures, as listed when another is given.
+const SETTINGS: &str =
+    "inspector, policy, schema, strict, enforce, clock, idscheme, watch or audit";
+
+fn parse_switch(token: &str) -> Option<bool> {
+    match token {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Takes the next token, described as `what` in case it is missing.
+fn next<'a>(tokens: &mut Tokens<'a>, what: &str) -> Result<Cow<'a, str>, String> {
+    match tokens.next() {
+        Some(token) => Ok(token),
+        None if tokens.is_empty() => Err(format!("expected {}", what)),
+        None => Err(format!("malformed quotes in {:?}", tokens.rest())),
+    }
+}
+
+/// Takes the next token, which `parse` must accept as `what`.
+fn next_as<T>(
+    tokens: &mut Tokens,
+    what: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, String> {
+    let token = next(tokens, what)?;
+    parse(&token).ok_or_else(|| format!("expected {}, got {:?}", what, token))
+}
+
+fn next_ref(tokens: &mut Tokens, what: &str) -> Result<Ref, String> {
+    next_as(tokens, &format!("{} index or label", what), Ref::parse)
+}
+
+/// The character identifying the `idx`-th action, counting from `a` and skipping the
+/// surrogate code points, which are no characters.
+fn action_char(idx: usize) -> Option<char> {
+    let code = u32::try_from(idx).ok()?.checked_add('a' as u32)?;
+    char::from_u32(if code >= 0xd800 { code.checked_add(0x800)? } else { code })
+}
+
+/// Parses a time, which is either absolute, `now`, or relative to now, e.g., `+3`.
+fn parse_time(token: &str, now: Time) -> Option<Time> {
+    match token {
+        "now" => Some(now),
+        _ => match token.strip_prefix('+') {
+            Some(delta) => now.checked_add(delta.parse().ok()?),
+            None => token.parse().ok(),
+        },
+    }
+}
+
+fn next_time(tokens: &mut Tokens, now: Time) -> Result<Time, String> {
+    next_as(tokens, "a time", |t| parse_time(t, now))
+}
+
+/// Parses `--only <section>(,<section>)*` or `--since <time>` into the filter, returning
+/// whether the next token was either.
+fn next_event_filter(
+    tokens: &mut Tokens,
+    now: Time,
+    filter: &mut inspector::EventFilter,
+) -> Result<bool, String> {
+    match tokens.rest().split_whitespace().next() {
+        Some("--only") => {
+            tokens.next();
+            let sections = next(tokens, "sections")?;
+            let sections = sections.split(',').map(|s| {
+                query::Section::parse(s).ok_or_else(|| format!("expected a section, got {:?}", s))
+            });
+            filter.only = Some(sections.collect::<Result<_, _>>()?);
+        }
+        Some("--since") => {
+            tokens.next();
+            filter.since = Some(next_time(tokens, now)?);
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Parses a statement, agreement or action as `[stmt] <stmt.id>`, `agreement <ag.id>` or
+/// `action <act.id>`.
+fn next_target(tokens: &mut Tokens) -> Result<(query::Section, Ref), String> {
+    let section = match tokens.rest().split_whitespace().next() {
+        Some("stmt") => Some(query::Section::Statements),
+        Some("agreement") => Some(query::Section::Agreements),
+        Some("action") => Some(query::Section::Actions),
+        _ => None,
+    };
+    if section.is_some() {
+        tokens.next();
+    }
+    let section = section.unwrap_or(query::Section::Statements);
+    Ok((section, next_ref(tokens, "a statement, agreement or action")?))
+}
+
+/// Parses an optional `until <time>`.
+fn parse_until(tokens: &mut Tokens, now: Time) -> Result<Option<Time>, String> {
+    if tokens.rest().split_whitespace().next() != Some("until") {
+        return Ok(None);
+    }
+    tokens.next();
+    next_time(tokens, now).map(Some)
+}
+
+fn next_label(tokens: &mut Tokens, what: &str) -> Result<String, String> {
+    next_as(tokens, what, |t| is_label(t).then(|| t.to_string()))
+}
+
+/// Parses an optional trailing `as <label>`.
+fn parse_label(tokens: &mut Tokens) -> Result<Option<String>, String> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    next_as(tokens, "as <label>", |t| (t == "as").then_some(()))?;
+    next_label(tokens, "a label").map(Some)
+}
+
+/// Parses a payload, either quoted or verbatim, with an optional trailing `as <label>`.
+fn parse_payload(tokens: &mut Tokens) -> Result<(String, Option<String>), String> {
+    if tokens.rest().starts_with('"') {
+        Ok((next(tokens, "a payload")?.into_owned(), parse_label(tokens)?))
+    } else {
+        let remainder = tokens.remainder().ok_or("expected a payload")?;
+        let (payload, label) = split_label(&remainder);
+        Ok((payload.to_string(), label.map(str::to_string)))
+    }
+}
+
+/// Splits an optional trailing `as <label>` off an unquoted payload.
+fn split_label(payload: &str) -> (&str, Option<&str>) {
+    let mut words = payload.rsplitn(3, char::is_whitespace);
+    match (words.next(), words.next(), words.next()) {
+        (Some(label), Some("as"), Some(rest)) if is_label(label) && !rest.trim().is_empty() => {
+            (rest.trim_end(), Some(label))
+        }
+        _ => (payload, None),
+    }
+}
+
+impl Cmd {
+    /// Parses the command, resolving relative times w.r.t. the given current time.
+    fn parse(input: &str, now: Time) -> Result<Self, ParseError> {
+        let mut tokens = Tokens::new(input);
+        let Some(keyword) = tokens.next() else {
+            return Err(match tokens.is_empty() {
+                true => ParseError::Empty,
+                false => ParseError::UnknownCommand { keyword: input.into(), suggestion: None },
+            });
+        };
+        match Self::parse_args(&keyword, &mut tokens, now) {
+            Ok(Some(cmd)) => Ok(cmd),
+            Ok(None) => Err(ParseError::UnknownCommand {
+                suggestion: help::suggest(&keyword),
+                keyword: keyword.into_owned(),
+            }),
+            Err(reason) => Err(ParseError::Malformed { keyword: keyword.into_owned(), reason }),
+        }
+    }
+
+    /// Parses the arguments of the command with the given keyword, if it is one.
+    fn parse_args(keyword: &str, tokens: &mut Tokens, now: Time) -> Result<Option<Self>, String> {
+        use Cmd::*;
+        use UpdateCmd::*;
+        let cmd = match keyword {
+            "say" => {
+                let sayer = next(tokens, "a sayer")?.into_owned();
+                let (payload, label) = parse_payload(tokens)?;
+                Update(Say {
+                    sayer,
+                    payload: payload.into(),
+                    label,
+                    recipients: None,
+                    source: None,
+                })
+            }
+            "sayto" => {
+                let sayer = next(tokens, "a sayer")?.into_owned();
+                let recipients: Vec<String> = next(tokens, "recipients")?
+                    .split(',')
+                    .filter(|r| !r.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if recipients.is_empty() {
+                    return Err("expected at least one recipient".to_string());
+                }
+                let (payload, label) = parse_payload(tokens)?;
+                let recipients = Some(recipients);
+                Update(Say { sayer, payload: payload.into(), label, recipients, source: None })
+            }
+            "agree" => {
+                let on_idx = next_ref(tokens, "a statement")?;
+                let at = next_time(tokens, now)?;
+                let until = parse_until(tokens, now)?;
+                let label = parse_label(tokens)?;
+                Update(Agree { on_idx, at, until, label })
+            }
+            "amend" => {
+                let supersedes = next_ref(tokens, "an agreement")?;
+                let on_idx = next_ref(tokens, "a statement")?;
+                let at = next_time(tokens, now)?;
+                let until = parse_until(tokens, now)?;
+                let label = parse_label(tokens)?;
+                Update(Amend { supersedes, on_idx, at, until, label })
+            }
+            "enact" => {
+                let actor = next(tokens, "an actor")?.into_owned();
+                let basis = next_ref(tokens, "an agreement")?;
+                let (mut justification, mut effect, mut check) = (HashSet::new(), None, false);
+                while !tokens.is_empty() {
+                    match tokens.rest().split_whitespace().next() {
+                        Some("--effect") => {
+                            tokens.next();
+                            effect = Some(next(tokens, "an effect")?.into_owned());
+                        }
+                        Some("--check") => {
+                            tokens.next();
+                            check = true;
+                        }
+                        _ => {
+                            justification.insert(next_ref(tokens, "a statement")?);
+                        }
+                    }
+                }
+                match check {
+                    true => CheckEnact { enact: Enact { actor, basis, justification, effect } },
+                    false => Update(Enact { actor, basis, justification, effect }),
+                }
+            }
+            "now" if tokens.rest().split_whitespace().next() == Some("--force") => {
+                tokens.next();
+                ForceNow { now: next_time(tokens, now)? }
+            }
+            "now" => Update(Now { now: next_time(tokens, now)?, tick: false }),
+            "step" => {
+                let (mut n, mut seed) = (None, None);
+                while !tokens.is_empty() {
+                    if tokens.rest().split_whitespace().next() == Some("--seed") && seed.is_none() {
+                        tokens.next();
+                        seed = Some(next_as(tokens, "a seed", |t| t.parse().ok())?);
+                    } else if n.is_none() {
+                        n = Some(next_as(tokens, "a number of steps", |t| t.parse().ok())?);
+                    } else {
+                        return Err(format!("unexpected {:?}", tokens.rest()));
+                    }
+                }
+                Step { n: n.unwrap_or(1), seed }
+            }
+            "tick" => match tokens.is_empty() {
+                true => Tick { by: 1 },
+                false => Tick { by: next_as(tokens, "a duration", |t| t.parse().ok())? },
+            },
+            "retract" => {
+                let retractor = next(tokens, "a retractor")?.into_owned();
+                Update(Retract { retractor, stmt: next_ref(tokens, "a statement")? })
+            }
+            "dataset" => {
+                let what = next(tokens, "add")?;
+                if what != "add" {
+                    return Err(format!("expected add, got {:?}", what));
+                }
+                let name = next(tokens, "a dataset")?.into_owned();
+                let owner = next_as(tokens, "owner=<agent>", |t| {
+                    t.strip_prefix("owner=").filter(|o| !o.is_empty()).map(str::to_string)
+                })?;
+                Update(AddDataset { name, owner })
+            }
+            "read" => {
+                let reader = next(tokens, "a reader")?.into_owned();
+                let dataset = next(tokens, "a dataset")?.into_owned();
+                if next(tokens, "via")? != "via" {
+                    return Err("expected via <act.id> after the dataset".to_string());
+                }
+                let via = next_as(tokens, "an action index", |t| t.parse().ok())?;
+                Update(Read { reader, dataset, via })
+            }
+            "withdraw" => {
+                let actor = next(tokens, "an actor")?.into_owned();
+                let action = next_as(tokens, "an action index", |t| t.parse().ok())?;
+                Update(Withdraw { actor, action })
+            }
+            "inspect" if tokens.rest().split_whitespace().next() == Some("--stop") => {
+                tokens.next();
+                StopInspect
+            }
+            "inspect" => {
+                let (mut filter, mut live) = (inspector::EventFilter::default(), false);
+                let mut target = None;
+                while !tokens.is_empty() {
+                    if next_event_filter(tokens, now, &mut filter)? {
+                        continue;
+                    }
+                    match tokens.rest().split_whitespace().next() {
+                        Some("--live") => {
+                            tokens.next();
+                            live = true;
+                        }
+                        Some(_) if target.is_none() => target = Some(next_target(tokens)?),
+                        _ => return Err(format!("unexpected {:?}", next(tokens, "an option")?)),
+                    }
+                }
+                Inspect { filter, live, target }
+            }
+            "quit" => Quit,
+            "dump" => {
+                let (mut format, mut append) = (dump::Format::default(), false);
+                let mut filter = inspector::EventFilter::default();
+                loop {
+                    if next_event_filter(tokens, now, &mut filter)? {
+                        continue;
+                    }
+                    match tokens.rest().split_whitespace().next() {
+                        Some("--format") => {
+                            tokens.next();
+                            format = next_as(
+                                tokens,
+                                "jsonl, json, yaml or upstream",
+                                dump::Format::parse,
+                            )?;
+                        }
+                        Some("--append") => {
+                            tokens.next();
+                            append = true;
+                        }
+                        _ => break,
+                    }
+                }
+                if append && format != dump::Format::JsonLines {
+                    // Appended arrays or documents cannot be read back as one trace
+                    return Err("--append requires --format jsonl".to_string());
+                }
+                let path = match append {
+                    true => Some(next(tokens, "a file")?.into_owned()),
+                    false => tokens.next().map(|t| t.into_owned()),
+                };
+                Dump { path, append, format, filter }
+            }
+            "show" if tokens.rest().split_whitespace().next() == Some("trust") => {
+                tokens.next();
+                ShowTrust
+            }
+            "show" if tokens.rest().split_whitespace().next() == Some("resources") => {
+                tokens.next();
+                ShowResources
+            }
+            "show" => {
+                let (mut viewer, mut filter) = (None, query::Filter::default());
+                while !tokens.is_empty() {
+                    let token = next(tokens, "an option")?;
+                    match &*token {
+                        "as" if viewer.is_none() => {
+                            viewer = Some(next(tokens, "a viewer")?.into_owned())
+                        }
+                        "by" if filter.by.is_none() => {
+                            filter.by = Some(next(tokens, "a sayer or actor")?.into_owned())
+                        }
+                        "since" if filter.since.is_none() => {
+                            filter.since = Some(next_time(tokens, now)?)
+                        }
+                        "--head" if filter.limit.is_none() => {
+                            let n = next_as(tokens, "a number of rows", |t| t.parse().ok())?;
+                            filter.limit = Some(query::Limit::Head(n))
+                        }
+                        "--tail" if filter.limit.is_none() => {
+                            let n = next_as(tokens, "a number of rows", |t| t.parse().ok())?;
+                            filter.limit = Some(query::Limit::Tail(n))
+                        }
+                        section if filter.section.is_none() => {
+                            filter.section =
+                                Some(query::Section::parse(section).ok_or_else(|| {
+                                    format!(
+                                    "expected a section, by, since, as, --head or --tail, got {:?}",
+                                    section
+                                )
+                                })?)
+                        }
+                        _ => return Err(format!("unexpected {:?}", token)),
+                    }
+                }
+                Show { viewer, filter }
+            }
+            "load" | "source" => Load { path: next(tokens, "a file")?.into_owned() },
+            "save" => Save { path: next(tokens, "a file")?.into_owned() },
+            "restore" => Restore { path: next(tokens, "a file")?.into_owned() },
+            "scenario" => match &*next(tokens, "list or load")? {
+                "list" => ListScenarios,
+                "load" => LoadScenario { name: next(tokens, "a scenario")?.into_owned() },
+                other => return Err(format!("expected list or load, got {:?}", other)),
+            },
+            "import" if matches!(tokens.rest().split_whitespace().next(), Some("csv" | "tsv")) => {
+                let delimiter = if next(tokens, "csv or tsv")? == "csv" { ',' } else { '\t' };
+                let path = next(tokens, "a file")?.into_owned();
+                let columns = if tokens.rest().split_whitespace().next() == Some("--columns") {
+                    tokens.next();
+                    Some(csv::parse_columns(&next(tokens, "columns")?)?)
+                } else {
+                    None
+                };
+                ImportCsv { path, delimiter, columns }
+            }
+            "import" => Import { path: next(tokens, "a file")?.into_owned() },
+            "set" => {
+                let what = next(tokens, SETTINGS)?;
+                match &*what {
+                    "inspector" => {
+                        let mut command = vec![next(tokens, "an inspector command")?.into_owned()];
+                        while !tokens.is_empty() {
+                            command.push(next(tokens, "an argument")?.into_owned());
+                        }
+                        SetInspector { command }
+                    }
+                    "policy" => SetPolicy { name: next(tokens, "a policy engine")?.into_owned() },
+                    "schema" => SetSchema { name: next(tokens, "a schema")?.into_owned() },
+                    "strict" => {
+                        Update(Strict { strict: next_as(tokens, "on or off", parse_switch)? })
+                    }
+                    "enforce" => {
+                        Update(Enforce { enforce: next_as(tokens, "on or off", parse_switch)? })
+                    }
+                    "watch" => SetWatch { watch: next_as(tokens, "on or off", parse_switch)? },
+                    "audit" => SetAudit {
+                        continuous: next_as(tokens, "continuous or off", |t| match t {
+                            "continuous" => Some(true),
+                            "off" => Some(false),
+                            _ => None,
+                        })?,
+                    },
+                    "clock" => match &*next(tokens, "real or manual")? {
+                        "manual" => SetClock { real: None },
+                        "real" if tokens.is_empty() => {
+                            SetClock { real: Some(clock::Unit::Seconds) }
+                        }
+                        "real" => {
+                            SetClock { real: Some(next_as(tokens, "s or ms", clock::Unit::parse)?) }
+                        }
+                        other => return Err(format!("expected real or manual, got {:?}", other)),
+                    },
+                    "idscheme" => Update(IdScheme {
+                        scheme: next_as(tokens, "hash or counter", ids::IdScheme::parse)?,
+                    }),
+                    _ => return Err(format!("expected {}, got {:?}", SETTINGS, what)),
+                }
+            }
+            "agent" => {
+                let what = next(tokens, "add, remove, list, keygen, key, run or stop")?;
+                match &*what {
+                    "add" => {
+                        let name = next(tokens, "a name")?.into_owned();
+                        let mut info = AgentInfo::default();
+                        while !tokens.is_empty() {
+                            let option = next(tokens, "role or desc")?;
+                            let value = Some(
+                                next(tokens, &format!("a value for {}", option))?.into_owned(),
+                            );
+                            match &*option {
+                                "role" => info.role = value,
+                                "desc" => info.description = value,
+                                _ => {
+                                    return Err(format!("expected role or desc, got {:?}", option))
+                                }
+                            }
+                        }
+                        Update(AddAgent { name, info })
+                    }
+                    "remove" => Update(RemoveAgent { name: next(tokens, "a name")?.into_owned() }),
+                    "list" => ListAgents,
+                    "keygen" => Keygen { name: next(tokens, "a name")?.into_owned() },
+                    "key" => Update(AddKey {
+                        agent: next(tokens, "a name")?.into_owned(),
+                        public: next(tokens, "a public key")?.into_owned(),
+                    }),
+                    "run" => RunAgent {
+                        name: next(tokens, "a name")?.into_owned(),
+                        script: next(tokens, "a script")?.into_owned(),
+                    },
+                    "stop" => StopAgent { name: next(tokens, "a name")?.into_owned() },
+                    _ => {
+                        return Err(format!(
+                            "expected add, remove, list, keygen, key, run or stop, got {:?}",
+                            what
+                        ))
+                    }
+                }
+            }
+            "audit" => Audit,
+            "eval" => {
+                let mut stmts = HashSet::new();
+                while !tokens.is_empty() {
+                    stmts.insert(next_ref(tokens, "a statement")?);
+                }
+                Eval { stmts }
+            }
+            "export" => {
+                let format = next_as(tokens, "dot, mermaid, html or csv", export::Format::parse)?;
+                Export { format, path: next(tokens, "a path")?.into_owned() }
+            }
+            "find" => {
+                let regex = tokens.rest().starts_with("--regex ");
+                if regex {
+                    tokens.next();
+                }
+                Find {
+                    pattern: tokens.remainder().ok_or("expected a pattern")?.into_owned(),
+                    regex,
+                }
+            }
+            "query" => Query(query::Query::parse(&mut *tokens).ok_or(
+                "expected <stmts|agreements|actions> [where <field> <op> <value> (and ...)*]",
+            )?),
+            "history" if tokens.rest().split_whitespace().next() == Some("search") => {
+                tokens.next();
+                let regex = tokens.rest().starts_with("--regex ");
+                if regex {
+                    tokens.next();
+                }
+                HistorySearch {
+                    pattern: tokens.remainder().ok_or("expected a pattern")?.into_owned(),
+                    regex,
+                }
+            }
+            "history" => History,
+            "replay" => {
+                let path = next(tokens, "a file")?;
+                match &*path {
+                    "--step" => Replay { path: next(tokens, "a file")?.into_owned(), step: true },
+                    _ => Replay { path: path.into_owned(), step: false },
+                }
+            }
+            "snapshot" => Snapshot { name: next(tokens, "a name")?.into_owned() },
+            "diff" => Diff {
+                old: next(tokens, "a snapshot, branch or file")?.into_owned(),
+                new: tokens.next().map(|t| t.into_owned()),
+            },
+            "checkpoint" => Checkpoint { name: next(tokens, "a name")?.into_owned() },
+            "checkpoints" => ListCheckpoints,
+            "rollback" => Rollback { name: next(tokens, "a checkpoint")?.into_owned() },
+            "branch" => Branch { name: next(tokens, "a name")?.into_owned() },
+            "branches" => ListBranches,
+            "switch" => Switch { name: next(tokens, "a branch")?.into_owned() },
+            "macro" => {
+                let name = next_label(tokens, "a name")?;
+                next_as(tokens, "=", |t| (t == "=").then_some(()))?;
+                DefineMacro {
+                    name,
+                    body: tokens.remainder().ok_or("expected a body")?.into_owned(),
+                }
+            }
+            "macros" => ListMacros,
+            "template" => {
+                let name = next_label(tokens, "a name")?;
+                next_as(tokens, "=", |t| (t == "=").then_some(()))?;
+                DefineTemplate {
+                    name,
+                    body: tokens.remainder().ok_or("expected a payload")?.into_owned(),
+                }
+            }
+            "templates" => ListTemplates,
+            "let" => {
+                let name = next_label(tokens, "a name")?;
+                next_as(tokens, "=", |t| (t == "=").then_some(()))?;
+                return match Cmd::parse(tokens.rest(), now).map_err(|e| e.to_string())? {
+                    Update(
+                        update_cmd @ (Say { .. } | Agree { .. } | Amend { .. } | Enact { .. }),
+                    ) => Ok(Some(Let { name, update_cmd })),
+                    _ => Err("expected a say, sayto, agree, amend or enact command".to_string()),
+                };
+            }
+            "vars" => ListVariables,
+            "current-agreements" | "current-agreement" => CurrentAgreements,
+            "dedup" => Dedup,
+            "stats" if tokens.rest().split_whitespace().next() == Some("--memory") => {
+                tokens.next();
+                MemoryStats
+            }
+            "stats" => Stats,
+            "sayfile" => {
+                let sayer = next(tokens, "a sayer")?.into_owned();
+                let path = next(tokens, "a file")?.into_owned();
+                SayFile { sayer, path, label: parse_label(tokens)? }
+            }
+            "cat" => {
+                let (section, target) = next_target(tokens)?;
+                Cat { section, target }
+            }
+            "tui" => Tui,
+            "uses" => Uses { stmt: next_ref(tokens, "a statement")? },
+            "inbox" => Inbox { agent: next(tokens, "an agent")?.into_owned() },
+            "minimize" => {
+                let action = next_as(tokens, "an action index", |t| t.parse().ok())?;
+                let goal = match tokens.is_empty() {
+                    true => None,
+                    false if next(tokens, "for")? == "for" => {
+                        Some(next(tokens, "a fact")?.into_owned())
+                    }
+                    false => return Err("expected for <fact> after the action".to_string()),
+                };
+                Minimize { action, goal }
+            }
+            "explain" => {
+                Explain { action: next_as(tokens, "an action index", |t| t.parse().ok())? }
+            }
+            "why" => Why { action: next_as(tokens, "an action index", |t| t.parse().ok())? },
+            "sync" => Update(Sync {
+                from: next(tokens, "an agent to sync from")?.into_owned(),
+                to: next(tokens, "an agent to sync to")?.into_owned(),
+            }),
+            "partition" if tokens.is_empty() => ShowPartition,
+            "partition" => {
+                let mut groups = [vec![], vec![]];
+                let mut group = 0;
+                while !tokens.is_empty() {
+                    match &*next(tokens, "an agent")? {
+                        "|" if group == 0 => group = 1,
+                        "|" => return Err("expected two groups of agents".to_string()),
+                        agent => groups[group].push(agent.to_string()),
+                    }
+                }
+                if groups.iter().any(Vec::is_empty) {
+                    return Err("expected agents on both sides of |".to_string());
+                }
+                Update(Partition { groups })
+            }
+            "heal" => Update(Heal),
+            "autojustify" => {
+                let actor = next(tokens, "an actor")?.into_owned();
+                let basis = next_ref(tokens, "an agreement")?;
+                let (mut goal, mut enact) = (None, false);
+                while !tokens.is_empty() {
+                    match &*next(tokens, "for or --enact")? {
+                        "for" if goal.is_none() => {
+                            goal = Some(next(tokens, "a fact")?.into_owned())
+                        }
+                        "--enact" => enact = true,
+                        other => return Err(format!("expected for or --enact, got {:?}", other)),
+                    }
+                }
+                Autojustify { actor, basis, goal, enact }
+            }
+            "link" if tokens.is_empty() => ShowLinks,
+            "link" => {
+                let from = next(tokens, "an agent to link from")?.into_owned();
+                let to = next(tokens, "an agent to link to")?.into_owned();
+                let (mut delay, mut drop) = (0, 0.0);
+                while !tokens.is_empty() {
+                    match &*next(tokens, "delay or drop")? {
+                        "delay" => delay = next_as(tokens, "a delay", |t| t.parse().ok())?,
+                        "drop" => {
+                            drop = next_as(tokens, "a probability between 0 and 1", |t| {
+                                t.parse().ok().filter(|p| (0.0..=1.0).contains(p))
+                            })?
+                        }
+                        other => return Err(format!("expected delay or drop, got {:?}", other)),
+                    }
+                }
+                Update(Link { from, to, delay, drop })
+            }
+            "trust" => Update(Trust {
+                truster: next(tokens, "a truster")?.into_owned(),
+                trustee: next(tokens, "a trustee")?.into_owned(),
+            }),
+            "check" => Check {
+                script: next(tokens, "a script")?.into_owned(),
+                expected: next(tokens, "an expected dump")?.into_owned(),
+            },
+            "compare" => Compare {
+                old: next(tokens, "a trace")?.into_owned(),
+                new: next(tokens, "another trace")?.into_owned(),
+            },
+            "verify" => {
+                let path = tokens.next().map(|t| t.into_owned());
+                let keys = match tokens.next() {
+                    Some(flag) if path.is_some() && flag == "--keys" => {
+                        Some(next(tokens, "a key file")?.into_owned())
+                    }
+                    Some(other) => return Err(format!("expected --keys, got {:?}", other)),
+                    None => None,
+                };
+                Verify { path, keys }
+            }
+            "schedule" => {
+                let at = next_time(tokens, now)?;
+                return match Cmd::parse(tokens.rest(), now).map_err(|e| e.to_string())? {
+                    Update(update_cmd @ Enact { .. }) => Ok(Some(Update(UpdateCmd::Schedule {
+                        at,
+                        update_cmd: Box::new(update_cmd),
+                    }))),
+                    _ => Err("expected an enact command".to_string()),
+                };
+            }
+            "scheduled" => ListScheduled,
+            "help" => Help { command: tokens.next().map(|t| t.into_owned()) },
+            "undo" => Undo,
+            "redo" => Redo,
+            "comment" => return Ok(Some(Comment)),
+            _ => return Ok(None),
+        };
+        // Trailing tokens are either superfluous or malformed
+        match tokens.is_empty() {
+            true => Ok(Some(cmd)),
+            false => Err(format!("unexpected {:?}", tokens.rest())),
+        }
+    }
+}
+
+impl Config {
+    /// Parses a command, which may also be an invocation of a defined macro,
+    /// after substituting the values of the variables it refers to.
+    fn parse(&self, command: &str) -> Result<Cmd, ParseError> {
+        let command = self.substitute(command);
+        let command = &*self.expand_template(&command)?;
+        match Cmd::parse(command, self.current) {
+            Err(ParseError::UnknownCommand { keyword, .. })
+                if self.macros.contains_key(&keyword) =>
+            {
+                let mut tokens = Tokens::new(command);
+                tokens.next();
+                let mut args = vec![];
+                while !tokens.is_empty() {
+                    let arg = next(&mut tokens, "an argument");
+                    args.push(
+                        arg.map_err(|reason| ParseError::Malformed {
+                            keyword: keyword.clone(),
+                            reason,
+                        })?
+                        .into_owned(),
+                    );
+                }
+                Ok(Cmd::Invoke { name: keyword, args })
+            }
+            result => result,
+        }
+    }
+
+    /// Parses and executes a single command, explaining why it is malformed on failure.
+    fn run(&mut self, command: &str) -> Flow {
+        let cmd = match self.parse(command) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                println!("{}", e);
+                if let ParseError::Malformed { keyword, .. } = &e {
+                    help::print_usage(keyword);
+                }
+                return Flow::Continue;
+            }
+        };
+        println!("{:?}", cmd);
+        self.record(command.trim());
+        let journaled = (self.journal.len(), self.undone.len());
+        let (actions, replays) = (self.enacted.len(), self.replays);
+        let mut flow = self.execute(cmd).unwrap_or_else(|e| {
+            println!("{}", e);
+            Flow::Continue
+        });
+        if let Flow::Continue = flow {
+            flow = self.react();
+        }
+        // Audits incrementally only if the journal was extended, not replaced or undone
+        if self.continuous_audit && self.replays == replays {
+            self.audit_updates(journaled.0, actions);
+        }
+        self.feed_inspector();
+        if self.watch && journaled != (self.journal.len(), self.undone.len()) {
+            print!("{}", render::clear());
+            for line in self.show_lines(None, &query::Filter::default()) {
+                println!("{}", line);
+            }
+        }
+        flow
+    }
+
+    /// Sends the live inspector the events of the latest commands, if there is one.
+    fn feed_inspector(&mut self) {
+        if let Err(e) = self.feed_live_inspector() {
+            println!("Cannot feed inspector {:?}: {}", self.inspector, e);
+            self.live_inspector = None;
+        }
+    }
+
+    /// Executes the command, failing if it could not be carried out.
+    fn execute(&mut self, cmd: Cmd) -> Result<Flow, String> {
+        self.sync_clock()?;
+        match cmd {
+            Cmd::Tick { .. } | Cmd::Step { .. }
+                if matches!(self.clock, clock::Clock::Real { .. }) =>
+            {
+                return Err("Time follows the system clock, unless with set clock manual".into());
+            }
+            Cmd::Update(UpdateCmd::Now { now, .. }) => self.set_time(now, false)?,
+            Cmd::ForceNow { now } => self.set_time(now, true)?,
+            Cmd::Tick { by } => {
+                self.advance(self.current.checked_add(by).ok_or("Time cannot advance that far")?)?
+            }
+            Cmd::Update(update_cmd) => self.update_and_bind(update_cmd, None)?,
+            Cmd::Let { name, update_cmd } => self.update_and_bind(update_cmd, Some(name))?,
+            Cmd::ListVariables => self.list_variables(),
+            Cmd::ListScheduled => self.list_scheduled(),
+            Cmd::CurrentAgreements => match self.current_agreements() {
+                current if current.is_empty() => println!("No current agreements"),
+                current => self.print_rows(query::Section::Agreements, &current),
+            },
+            Cmd::Dedup => self.print_duplicates(),
+            Cmd::Stats => self.print_stats(),
+            Cmd::MemoryStats => self.print_memory_stats(),
+            Cmd::SayFile { sayer, path, label } => {
+                let payload = std::fs::read_to_string(self.relative_path(Path::new(&path)))
+                    .map_err(|e| format!("Cannot read {:?}: {}", path, e))?;
+                let (payload, source) = (payload.into(), Some(path));
+                let say = UpdateCmd::Say { sayer, payload, label, recipients: None, source };
+                self.update_and_bind(say, None)?
+            }
+            Cmd::Cat { section, target } => self.cat(section, &target)?,
+            #[cfg(feature = "tui")]
+            Cmd::Tui => self.tui().map_err(|e| format!("Cannot run the TUI: {}", e))?,
+            #[cfg(not(feature = "tui"))]
+            Cmd::Tui => return Err("The TUI requires the tui feature".into()),
+            Cmd::ShowTrust => self.show_trust(),
+            Cmd::ShowResources => self.show_resources(),
+            Cmd::Uses { stmt } => self.print_uses(stmt.resolve(&self.stmt_labels)?)?,
+            Cmd::Inbox { agent } => self.print_inbox(&agent),
+            Cmd::ShowPartition => self.print_partition(),
+            Cmd::CheckEnact { enact } => self.check_enact(&enact)?,
+            Cmd::Autojustify { actor, basis, goal, enact } => {
+                self.autojustify(&actor, &basis, goal.as_deref(), enact)?
+            }
+            Cmd::ShowLinks => self.print_links(),
+            Cmd::Why { action } => self.why(action)?,
+            Cmd::Explain { action } => self.explain(action)?,
+            Cmd::Minimize { action, goal } => self.minimize(action, goal.as_deref())?,
+            Cmd::Check { script, expected } => self.check(&script, &expected)?,
+            Cmd::Compare { old, new } => compare::compare_files(&old, &new)?,
+            Cmd::Verify { path, keys } => {
+                let keys = keys.map(|keys| verify::read_keys(Path::new(&keys))).transpose()?;
+                let verification = match &path {
+                    Some(path) => dump::read_trace(Path::new(path))
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| self.verify_trace(&text, keys.as_ref()))
+                        .map_err(|e| format!("Cannot verify {:?}: {}", path, e))?,
+                    None => self.verify_session(),
+                };
+                verification.print();
+                if !verification.failures.is_empty() {
+                    return Err(format!(
+                        "{} entries failed verification",
+                        verification.failures.len()
+                    ));
+                }
+            }
+            Cmd::Help { command: None } => help::print_help(),
+            Cmd::Help { command: Some(command) } => match self.macros.get(&command) {
+                Some(body) => println!("{} is a macro for: {}", command, body),
+                None => help::print_command_help(&command)?,
+            },
+            Cmd::Quit => return Ok(Flow::Quit),
+            Cmd::Inspect { mut filter, live, target } => {
+                if let Some((section, target)) = target {
+                    filter.focus = Some(self.focus(section, &target)?);
+                }
+                let inspected = if live {
+                    self.start_live_inspector(filter)
+                } else {
+                    self.run_inspection(&filter)
+                };
+                inspected
+                    .map_err(|e| format!("Cannot run inspector {:?}: {}", self.inspector, e))?
+            }
+            Cmd::StopInspect => self.stop_live_inspector()?,
+            Cmd::Dump { path: None, format, filter, .. } => {
+                self.dump(format, &filter).map_err(|e| format!("Cannot dump: {}", e))?
+            }
+            Cmd::Dump { path: Some(path), append, format, filter } => self
+                .dump_to(Path::new(&path), append, format, &filter)
+                .map_err(|e| format!("Cannot dump to {:?}: {}", path, e))?,
+            Cmd::Show { viewer, filter } => self.show(viewer.as_deref(), &filter),
+            Cmd::Comment => (),
+            Cmd::Load { path } => return self.load(Path::new(&path)),
+            Cmd::Undo => self.undo(),
+            Cmd::Redo => self.redo(),
+            Cmd::Save { path } => {
+                self.save(Path::new(&path)).map_err(|e| format!("Cannot save {:?}: {}", path, e))?
+            }
+            Cmd::Restore { path } => self
+                .restore(Path::new(&path))
+                .map_err(|e| format!("Cannot restore {:?}: {}", path, e))?,
+            Cmd::Import { path } => self
+                .import(Path::new(&path))
+                .map_err(|e| format!("Cannot import {:?}: {}", path, e))?,
+            Cmd::ListScenarios => self.list_scenarios(),
+            Cmd::LoadScenario { name } => return self.load_scenario(&name),
+            Cmd::ImportCsv { path, delimiter, columns } => {
+                self.import_csv(&path, delimiter, columns)?
+            }
+            Cmd::SetInspector { command } => self.inspector = command,
+            Cmd::SetClock { real } => self.set_clock(real),
+            Cmd::SetWatch { watch } => self.watch = watch,
+            Cmd::SetAudit { continuous } => self.continuous_audit = continuous,
+            Cmd::SetPolicy { name } => {
+                self.policy = policy::by_name(&name)
+                    .ok_or(format!("Unknown or disabled policy engine {:?}", name))?
+            }
+            Cmd::SetSchema { name } if name == "none" => self.schema = None,
+            Cmd::SetSchema { name } => self.schema = Some(schema::Schema::load(&name)?),
+            Cmd::ListAgents => self.list_agents(),
+            Cmd::Keygen { name } => self.keygen(name)?,
+            Cmd::RunAgent { name, script } => self.run_agent(name, &script)?,
+            Cmd::Step { n, seed } => return self.step(n, seed),
+            Cmd::StopAgent { name } => self.stop_agent(&name)?,
+            Cmd::Audit => self.audit(),
+            Cmd::Eval { stmts } => self.eval(&stmts)?,
+            Cmd::Export { format, path } => self
+                .export(format, Path::new(&path))
+                .map_err(|e| format!("Cannot export to {:?}: {}", path, e))?,
+            Cmd::History => self.print_history(),
+            Cmd::HistorySearch { pattern, regex } => {
+                let pattern = query::Pattern::new(&pattern, regex)
+                    .map_err(|e| format!("Invalid pattern {:?}: {}", pattern, e))?;
+                self.search_history(&pattern)
+            }
+            Cmd::Branch { name } => self.branch(name)?,
+            Cmd::Switch { name } => self.switch(name)?,
+            Cmd::ListBranches => self.list_branches(),
+            Cmd::Checkpoint { name } => self.checkpoint(name),
+            Cmd::ListCheckpoints => self.list_checkpoints(),
+            Cmd::Rollback { name } => self.rollback(&name)?,
+            Cmd::Snapshot { name } => {
+                let snapshot = self.snapshot();
+                self.snapshots.insert(name, snapshot);
+            }
+            Cmd::Diff { old, new } => {
+                let by_name = |name: &str| {
+                    self.snapshot_by_name(name).map_err(|e| {
+                        format!("No snapshot {:?}, and cannot read it as a file: {}", name, e)
+                    })
+                };
+                let new = match &new {
+                    Some(new) => by_name(new)?,
+                    None => self.snapshot(),
+                };
+                snapshot::print_diff(&by_name(&old)?, &new);
+            }
+            Cmd::Replay { path, step } => {
+                return self
+                    .replay_file(Path::new(&path), step)
+                    .map_err(|e| format!("Cannot replay {:?}: {}", path, e));
+            }
+            Cmd::Query(query) => match self.query(&query)? {
+                selected if selected.is_empty() => println!("No matches"),
+                selected => self.print_rows(query.section, &selected),
+            },
+            Cmd::Find { pattern, regex } => {
+                let pattern = query::Pattern::new(&pattern, regex)
+                    .map_err(|e| format!("Invalid pattern {:?}: {}", pattern, e))?;
+                self.find(&pattern)
+            }
+            Cmd::DefineMacro { name, body } => self.define_macro(name, body)?,
+            Cmd::ListMacros => self.list_macros(),
+            Cmd::DefineTemplate { name, body } => self.define_template(name, body),
+            Cmd::ListTemplates => self.list_templates(),
+            Cmd::Invoke { name, args } => return self.invoke(&name, &args),
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// The path, relative to the directory of the script being loaded, if any.
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        match self.loading.last().and_then(|outer| outer.parent()) {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Executes the commands in the given script file. Relative paths are resolved
+    /// w.r.t. the directory of the script doing the loading, if any.
+    /// Loading stops at the first command that cannot be parsed, or, if failing fast,
+    /// at the first command that fails.
+    fn load(&mut self, path: &Path) -> Result<Flow, String> {
+        let path = self.relative_path(path);
+        let read = path.canonicalize().and_then(|c| std::fs::read_to_string(&c).map(|t| (c, t)));
+        let (canonical, text) = read.map_err(|e| format!("Cannot load {:?}: {}", path, e))?;
+        if self.loading.contains(&canonical) {
+            return Err(format!("Cannot load {:?} while it is already being loaded", path));
+        }
+        self.loading.push(canonical);
+        let result = self.run_script(&path.display().to_string(), &text);
+        self.loading.pop();
+        result
+    }
+
+    /// Runs the commands of a script, reporting failing ones by the name of the script.
+    fn run_script(&mut self, name: &str, text: &str) -> Result<Flow, String> {
+        for (line, command) in tokens::commands(text) {
+            let cmd = self.parse(&command).map_err(|e| format!("{}:{}: {}", name, line, e))?;
+            println!("{:?}", cmd);
+            match self.execute(cmd) {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Quit) => return Ok(Flow::Quit),
+                Err(e) if self.fail_fast => return Err(format!("{}:{}: {}", name, line, e)),
+                Err(e) => println!("{}:{}: {}", name, line, e),
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Applies the given update, recording it in the journal if it succeeds.
+    fn update(&mut self, update_cmd: UpdateCmd) {
+        if let Err(e) = self.try_update(update_cmd) {
+            println!("{}", e);
+        }
+    }
+
+    fn try_update(&mut self, mut update_cmd: UpdateCmd) -> Result<(), String> {
+        if let UpdateCmd::Say { payload, .. } = &mut update_cmd {
+            *payload = self.pool.intern(payload);
+        }
+        self.validate(&update_cmd)?;
+        self.apply(&update_cmd)?;
+        self.journal.push(update_cmd);
+        self.undone.clear();
+        Ok(())
+    }
+
+    fn apply(&mut self, update_cmd: &UpdateCmd) -> Result<(), String> {
+        match update_cmd {
+            UpdateCmd::Say { sayer, payload, label, recipients, source } => {
+                self.check_agent(sayer)?;
+                for recipient in recipients.iter().flatten() {
+                    self.check_agent(recipient)?;
+                }
+                let id = (self.id_scheme.message_id(self.statements.len(), sayer, payload))
+                    .ok_or("Cannot number any more statements")?;
+                if let Some(taken) = self.stmt_idx_by_id(&(sayer.clone(), id)) {
+                    return Err(format!(
+                        "Statement {} by {} has the same identifier {} already",
+                        taken, sayer, id
+                    ));
+                }
+                if let Some(label) = label {
+                    insert_label(&mut self.stmt_labels, label, self.statements.len())?;
+                }
+                if let Some(recipients) = recipients {
+                    self.recipients.insert(self.statements.len(), recipients.clone());
+                }
+                if let Some(source) = source {
+                    self.sources.insert(self.statements.len(), source.clone());
+                }
+                if let Some(value) = json::parse(payload) {
+                    self.structured.insert(self.statements.len(), value);
+                }
+                let msg =
+                    Arc::new(Message { id: (sayer.clone(), id), payload: payload.to_string() });
+                self.index.add_statement(self.statements.len(), &msg);
+                self.statements.push(msg);
+                self.stated_at.push(self.current);
+                self.deliver_said();
+                self.sign(self.statements.len() - 1);
+            }
+            UpdateCmd::Agree { on_idx, at, until, label } => {
+                self.agree(on_idx, *at, *until, label)?
+            }
+            UpdateCmd::Amend { supersedes, on_idx, at, until, label } => {
+                let supersedes = supersedes.resolve(&self.agree_labels)?;
+                if supersedes >= self.agreements.len() {
+                    return Err(format!("Cannot amend unmade agreement {}", supersedes));
+                } else if let Some(by) = self.superseded_by.get(&supersedes) {
+                    return Err(format!(
+                        "Agreement {} is already superseded by {}",
+                        supersedes, by
+                    ));
+                }
+                self.agree(on_idx, *at, *until, label)?;
+                self.superseded_by.insert(supersedes, self.agreements.len() - 1);
+            }
+            UpdateCmd::Enact { actor, basis, justification, effect } => {
+                let (action, basis) = self.new_action(actor, basis, justification)?;
+                if let Some(effect) = effect {
+                    self.apply_effect(actor, effect)?;
+                    self.effects.insert(self.enacted.len(), effect.clone());
+                }
+                self.index.add_action(self.enacted.len(), &action);
+                self.enacted.push(action);
+                self.enacted_at.push(self.current);
+                self.enacted_basis.push(basis);
+            }
+            UpdateCmd::Now { now, .. } => self.commit_scheduled(*now),
+            UpdateCmd::Retract { retractor, stmt } => {
+                self.check_agent(retractor)?;
+                let idx = stmt.resolve(&self.stmt_labels)?;
+                let Some(s) = self.statements.get(idx) else {
+                    return Err(format!("Cannot retract unsaid message {}", idx));
+                };
+                if s.id.0 != *retractor {
+                    return Err(format!("Only {} can retract message {}", s.id.0, idx));
+                } else if !self.retracted.insert(idx) {
+                    return Err(format!("Message {} is already retracted", idx));
+                }
+                for &i in self.actions_using(s) {
+                    if self.enacted[i].justification.iter().any(|m| m.id == s.id) {
+                        println!("Warning: action {} is justified by retracted message {}", i, idx);
+                    }
+                }
+            }
+            UpdateCmd::Withdraw { actor, action } => {
+                self.check_agent(actor)?;
+                let Some(e) = self.enacted.get(*action) else {
+                    return Err(format!("Cannot withdraw unenacted action {}", action));
+                };
+                if e.id.0 != *actor {
+                    return Err(format!("Only {} can withdraw action {}", e.id.0, action));
+                } else if let Some(at) = self.withdrawn.get(action) {
+                    return Err(format!("Action {} is already withdrawn at {}", action, at));
+                }
+                self.withdrawn.insert(*action, self.current);
+            }
+            UpdateCmd::Schedule { at, update_cmd } => self.schedule(*at, update_cmd)?,
+            UpdateCmd::AddAgent { name, info } => {
+                if self.agents.contains_key(name) {
+                    return Err(format!("Agent {} is already registered", name));
+                }
+                self.agents.insert(name.clone(), info.clone());
+            }
+            UpdateCmd::RemoveAgent { name } => {
+                if self.agents.remove(name).is_none() {
+                    return Err(format!("Agent {} is not registered", name));
+                }
+            }
+            UpdateCmd::Strict { strict } => self.strict = *strict,
+            UpdateCmd::Enforce { enforce } => self.enforce = *enforce,
+            UpdateCmd::IdScheme { scheme } => self.id_scheme = *scheme,
+            UpdateCmd::Trust { truster, trustee } => {
+                self.check_agent(truster)?;
+                self.check_agent(trustee)?;
+                if truster == trustee {
+                    return Err(format!("Agent {} always trusts itself", truster));
+                }
+                self.trust.entry(truster.clone()).or_default().insert(trustee.clone());
+            }
+            UpdateCmd::Sync { from, to } => self.sync(from, to)?,
+            UpdateCmd::Partition { groups } => self.partition(groups)?,
+            UpdateCmd::Link { from, to, delay, drop } => self.link(from, to, *delay, *drop)?,
+            UpdateCmd::AddDataset { name, owner } => self.add_dataset(name, owner)?,
+            UpdateCmd::Read { reader, dataset, via } => self.read(reader, dataset, *via)?,
+            UpdateCmd::Heal => {
+                if self.partition.take().is_none() {
+                    return Err("The agents are not partitioned".to_string());
+                }
+            }
+            UpdateCmd::AddKey { agent, public } => {
+                self.check_agent(agent)?;
+                signing::parse_public_key(public).ok_or("Malformed public key")?;
+                match self.secrets.get(public) {
+                    Some(secret) => self.keys.insert(agent.clone(), secret.clone()),
+                    None => self.keys.remove(agent),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// In strict mode, rejects agents that are not registered.
+    fn check_agent(&self, name: &str) -> Result<(), String> {
+        if self.strict && !self.agents.contains_key(name) {
+            return Err(format!("Agent {} is not registered", name));
+        }
+        Ok(())
+    }
+
+    fn list_agents(&self) {
+        println!("strict mode: {}", if self.strict { "on" } else { "off" });
+        if !self.agents.is_empty() {
+            println!("___name____|___role____|___description___ AGENTS");
+            for (name, info) in &self.agents {
+                let role = info.role.as_deref().unwrap_or("");
+                let [a, b] = trucated(info.description.as_deref().unwrap_or(""));
+                println!("{: >10} | {: <9} | {}{}", name, role, a, b);
+            }
+        }
+        self.list_behaviors();
+    }
+
+    fn agree(
+        &mut self,
+        on_idx: &Ref,
+        at: Time,
+        until: Option<Time>,
+        label: &Option<String>,
+    ) -> Result<(), String> {
+        let on_idx = on_idx.resolve(&self.stmt_labels)?;
+        let Some(s) = self.statements.get(on_idx) else {
+            return Err("Limitation: cannot agree on unsaid messages!".into());
+        };
+        if until.is_some_and(|until| until < at) {
+            return Err(format!("Agreement cannot expire before it applies at {}", at));
+        }
+        let agreement = Agreement { at, message: s.clone() };
+        if let Some(label) = label {
+            insert_label(&mut self.agree_labels, label, self.agreements.len())?;
+        }
+        if let Some(until) = until {
+            self.expires_at.insert(self.agreements.len(), until);
+        }
+        self.index.add_agreement(self.agreements.len(), &agreement.message);
+        self.agreements.push(agreement);
+        Ok(())
+    }
+
+    /// Whether the agreement applies at the given time, i.e., the time is within its
+    /// validity window, or equals its time if it has none.
+    fn applies_at(&self, idx: AgreeIdx, time: Time) -> bool {
+        let at = self.agreements[idx].at;
+        match self.expires_at.get(&idx) {
+            Some(&until) => (at..=until).contains(&time),
+            None => at == time,
+        }
+    }
+
+    /// The agreements that apply at the current time, and have not been superseded by then.
+    fn current_agreements(&self) -> Vec<AgreeIdx> {
+        (0..self.agreements.len()).filter(|&i| self.not_current(i).is_none()).collect()
+    }
+
+    /// The action that the actor would enact next, based on the agreement and justified by
+    /// the statements, with the index of that agreement.
+    fn new_action(
+        &self,
+        actor: &str,
+        basis: &Ref,
+        justification: &HashSet<Ref>,
+    ) -> Result<(Action, AgreeIdx), String> {
+        self.check_agent(actor)?;
+        let basis = basis.resolve(&self.agree_labels)?;
+        let justification: HashSet<StmtIdx> =
+            justification.iter().map(|r| r.resolve(&self.stmt_labels)).collect::<Result<_, _>>()?;
+        if basis >= self.agreements.len() {
+            return Err(format!("Cannot be based using unsaid message {}", basis));
+        } else if let Some(id) = justification.iter().find(|&&id| id >= self.statements.len()) {
+            return Err(format!("Cannot justify using unsaid message {}", id));
+        }
+        let id = action_char(self.enacted.len()).ok_or("Cannot identify any more actions")?;
+        let action = Action {
+            id: (actor.to_string(), id),
+            basis: self.agreements[basis].clone(),
+            justification: justification.iter().map(|&idx| self.statements[idx].clone()).collect(),
+        };
+        Ok((action, basis))
+    }
+
+    /// Why the agreement does not apply at the current time, or has been superseded by
+    /// then, if so.
+    fn not_current(&self, idx: AgreeIdx) -> Option<String> {
+        if !self.applies_at(idx, self.current) {
+            let at = self.agreements[idx].at;
+            return Some(match self.expires_at.get(&idx) {
+                Some(until) => format!("it applies from {} until {}", at, until),
+                None => format!("it applies at {} only", at),
+            });
+        }
+        let chain = self.supersession_chain(idx);
+        let by = chain.iter().take_while(|&&by| self.agreements[by].at <= self.current).last()?;
+        Some(format!("it is superseded by agreement {}", by))
+    }
+
+    /// The agreements superseding the given one, oldest first.
+    fn supersession_chain(&self, mut idx: AgreeIdx) -> Vec<AgreeIdx> {
+        let mut chain = vec![];
+        while let Some(&by) = self.superseded_by.get(&idx) {
+            chain.push(by);
+            idx = by;
+        }
+        chain
+    }
+
+    /// Whether the given action is justified by any retracted statement.
+    fn relies_on_retracted(&self, action: &Action) -> bool {
+        self.retracted
+            .iter()
+            .any(|&idx| action.justification.iter().any(|m| m.id == self.statements[idx].id))
+    }
+
+    /// Reverts the most recent update, if any, other than ticks of the system clock.
+    fn undo(&mut self) {
+        let Some(idx) =
+            self.journal.iter().rposition(|u| !matches!(u, UpdateCmd::Now { tick: true, .. }))
+        else {
+            println!("Nothing to undo");
+            return;
+        };
+        self.undone.push(self.journal.remove(idx));
+        self.replay();
+    }
+
+    /// Re-applies the most recently undone update, if any.
+    fn redo(&mut self) {
+        let Some(update_cmd) = self.undone.pop() else {
+            println!("Nothing to redo");
+            return;
+        };
+        match self.validate(&update_cmd).and_then(|()| self.apply(&update_cmd)) {
+            Ok(()) => self.journal.push(update_cmd),
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    /// Rebuilds the state from scratch by re-applying the journal. Updates that no
+    /// longer apply, e.g., because they depended on an undone statement, are dropped.
+    fn replay(&mut self) {
+        self.replays += 1;
+        self.current = 0;
+        self.statements.clear();
+        self.agreements.clear();
+        self.enacted.clear();
+        self.enacted_at.clear();
+        self.enacted_basis.clear();
+        self.effects.clear();
+        self.resources.clear();
+        self.reads.clear();
+        self.stated_at.clear();
+        self.recipients.clear();
+        self.inboxes.clear();
+        self.forwarded.clear();
+        self.partition = None;
+        self.cut_off.clear();
+        self.links.clear();
+        self.delayed.clear();
+        self.sources.clear();
+        self.structured.clear();
+        self.index.clear();
+        self.retracted.clear();
+        self.withdrawn.clear();
+        self.scheduled.clear();
+        self.stmt_labels.clear();
+        self.agree_labels.clear();
+        self.superseded_by.clear();
+        self.expires_at.clear();
+        self.agents.clear();
+        self.strict = false;
+        self.enforce = false;
+        self.id_scheme = ids::IdScheme::default();
+        self.keys.clear();
+        self.trust.clear();
+        self.signatures.clear();
+        // Sessions restored from files do not share the payloads of the pool yet
+        for update_cmd in self.journal.iter_mut().chain(&mut self.undone) {
+            if let UpdateCmd::Say { payload, .. } = update_cmd {
+                *payload = self.pool.intern(payload);
+            }
+        }
+        let journal = std::mem::take(&mut self.journal);
+        for update_cmd in journal {
+            match self.apply(&update_cmd) {
+                Ok(()) => self.journal.push(update_cmd),
+                Err(e) => println!("Dropping {:?}: {}", update_cmd, e),
+            }
+        }
+        self.pool.prune();
+    }
+
+    /// Writes the state as a trace of control events. The trace has no notion of
+    /// retraction, so retracted statements are simply not stated.
+    fn write_inspection<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        self.write_dump(w, dump::Format::JsonLines, &inspector::EventFilter::default())
+    }
+
+    /// The selected trace of the state, as the control events the inspector digests.
+    fn inspection_events<'a>(
+        &'a self,
+        filter: &'a inspector::EventFilter,
+    ) -> impl Iterator<Item = Event<'static>> + 'a {
+        self.events_after(&inspector::Progress::default(), filter)
+    }
+
+    /// Evaluates the payloads of the given statements as Datalog, printing all derived
+    /// facts. Evaluates all statements that have not been retracted if none are given.
+    fn eval(&self, stmts: &HashSet<Ref>) -> Result<(), String> {
+        let mut stmts: Vec<StmtIdx> =
+            stmts.iter().map(|r| r.resolve(&self.stmt_labels)).collect::<Result<_, _>>()?;
+        if stmts.is_empty() {
+            stmts = (0..self.statements.len()).filter(|i| !self.retracted.contains(i)).collect();
+        }
+        stmts.sort();
+        let mut rules = vec![];
+        for idx in stmts {
+            let s = self
+                .statements
+                .get(idx)
+                .ok_or(format!("Cannot evaluate unsaid message {}", idx))?;
+            rules.extend(
+                datalog::parse(&s.payload).map_err(|e| format!("Statement {}: {}", idx, e))?,
+            );
+        }
+        let facts = datalog::closure(&rules);
+        for fact in &facts {
+            println!("{}.", fact);
+        }
+        if facts.iter().any(|f| f.pred == "error" && f.args.is_empty()) {
+            println!("error is derived: the statements are not permissible");
+        }
+        Ok(())
+    }
+
+    /// Whether the given agent has received the given statement.
+    fn is_visible_to(&self, idx: StmtIdx, agent: &str) -> bool {
+        self.statements[idx].id.0 == agent || self.received_at(agent, idx).is_some()
+    }
+
+    /// Shows the selected state, or only the part thereof that the given viewer has
+    /// received, paged if it does not fit on the terminal. Agreements and actions are
+    /// always stated to all agents.
+    fn show(&self, viewer: Option<&str>, filter: &query::Filter) {
+        pager::page(&self.show_lines(viewer, filter));
+    }
+
+    fn show_lines(&self, viewer: Option<&str>, filter: &query::Filter) -> Vec<String> {
+        let mut lines = vec![format!("current time: {}", render::bold(&self.current.to_string()))];
+        if let Some(viewer) = viewer {
+            lines.push(format!("as seen by: {}", viewer));
+        }
+        let limit = |indices| match filter.limit {
+            Some(limit) => limit.apply(indices),
+            None => indices,
+        };
+        let visible = limit(self.query_statements(filter, viewer).map(|(i, _)| i).collect());
+        lines.extend(self.rows(query::Section::Statements, &visible));
+        let agreements = limit(self.query_agreements(filter).map(|(i, _)| i).collect());
+        lines.extend(self.rows(query::Section::Agreements, &agreements));
+        let enacted = limit(self.query_actions(filter).map(|(i, _)| i).collect());
+        lines.extend(self.rows(query::Section::Actions, &enacted));
+        lines
+    }
+
+    /// Prints the given statements, agreements or actions as a table, if there are any.
+    fn print_rows(&self, section: query::Section, indices: &[usize]) {
+        for line in self.rows(section, indices) {
+            println!("{}", line);
+        }
+    }
+
+    /// The lines of a table of the given statements, agreements or actions, if there are any.
+    fn rows(&self, section: query::Section, indices: &[usize]) -> Vec<String> {
+        if indices.is_empty() {
+            return vec![];
+        }
+        let (header, row): (&str, fn(&Self, usize) -> String) = match section {
+            query::Section::Statements => {
+                ("__stmt.id__|___sayer___|___payload___ STATEMENTS", Self::statement_row)
+            }
+            query::Section::Agreements => {
+                ("___ag.id___|___s_id___|___time___ AGREEMENTS", Self::agreement_row)
+            }
+            query::Section::Actions => (
+                "___act.id__|___actor___|___basis___|___justification___ ENACTED ACTIONS",
+                Self::action_row,
+            ),
+        };
+        let rows = indices.iter().map(|&i| row(self, i));
+        std::iter::once(header.to_string()).chain(rows).collect()
+    }
+
+    fn statement_row(&self, i: StmtIdx) -> String {
+        let s = &self.statements[i];
+        let [a, b] = trucated(&s.payload);
+        let retracted = if self.retracted.contains(&i) { " (retracted)" } else { "" };
+        let unsigned =
+            if self.signing() && !self.signatures.contains_key(&i) { " (unsigned)" } else { "" };
+        let to = match self.recipients.get(&i) {
+            Some(recipients) => format!(" (to {})", recipients.join(", ")),
+            None => String::new(),
+        };
+        // Messages are only identified otherwise than by their indices with hashed ids
+        let id = if s.id.1 as usize != i { format!(" (id {:08x})", s.id.1) } else { String::new() };
+        let i = labelled(&self.stmt_labels, i);
+        let row = format!(
+            "{: >8} | {: <9} | {:?}{}{}{}{}{}",
+            i, s.id.0, a, b, id, to, unsigned, retracted
+        );
+        render::agent(&s.id.0, &row)
+    }
+
+    fn agreement_row(&self, i: AgreeIdx) -> String {
+        let a = &self.agreements[i];
+        let until = match self.expires_at.get(&i) {
+            Some(until) => format!(" until {}", until),
+            None => String::new(),
+        };
+        let superseded = match self.superseded_by.get(&i) {
+            Some(by) => format!(" (superseded by {})", by),
+            None => String::new(),
+        };
+        let current = if self.not_current(i).is_none() { " (current)" } else { "" };
+        let on = self.stmt_idx(&a.message).map_or("?".to_string(), |s| s.to_string());
+        let i = labelled(&self.agree_labels, i);
+        format!("{: >8} | {: <9} | {:?}{}{}{}", i, on, a.at, until, superseded, current)
+    }
+
+    fn action_row(&self, i: usize) -> String {
+        let e = &self.enacted[i];
+        let withdrawn = match self.withdrawn.get(&i) {
+            Some(at) => format!(" (withdrawn at {})", at),
+            None => String::new(),
+        };
+        let row = format!(
+            "{: >8} | {: <9} | {:?} | {:?}{}{}",
+            i,
+            e.id.0,
+            e.basis.at,
+            e.justification.iter().filter_map(|s| self.stmt_idx(s)).collect::<HashSet<_>>(),
+            if self.relies_on_retracted(e) { " (relies on retracted)" } else { "" },
+            withdrawn
+        );
+        render::agent(&e.id.0, &row)
+    }
+
+    fn find(&self, pattern: &query::Pattern) {
+        let found: Vec<_> = self.find_statements(pattern).collect();
+        if found.is_empty() {
+            println!("No matching statements");
+            return;
+        }
+        println!("__stmt.id__|___sayer___|___match___ STATEMENTS");
+        for (i, s, range) in found {
+            let i = labelled(&self.stmt_labels, i);
+            let row =
+                format!("{: >8} | {: <9} | {:?}", i, s.id.0, query::context(&s.payload, range));
+            println!("{}", render::agent(&s.id.0, &row));
+        }
+    }
+}
+
+fn insert_label(
+    labels: &mut HashMap<String, usize>,
+    label: &str,
+    idx: usize,
+) -> Result<(), String> {
+    if labels.contains_key(label) {
+        return Err(format!("Label {} is already in use", label));
+    }
+    labels.insert(label.to_string(), idx);
+    Ok(())
+}
+
+/// Displays an index along with its label, if it has one.
+fn labelled(labels: &HashMap<String, usize>, idx: usize) -> String {
+    match labels.iter().find(|&(_, &i)| i == idx) {
+        Some((label, _)) => format!("{} {}", label, idx),
+        None => idx.to_string(),
+    }
+}
+
+fn trucated(s: &str) -> [&str; 2] {
+    const MAX_BYTES: usize = 40;
+    if let Some(cutoff) = s.char_indices().nth(MAX_BYTES).map(|(idx, _)| idx) {
+        [&s[..cutoff], "..."]
+    } else {
+        [s, ""]
+    }
+}
+
+/// A session that executed the given commands, each of which must succeed.
+#[cfg(test)]
+fn config(commands: &[&str]) -> Config {
+    let mut config = Config::default();
+    for command in commands {
+        let cmd = config.parse(command).unwrap();
+        config.execute(cmd).unwrap();
+    }
+    config
+}
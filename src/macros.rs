@@ -0,0 +1,101 @@
+//! User-defined macros, which expand to a sequence of commands with positional parameters.
+
+use crate::{help, tokens, Config, Flow};
+use std::ops::Range;
+
+/// The parameters `$1`, `$2`, ... occurring in the body, with their byte ranges.
+fn params(body: &str) -> impl Iterator<Item = (Range<usize>, usize)> + '_ {
+    body.match_indices('$').filter_map(|(idx, _)| {
+        let digits = body[idx + 1..].find(|c: char| !c.is_ascii_digit());
+        let end = digits.map_or(body.len(), |digits| idx + 1 + digits);
+        let param = body[idx + 1..end].parse().ok()?;
+        Some((idx..end, param))
+    })
+}
+
+/// The commands of the body, with the arguments substituted for the parameters. The number
+/// of arguments must equal the highest parameter. The body is split into commands first,
+/// such that arguments containing `;` do not split them.
+fn expand(body: &str, args: &[String]) -> Result<Vec<String>, String> {
+    let expected = params(body).map(|(_, param)| param).max().unwrap_or(0);
+    if expected != args.len() {
+        return Err(format!("expected {} arguments, but got {}", expected, args.len()));
+    }
+    let expand_command = |command: &str| {
+        let mut expanded = String::new();
+        let mut end = 0;
+        for (range, param) in params(command) {
+            expanded.push_str(&command[end..range.start]);
+            expanded.push_str(&tokens::quote(&args[param - 1]));
+            end = range.end;
+        }
+        expanded.push_str(&command[end..]);
+        expanded
+    };
+    Ok(tokens::split_commands(body).map(expand_command).collect())
+}
+
+impl Config {
+    pub fn define_macro(&mut self, name: String, body: String) -> Result<(), String> {
+        if help::is_keyword(&name) {
+            return Err(format!("Cannot define macro {:?}, as it is a command", name));
+        }
+        if params(&body).any(|(_, param)| param == 0) {
+            return Err("Macro parameters are numbered from $1".to_string());
+        }
+        self.macros.insert(name, body);
+        Ok(())
+    }
+
+    pub fn list_macros(&self) {
+        for (name, body) in &self.macros {
+            println!("{: <16} = {}", name, body);
+        }
+    }
+
+    /// Executes the commands of the macro, with the given arguments, until one fails.
+    pub fn invoke(&mut self, name: &str, args: &[String]) -> Result<Flow, String> {
+        if self.expanding.iter().any(|outer| outer == name) {
+            return Err(format!(
+                "Cannot invoke macro {:?} while it is already being expanded",
+                name
+            ));
+        }
+        let body = self.macros.get(name).ok_or(format!("Unknown macro {:?}", name))?;
+        let commands =
+            expand(body, args).map_err(|e| format!("Cannot invoke macro {:?}: {}", name, e))?;
+        self.expanding.push(name.to_string());
+        let mut result = Ok(Flow::Continue);
+        for command in &commands {
+            let cmd = match self.parse(command) {
+                Ok(cmd) => cmd,
+                Err(e) => {
+                    result = Err(format!(
+                        "Cannot parse command {:?} of macro {:?}: {}",
+                        command, name, e
+                    ));
+                    break;
+                }
+            };
+            println!("{:?}", cmd);
+            result = self.execute(cmd);
+            if !matches!(result, Ok(Flow::Continue)) {
+                break;
+            }
+        }
+        self.expanding.pop();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+
+    #[test]
+    fn splits_bodies_on_unquoted_semicolons_only() {
+        let config = config(&["macro deal = say amy $1; say bob \"c; d\"", "deal \"a; b\""]);
+        let payloads: Vec<&str> = config.statements.iter().map(|s| &*s.payload).collect();
+        assert_eq!(payloads, ["a; b", "c; d"]);
+    }
+}
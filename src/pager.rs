@@ -0,0 +1,85 @@
+//! Paging output that does not fit on the terminal, through `$PAGER` if it is set, or
+//! a screen at a time otherwise.
+
+use std::{
+    io::{BufRead, Write},
+    process::{Command, Stdio},
+};
+
+/// Environment variable with the command running the pager.
+const PAGER_VAR: &str = "PAGER";
+
+/// The number of lines of the terminal, if stdout is one.
+#[cfg(not(target_arch = "wasm32"))]
+fn height() -> Option<usize> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    terminal_size::terminal_size().map(|(_, height)| height.0 as usize)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn height() -> Option<usize> {
+    None
+}
+
+/// Prints the lines, paging them if they do not fit on the terminal.
+pub fn page(lines: &[String]) {
+    match height() {
+        Some(height) if lines.len() >= height => {
+            if let Ok(pager) = std::env::var(PAGER_VAR)
+                && !pager.trim().is_empty()
+            {
+                match external(&pager, lines) {
+                    Ok(()) => return,
+                    Err(e) => println!("Cannot run pager {:?}: {}", pager, e),
+                }
+            }
+            internal(lines, height)
+        }
+        _ => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+fn external(pager: &str, lines: &[String]) -> std::io::Result<()> {
+    let mut command = pager.split_whitespace();
+    let program = command.next().unwrap_or_default();
+    // As git does, lest `less` show the styling as escape codes
+    let less = std::env::var("LESS").unwrap_or_else(|_| "FRX".to_string());
+    let mut child =
+        Command::new(program).args(command).env("LESS", less).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        for line in lines {
+            // The pager may be quit before reading everything
+            if writeln!(stdin, "{}", line).is_err() {
+                break;
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Prints a screen of lines at a time, until they are exhausted or `q` is entered.
+fn internal(lines: &[String], height: usize) {
+    let page = height.saturating_sub(1).max(1);
+    for (n, chunk) in lines.chunks(page).enumerate() {
+        for line in chunk {
+            println!("{}", line);
+        }
+        if (n + 1) * page >= lines.len() {
+            break;
+        }
+        print!("-- {}/{} lines, enter for more, q to quit --", (n + 1) * page, lines.len());
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer).is_err() || answer.trim() == "q" {
+            break;
+        }
+    }
+}
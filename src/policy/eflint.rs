@@ -0,0 +1,73 @@
+use super::{PolicyEngine, Verdict};
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+};
+
+/// Environment variable with the address of the eFLINT server.
+const ADDR_VAR: &str = "EFLINT_SERVER";
+const DEFAULT_ADDR: &str = "localhost:5000";
+
+/// Treats payloads as eFLINT phrases, which are forbidden if they cause violations.
+/// Expects an `eflint-server` to be listening, loaded with the common specification.
+/// The server is reverted to its prior state after every check.
+pub struct EflintEngine {
+    addr: String,
+}
+
+impl EflintEngine {
+    pub fn from_env() -> Self {
+        Self { addr: std::env::var(ADDR_VAR).unwrap_or_else(|_| DEFAULT_ADDR.to_string()) }
+    }
+}
+
+/// Sends a single command to the server, returning its response.
+fn request(addr: &str, command: Value) -> Result<Value, String> {
+    let mut stream =
+        TcpStream::connect(addr).map_err(|e| format!("cannot reach eFLINT server: {}", e))?;
+    writeln!(stream, "{}", command).map_err(|e| e.to_string())?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).map_err(|e| e.to_string())?;
+    let response: Value = serde_json::from_str(&response).map_err(|e| e.to_string())?;
+    if response.get("response").and_then(Value::as_str) == Some("invalid command") {
+        let message = response.get("message").and_then(Value::as_str).unwrap_or("");
+        return Err(format!("eFLINT server rejected command: {}", message));
+    }
+    Ok(response)
+}
+
+impl PolicyEngine for EflintEngine {
+    fn name(&self) -> &'static str {
+        "eflint"
+    }
+
+    fn check(&self, payloads: &[&str]) -> Result<Verdict, String> {
+        let status = request(&self.addr, json!({ "command": "status" }))?;
+        let initial = status.get("current").and_then(Value::as_u64);
+        let mut violations = vec![];
+        let mut result = Ok(());
+        for payload in payloads {
+            match request(&self.addr, json!({ "command": "phrase", "text": payload })) {
+                Ok(response) => {
+                    if let Some(vs) = response.get("violations").and_then(Value::as_array) {
+                        violations.extend(vs.iter().map(|v| v.to_string()));
+                    }
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if let Some(initial) = initial {
+            request(&self.addr, json!({ "command": "revert", "value": initial }))?;
+        }
+        result?;
+        Ok(if violations.is_empty() {
+            Verdict::Permitted
+        } else {
+            Verdict::Forbidden(format!("violations: {}", violations.join(", ")))
+        })
+    }
+}
@@ -0,0 +1,77 @@
+//! Backends deciding whether a set of payloads, e.g., the justification of an action,
+//! is permissible under the semantics of some policy language.
+
+use crate::datalog;
+use std::collections::BTreeSet;
+
+#[cfg(feature = "eflint")]
+mod eflint;
+mod slick;
+
+#[cfg(feature = "eflint")]
+pub use eflint::EflintEngine;
+pub use slick::SlickEngine;
+
+pub enum Verdict {
+    Permitted,
+    /// Not permissible, with an explanation why.
+    Forbidden(String),
+}
+
+pub trait PolicyEngine: Send {
+    fn name(&self) -> &'static str;
+
+    /// Evaluates the given payloads together. Fails if they cannot be evaluated at all,
+    /// e.g., because they are not written in the engine's language.
+    fn check(&self, payloads: &[&str]) -> Result<Verdict, String>;
+
+    /// The facts derived from the given payloads together, if the engine reports them.
+    fn derive(&self, _payloads: &[&str]) -> Option<Result<Vec<String>, String>> {
+        None
+    }
+}
+
+/// Treats payloads as Datalog, which are forbidden if they derive `error`.
+pub struct DatalogEngine;
+
+impl PolicyEngine for DatalogEngine {
+    fn name(&self) -> &'static str {
+        "datalog"
+    }
+
+    fn check(&self, payloads: &[&str]) -> Result<Verdict, String> {
+        let facts = datalog_closure(payloads)?;
+        Ok(if facts.iter().any(|f| f.pred == "error" && f.args.is_empty()) {
+            Verdict::Forbidden("error is derived".into())
+        } else {
+            Verdict::Permitted
+        })
+    }
+
+    fn derive(&self, payloads: &[&str]) -> Option<Result<Vec<String>, String>> {
+        Some(
+            datalog_closure(payloads)
+                .map(|facts| facts.iter().map(|f| format!("{}.", f)).collect()),
+        )
+    }
+}
+
+fn datalog_closure(payloads: &[&str]) -> Result<BTreeSet<datalog::Atom>, String> {
+    let mut rules = vec![];
+    for payload in payloads {
+        rules.extend(datalog::parse(payload)?);
+    }
+    Ok(datalog::closure(&rules))
+}
+
+/// The policy engine with the given name, if it exists and is enabled in this build.
+pub fn by_name(name: &str) -> Option<Option<Box<dyn PolicyEngine>>> {
+    Some(match name {
+        "none" => None,
+        "datalog" => Some(Box::new(DatalogEngine)),
+        "slick" => Some(Box::new(SlickEngine::from_env())),
+        #[cfg(feature = "eflint")]
+        "eflint" => Some(Box::new(EflintEngine::from_env())),
+        _ => return None,
+    })
+}
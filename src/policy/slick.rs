@@ -0,0 +1,101 @@
+use super::{PolicyEngine, Verdict};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Environment variable with the command running the Slick reasoner.
+const REASONER_VAR: &str = "SLICK_REASONER";
+const DEFAULT_REASONER: &str = "slick";
+
+/// Treats payloads as Slick, which are forbidden if they derive any `error` fact.
+/// The reasoner reads a program on stdin and prints its derived truths, one per line.
+pub struct SlickEngine {
+    command: Vec<String>,
+}
+
+impl SlickEngine {
+    pub fn from_env() -> Self {
+        let command = match std::env::var(REASONER_VAR) {
+            Ok(command) => command.split_whitespace().map(str::to_string).collect(),
+            Err(_) => vec![DEFAULT_REASONER.to_string()],
+        };
+        Self { command }
+    }
+}
+
+impl PolicyEngine for SlickEngine {
+    fn name(&self) -> &'static str {
+        "slick"
+    }
+
+    fn check(&self, payloads: &[&str]) -> Result<Verdict, String> {
+        let truths = self.truths(payloads)?;
+        let errors: Vec<&str> = truths
+            .iter()
+            .map(String::as_str)
+            .filter(|t| *t == "error" || t.starts_with("error "))
+            .collect();
+        Ok(if errors.is_empty() {
+            Verdict::Permitted
+        } else {
+            Verdict::Forbidden(format!("derives {}", errors.join(", ")))
+        })
+    }
+
+    fn derive(&self, payloads: &[&str]) -> Option<Result<Vec<String>, String>> {
+        Some(self.truths(payloads))
+    }
+}
+
+impl SlickEngine {
+    /// Runs the reasoner on the payloads, returning the truths it derives.
+    fn truths(&self, payloads: &[&str]) -> Result<Vec<String>, String> {
+        let [program, args @ ..] = &self.command[..] else {
+            return Err("no reasoner configured".into());
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run reasoner {:?}: {}", program, e))?;
+        let stdin = child.stdin.take();
+        // The program is written while the truths are read, as the reasoner may print truths
+        // before reading all of it, and block on a full pipe as we would on its input
+        let (written, output) = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || {
+                let Some(mut stdin) = stdin else { return Ok(()) };
+                payloads.iter().try_for_each(|payload| writeln!(stdin, "{}", payload))
+            });
+            let output = child.wait_with_output();
+            (writer.join(), output)
+        });
+        let output = output.map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!("reasoner failed with {}", output.status));
+        }
+        match written {
+            Ok(written) => written.map_err(|e| format!("cannot write to reasoner: {}", e))?,
+            Err(_) => return Err("writing to the reasoner panicked".into()),
+        }
+        let truths = String::from_utf8_lossy(&output.stdout);
+        Ok(truths.lines().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `cat` derives its program as is, and blocks on a full pipe like any reasoner
+    #[cfg(unix)]
+    #[test]
+    fn reads_truths_while_writing_large_programs() {
+        let engine = SlickEngine { command: vec!["cat".to_string()] };
+        let payload = "fact ".repeat(100_000);
+        let truths = engine.truths(&[&payload, "error"]).unwrap();
+        assert_eq!(truths.len(), 2);
+        assert!(matches!(engine.check(&["error"]), Ok(Verdict::Forbidden(_))));
+    }
+}
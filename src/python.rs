@@ -0,0 +1,75 @@
+//! Python bindings, built as the `justact_pdx` module with `maturin` and the `python`
+//! feature. States and traces are returned as dictionaries and lists thereof, e.g.,
+//! for loading into pandas with `pandas.DataFrame(engine.statements())`.
+
+use crate::Engine;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+#[pyclass(name = "Engine", unsendable)]
+struct PyEngine {
+    engine: Engine,
+}
+
+/// Converts JSON to the corresponding Python objects.
+fn from_json<'py>(py: Python<'py>, json: &str) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    fn new() -> Self {
+        Self { engine: Engine::new() }
+    }
+
+    /// Applies an update written in the REPL grammar, e.g., `say amy "ok."`.
+    /// Raises a `ValueError` if it cannot be parsed or applied.
+    fn apply(&mut self, line: &str) -> PyResult<()> {
+        self.engine.apply_line(line).map(|_| ()).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Reverts the most recently applied update. Returns whether there was one.
+    fn undo(&mut self) -> bool {
+        self.engine.undo()
+    }
+
+    #[getter]
+    fn current(&self) -> u64 {
+        self.engine.current()
+    }
+
+    fn state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        from_json(py, &serde_json::to_string(&self.engine.state()).expect("state is serializable"))
+    }
+
+    fn statements<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.state(py)?.get_item("statements")
+    }
+
+    fn agreements<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.state(py)?.get_item("agreements")
+    }
+
+    fn actions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.state(py)?.get_item("actions")
+    }
+
+    /// Why each action is invalid, or `None` for valid ones, by index.
+    fn audit(&self) -> Vec<Option<String>> {
+        (0..self.engine.actions().len()).map(|i| self.engine.violation(i)).collect()
+    }
+
+    /// The trace of the state, as a list of control events, as `dump --format upstream`
+    /// writes them.
+    fn dump<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let mut trace = vec![];
+        self.engine.write_trace(&mut trace).expect("writing to memory does not fail");
+        let trace = String::from_utf8(trace).expect("trace is UTF-8");
+        trace.lines().map(|event| from_json(py, event)).collect()
+    }
+}
+
+#[pymodule]
+fn justact_pdx(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEngine>()
+}
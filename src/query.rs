@@ -0,0 +1,347 @@
+//! Selection of the statements, agreements and actions of a [`Config`] to display.
+//!
+//! Besides fixed filters, statements, agreements and actions can be selected by
+//! predicates over their fields, e.g., `actions where actor = amy and basis.at > 3`.
+
+use crate::{json, AgreeIdx, Config, StmtIdx, Time};
+use justact_prototype::wire::{Action, Agreement, Message};
+use regex::Regex;
+use std::{borrow::Cow, collections::HashMap, ops::Range, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Statements,
+    Agreements,
+    Actions,
+}
+
+/// Restricts what is selected. The default selects everything.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Only selects from this section, if any.
+    pub section: Option<Section>,
+    /// Only selects statements said by, agreements on statements said by, and actions
+    /// enacted by this agent.
+    pub by: Option<String>,
+    /// Only selects statements said, agreements applying, and actions enacted at or
+    /// after this time.
+    pub since: Option<Time>,
+    /// Only selects the first or last few of each section, if any.
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Head(usize),
+    Tail(usize),
+}
+
+/// Selects the elements of a section for which all conditions hold.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub section: Section,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub field: String,
+    pub op: Op,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// The field contains the value as a substring.
+    Contains,
+}
+
+/// What `find` searches payloads for.
+pub enum Pattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl Section {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "stmts" | "statements" => Self::Statements,
+            "agreements" => Self::Agreements,
+            "actions" => Self::Actions,
+            _ => return None,
+        })
+    }
+}
+
+impl Query {
+    /// Parses `<section> [where <field> <op> <value> (and <field> <op> <value>)*]`.
+    pub fn parse<'a>(mut tokens: impl Iterator<Item = Cow<'a, str>>) -> Option<Self> {
+        let section = Section::parse(&tokens.next()?)?;
+        let mut conditions = vec![];
+        let mut keyword = "where";
+        while let Some(token) = tokens.next() {
+            if token != keyword {
+                return None;
+            }
+            let field = tokens.next()?.into_owned();
+            let op = Op::parse(&tokens.next()?)?;
+            conditions.push(Condition { field, op, value: tokens.next()?.into_owned() });
+            keyword = "and";
+        }
+        Some(Self { section, conditions })
+    }
+}
+
+impl Op {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "=" | "==" => Self::Eq,
+            "!=" => Self::Ne,
+            "<" => Self::Lt,
+            "<=" => Self::Le,
+            ">" => Self::Gt,
+            ">=" => Self::Ge,
+            "~" => Self::Contains,
+            _ => return None,
+        })
+    }
+
+    /// Compares numerically if both sides are numbers, and lexicographically otherwise.
+    fn holds(self, lhs: &str, rhs: &str) -> bool {
+        let ordering = match (lhs.parse::<u64>(), rhs.parse::<u64>()) {
+            (Ok(l), Ok(r)) => l.cmp(&r),
+            _ => lhs.cmp(rhs),
+        };
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+            Self::Contains => lhs.contains(rhs),
+        }
+    }
+}
+
+impl Limit {
+    /// Keeps the first or last few of the indices.
+    pub fn apply(self, mut indices: Vec<usize>) -> Vec<usize> {
+        match self {
+            Limit::Head(n) => indices.truncate(n),
+            Limit::Tail(n) => drop(indices.drain(..indices.len().saturating_sub(n))),
+        }
+        indices
+    }
+}
+
+impl Filter {
+    pub fn includes(&self, section: Section) -> bool {
+        self.section.is_none_or(|s| s == section)
+    }
+
+    fn matches(&self, agent: &str, at: Time) -> bool {
+        self.by.as_ref().is_none_or(|by| by == agent) && self.since.is_none_or(|t| at >= t)
+    }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self, String> {
+        if regex {
+            Regex::new(pattern).map(Self::Regex).map_err(|e| e.to_string())
+        } else {
+            Ok(Self::Substring(pattern.to_string()))
+        }
+    }
+
+    /// The byte range of the first match in the haystack, if any.
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        match self {
+            Self::Substring(s) => haystack.find(s.as_str()).map(|start| start..start + s.len()),
+            Self::Regex(r) => r.find(haystack).map(|m| m.range()),
+        }
+    }
+}
+
+/// The matched part of a payload with a few characters of context on either side.
+pub fn context(payload: &str, range: Range<usize>) -> String {
+    const CONTEXT_CHARS: usize = 20;
+    let before = &payload[..range.start];
+    let after = &payload[range.end..];
+    let start = before.char_indices().rev().nth(CONTEXT_CHARS - 1).map_or(0, |(idx, _)| idx);
+    let end = after.char_indices().nth(CONTEXT_CHARS).map_or(after.len(), |(idx, _)| idx);
+    format!(
+        "{}{}{}",
+        if start > 0 { "..." } else { "" },
+        &payload[start..range.end + end],
+        if end < after.len() { "..." } else { "" }
+    )
+}
+
+impl Config {
+    /// Selects statements, visible to the viewer if any, along with their indices.
+    pub fn query_statements<'a>(
+        &'a self,
+        filter: &'a Filter,
+        viewer: Option<&'a str>,
+    ) -> impl Iterator<Item = (StmtIdx, &'a Arc<Message>)> + 'a {
+        // Only the statements of the agent need to be considered if selecting by agent
+        let candidates: Box<dyn Iterator<Item = StmtIdx>> = match &filter.by {
+            Some(by) => Box::new(self.said_by(by).iter().copied()),
+            None => Box::new(0..self.statements.len()),
+        };
+        (candidates.map(|i| (i, &self.statements[i])))
+            .filter(move |_| filter.includes(Section::Statements))
+            .filter(move |&(i, _)| viewer.is_none_or(|v| self.is_visible_to(i, v)))
+            .filter(move |&(i, s)| filter.matches(&s.id.0, self.stated_at[i]))
+    }
+
+    pub fn query_agreements<'a>(
+        &'a self,
+        filter: &'a Filter,
+    ) -> impl Iterator<Item = (AgreeIdx, &'a Agreement)> + 'a {
+        (self.agreements.iter().enumerate())
+            .filter(move |_| filter.includes(Section::Agreements))
+            .filter(move |(_, a)| filter.matches(&a.message.id.0, a.at))
+    }
+
+    pub fn query_actions<'a>(
+        &'a self,
+        filter: &'a Filter,
+    ) -> impl Iterator<Item = (usize, &'a Action)> + 'a {
+        (self.enacted.iter().enumerate())
+            .filter(move |_| filter.includes(Section::Actions))
+            .filter(move |&(i, e)| filter.matches(&e.id.0, self.enacted_at[i]))
+    }
+
+    /// Selects the statements whose payloads match, along with the matched byte ranges.
+    pub fn find_statements<'a>(
+        &'a self,
+        pattern: &'a Pattern,
+    ) -> impl Iterator<Item = (StmtIdx, &'a Arc<Message>, Range<usize>)> + 'a {
+        (self.statements.iter().enumerate())
+            .filter_map(|(i, s)| pattern.find(&s.payload).map(|range| (i, s, range)))
+    }
+
+    /// Selects the indices of the elements matching the query, or fails if the query
+    /// refers to a field that the section does not have.
+    pub fn query(&self, query: &Query) -> Result<Vec<usize>, String> {
+        let len = match query.section {
+            Section::Statements => self.statements.len(),
+            Section::Agreements => self.agreements.len(),
+            Section::Actions => self.enacted.len(),
+        };
+        let mut selected = vec![];
+        'elements: for i in 0..len {
+            for c in &query.conditions {
+                let Some(values) = self.field(query.section, i, &c.field) else {
+                    return Err(format!("{:?} have no field {:?}", query.section, c.field));
+                };
+                // Fields with multiple values, e.g., justifications, match if any value does
+                if !values.iter().any(|v| c.op.holds(v, &c.value)) {
+                    continue 'elements;
+                }
+            }
+            selected.push(i);
+        }
+        Ok(selected)
+    }
+
+    /// The values of the given field of the `idx`-th element of the section, if it exists.
+    fn field(&self, section: Section, idx: usize, field: &str) -> Option<Vec<String>> {
+        let label = |labels: &HashMap<String, usize>| {
+            labels.iter().filter(|&(_, &i)| i == idx).map(|(l, _)| l.clone()).collect()
+        };
+        Some(match (section, field) {
+            (_, "idx") => vec![idx.to_string()],
+            (Section::Statements, "sayer") => vec![self.statements[idx].id.0.clone()],
+            (Section::Statements, "payload") => vec![self.statements[idx].payload.clone()],
+            (Section::Statements, field) if field.starts_with("payload.") => self
+                .structured
+                .get(&idx)
+                .map_or(vec![], |value| json::lookup(value, &field["payload.".len()..])),
+            (Section::Statements, "at") => vec![self.stated_at[idx].to_string()],
+            (Section::Statements, "label") => label(&self.stmt_labels),
+            (Section::Statements, "retracted") => vec![self.retracted.contains(&idx).to_string()],
+            (Section::Statements, "to") => self.recipients.get(&idx).cloned().unwrap_or_default(),
+            (Section::Agreements, "stmt") => self
+                .stmt_idx(&self.agreements[idx].message)
+                .map(|i| i.to_string())
+                .into_iter()
+                .collect(),
+            (Section::Agreements, "sayer") => vec![self.agreements[idx].message.id.0.clone()],
+            (Section::Agreements, "at") => vec![self.agreements[idx].at.to_string()],
+            (Section::Agreements, "until") => {
+                self.expires_at.get(&idx).map(|until| until.to_string()).into_iter().collect()
+            }
+            (Section::Agreements, "label") => label(&self.agree_labels),
+            (Section::Actions, "actor") => vec![self.enacted[idx].id.0.clone()],
+            (Section::Actions, "at") => vec![self.enacted_at[idx].to_string()],
+            (Section::Actions, "basis") => vec![self.basis_idx(idx).to_string()],
+            (Section::Actions, "basis.at") => vec![self.enacted[idx].basis.at.to_string()],
+            (Section::Actions, "justification") => (self.enacted[idx].justification.iter())
+                .filter_map(|s| self.stmt_idx(s))
+                .map(|i| i.to_string())
+                .collect(),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, tokens::Tokens};
+
+    fn query(config: &Config, query: &str) -> Result<Vec<usize>, String> {
+        config.query(&Query::parse(Tokens::new(query)).unwrap())
+    }
+
+    #[test]
+    fn parses_conditions_joined_by_and() {
+        let query = Query::parse(Tokens::new("actions where actor = amy and basis.at >= 3"));
+        let query = query.unwrap();
+        assert_eq!(query.section, Section::Actions);
+        let ops: Vec<Op> = query.conditions.iter().map(|c| c.op).collect();
+        assert_eq!(ops, [Op::Eq, Op::Ge]);
+        assert_eq!(query.conditions[1].field, "basis.at");
+        assert!(Query::parse(Tokens::new("stmts where sayer")).is_none());
+        assert!(Query::parse(Tokens::new("stmts and sayer = amy")).is_none());
+        assert!(Query::parse(Tokens::new("stmts where sayer is amy")).is_none());
+        assert!(Query::parse(Tokens::new("agents")).is_none());
+    }
+
+    #[test]
+    fn compares_numbers_numerically_and_text_lexicographically() {
+        assert!(Op::Lt.holds("9", "10"));
+        assert!(!Op::Lt.holds("b", "a"));
+        assert!(Op::Lt.holds("10", "9a"));
+        assert!(Op::Contains.holds("grants access", "access"));
+        assert!(Op::Ne.holds("amy", "bob"));
+    }
+
+    #[test]
+    fn selects_elements_matching_all_conditions() {
+        let config =
+            config(&["say amy a.", "say bob b.", "say bob c.", "agree 1 0", "enact amy 0 1 2"]);
+        assert_eq!(query(&config, "stmts where sayer = bob").unwrap(), [1, 2]);
+        assert_eq!(query(&config, "stmts where sayer = bob and payload ~ c").unwrap(), [2]);
+        assert_eq!(query(&config, "agreements where stmt = 1").unwrap(), [0]);
+        assert_eq!(query(&config, "actions where justification = 2").unwrap(), [0]);
+        assert!(query(&config, "actions where justification = 0").unwrap().is_empty());
+        assert!(query(&config, "agreements where payload = a.").is_err());
+    }
+
+    #[test]
+    fn limits_to_the_first_or_last() {
+        assert_eq!(Limit::Head(2).apply(vec![1, 2, 3]), [1, 2]);
+        assert_eq!(Limit::Tail(2).apply(vec![1, 2, 3]), [2, 3]);
+        assert_eq!(Limit::Tail(5).apply(vec![1, 2, 3]), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Terminal styling of output. Styling is disabled if stdout is not a terminal,
+//! or if the `NO_COLOR` environment variable is set.
+
+use std::{
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+/// ANSI foreground colors that are readable on both light and dark backgrounds.
+const AGENT_COLORS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+/// Whether styling is disabled regardless of stdout, e.g., while the TUI draws the screen.
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    let enabled = *ENABLED
+        .get_or_init(|| std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none());
+    enabled && !PLAIN.load(Ordering::Relaxed)
+}
+
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+fn paint(code: u8, text: &str) -> String {
+    if enabled() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// The ANSI foreground color of the given agent, which is the same for every run.
+pub fn agent_color(name: &str) -> u8 {
+    // FNV-1a, as the standard hashers do not guarantee stable output
+    let hash = name
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    AGENT_COLORS[(hash % AGENT_COLORS.len() as u64) as usize]
+}
+
+/// Colors the text with the color of the given agent.
+pub fn agent(name: &str, text: &str) -> String {
+    paint(agent_color(name), text)
+}
+
+/// Clears the terminal and moves the cursor to its top left.
+pub fn clear() -> &'static str {
+    if enabled() {
+        "\x1b[2J\x1b[H"
+    } else {
+        ""
+    }
+}
+
+pub fn bold(text: &str) -> String {
+    paint(1, text)
+}
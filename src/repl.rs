@@ -0,0 +1,327 @@
+use crate::{help, scenarios, tokens, Config, Flow};
+use rustyline::{
+    completion::{Completer, FilenameCompleter, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::BufRead,
+    path::PathBuf,
+};
+
+/// The prompt, summarizing the logical time and the size of the state.
+fn prompt(config: &Config) -> String {
+    format!(
+        "[t={} | {} stmts | {} agr | {} act] > ",
+        config.current,
+        config.statements.len(),
+        config.agreements.len(),
+        config.enacted.len()
+    )
+}
+
+/// Where the history of interactive sessions is kept, if anywhere, unless given another
+/// file with `--history`.
+pub fn history_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("justact-pdx").join("history"))
+}
+
+/// Completes command keywords, file paths, and the agent names, indices and labels
+/// that are meaningful w.r.t. a snapshot of the session.
+#[derive(Default)]
+struct Completions {
+    agents: BTreeSet<String>,
+    statements: Vec<String>,
+    agreements: Vec<String>,
+    actions: usize,
+    snapshots: Vec<String>,
+    checkpoints: Vec<String>,
+    branches: Vec<String>,
+    macros: Vec<String>,
+    templates: Vec<String>,
+    files: FilenameCompleter,
+}
+
+impl Completions {
+    fn refresh(&mut self, config: &Config) {
+        self.agents = config.statements.iter().map(|s| s.id.0.clone()).collect();
+        self.agents.extend(config.enacted.iter().map(|e| e.id.0.clone()));
+        self.agents.extend(config.agents.keys().cloned());
+        let refs = |n: usize, labels: &HashMap<String, usize>| {
+            (0..n).map(|i| i.to_string()).chain(labels.keys().cloned()).collect()
+        };
+        self.statements = refs(config.statements.len(), &config.stmt_labels);
+        self.agreements = refs(config.agreements.len(), &config.agree_labels);
+        self.actions = config.enacted.len();
+        self.snapshots = config.snapshots.keys().cloned().collect();
+        self.checkpoints = config.checkpoints.keys().cloned().collect();
+        self.branches = config.branches.keys().cloned().collect();
+        self.macros = config.macros.keys().cloned().collect();
+        self.templates = config.templates.keys().map(|name| format!("@{}", name)).collect();
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        // Only the last of multiple `$`-separated commands is being completed
+        let command_start = line[..pos].rfind('$').map_or(0, |i| i + 1);
+        let word_start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word_start = word_start.max(command_start);
+        let word = &line[word_start..pos];
+        let preceding: Vec<&str> = line[command_start..word_start].split_whitespace().collect();
+
+        let options: Vec<String> = match (preceding.first().copied(), preceding.len()) {
+            (None, _) => {
+                help::keywords().map(str::to_string).chain(self.macros.iter().cloned()).collect()
+            }
+            (Some("say" | "sayto" | "sayfile" | "enact" | "retract" | "withdraw" | "trust"), 1)
+            | (Some("trust" | "sync"), 2)
+            | (Some("sync" | "link" | "read" | "autojustify"), 1)
+            | (Some("link"), 2)
+            | (Some("partition"), _)
+            | (Some("inbox"), 1) => self.agents.iter().cloned().collect(),
+            (Some("retract"), 2) | (Some("uses"), 1) => self.statements.clone(),
+            (Some("cat"), 1) => {
+                let sections = ["stmt", "agreement", "action"].map(str::to_string);
+                [&sections[..], &self.statements].concat()
+            }
+            (Some("cat"), 2) if preceding[1] == "stmt" => self.statements.clone(),
+            (Some("cat"), 2) if preceding[1] == "agreement" => self.agreements.clone(),
+            (Some("cat"), 2) if preceding[1] == "action" => {
+                (0..self.actions).map(|i| i.to_string()).collect()
+            }
+            (Some("why" | "explain" | "minimize"), 1) | (Some("withdraw"), 2) => {
+                (0..self.actions).map(|i| i.to_string()).collect()
+            }
+            (Some("sayto"), 2) => self.agents.iter().cloned().collect(),
+            (Some("say"), 2) | (Some("sayto"), 3) => self.templates.clone(),
+            (Some("agree"), 1) | (Some("amend"), 2) => self.statements.clone(),
+            (Some("schedule"), 2) => vec!["enact".to_string()],
+            (Some("amend"), 1) => self.agreements.clone(),
+            (Some("enact"), 2) => self.agreements.clone(),
+            (Some("enact"), _) | (Some("eval"), _) => self.statements.clone(),
+            (Some("set"), 1) => [
+                "inspector",
+                "policy",
+                "schema",
+                "strict",
+                "enforce",
+                "clock",
+                "idscheme",
+                "watch",
+                "audit",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            (Some("set"), 2) if preceding[1] == "clock" => {
+                vec!["real".to_string(), "manual".to_string()]
+            }
+            (Some("set"), 3) if preceding[1] == "clock" && preceding[2] == "real" => {
+                vec!["s".to_string(), "ms".to_string()]
+            }
+            (Some("set"), 2) if preceding[1] == "audit" => {
+                vec!["continuous".to_string(), "off".to_string()]
+            }
+            (Some("set"), 2) if preceding[1] == "idscheme" => {
+                vec!["hash".to_string(), "counter".to_string()]
+            }
+            (Some("set"), 2) if preceding[1] == "schema" && !word.contains('/') => {
+                ["datalog", "json", "none"].map(str::to_string).to_vec()
+            }
+            (Some("set"), 2) if preceding[1] == "schema" => {
+                return self.files.complete(line, pos, ctx);
+            }
+            (Some("set"), 2) if preceding[1] == "policy" => {
+                ["slick", "datalog", "eflint", "none"].map(str::to_string).to_vec()
+            }
+            (Some("set"), 2) if ["strict", "enforce", "watch"].contains(&preceding[1]) => {
+                vec!["on".to_string(), "off".to_string()]
+            }
+            (Some("agent"), 1) => ["add", "remove", "list", "keygen", "key", "run", "stop"]
+                .map(str::to_string)
+                .to_vec(),
+            (Some("agent"), 3) if preceding[1] == "run" => {
+                return self.files.complete(line, pos, ctx);
+            }
+            (Some("agent"), 2)
+                if ["remove", "keygen", "key", "run", "stop"].contains(&preceding[1]) =>
+            {
+                self.agents.iter().cloned().collect()
+            }
+            (Some("show"), n) if ["as", "by"].contains(&preceding[n - 1]) => {
+                self.agents.iter().cloned().collect()
+            }
+            (Some("show"), n) if !["since", "--head", "--tail"].contains(&preceding[n - 1]) => {
+                let mut options =
+                    ["stmts", "agreements", "actions", "by", "since", "as", "--head", "--tail"]
+                        .to_vec();
+                if n == 1 {
+                    options.extend(["trust", "resources"]);
+                }
+                options.into_iter().map(str::to_string).collect()
+            }
+            (Some("query"), 1) => ["stmts", "agreements", "actions"].map(str::to_string).to_vec(),
+            (Some("replay"), 2) if preceding[1] == "--step" => {
+                return self.files.complete(line, pos, ctx);
+            }
+            (Some("diff"), 1 | 2) => [&self.snapshots[..], &self.branches].concat(),
+            (Some("switch"), 1) => self.branches.clone(),
+            (Some("rollback"), 1) => self.checkpoints.clone(),
+            (Some("help"), 1) => help::keywords().map(str::to_string).collect(),
+            (Some("find"), 1) => vec!["--regex".to_string()],
+            (Some("dataset"), 1) => vec!["add".to_string()],
+            (Some("read"), 3) => vec!["via".to_string()],
+            (Some("autojustify"), n) if n > 2 && preceding[n - 1] != "for" => {
+                ["for", "--enact"].map(str::to_string).to_vec()
+            }
+            (Some("link"), n) if n > 2 && !["delay", "drop"].contains(&preceding[n - 1]) => {
+                ["delay", "drop"].map(str::to_string).to_vec()
+            }
+            (Some("history"), 1) => vec!["search".to_string()],
+            (Some("history"), 2) if preceding[1] == "search" => vec!["--regex".to_string()],
+            (Some("now"), 1) => vec!["--force".to_string()],
+            (Some("dump" | "inspect"), n) if preceding[n - 1] == "--since" => vec![],
+            (Some("dump" | "inspect"), n) if preceding[n - 1] == "--only" => {
+                ["statements", "agreements", "actions"].map(str::to_string).to_vec()
+            }
+            (Some("inspect"), _) if word.starts_with('-') => {
+                ["--only", "--since", "--live", "--stop"].map(str::to_string).to_vec()
+            }
+            (Some("inspect"), n) if preceding[n - 1] == "agreement" => self.agreements.clone(),
+            (Some("inspect"), n) if preceding[n - 1] == "action" => {
+                (0..self.actions).map(|i| i.to_string()).collect()
+            }
+            (Some("inspect"), _) => {
+                let sections = ["stmt", "agreement", "action"].map(str::to_string);
+                [&sections[..], &self.statements].concat()
+            }
+            (Some("dump"), n) if preceding[n - 1] == "--format" => {
+                ["jsonl", "json", "yaml", "upstream"].map(str::to_string).to_vec()
+            }
+            (Some("dump"), _) if word.starts_with('-') => {
+                ["--format", "--only", "--since", "--append"].map(str::to_string).to_vec()
+            }
+            (Some("dump"), _) => return self.files.complete(line, pos, ctx),
+            (Some("stats"), 1) => vec!["--memory".to_string()],
+            (Some("scenario"), 1) => vec!["list".to_string(), "load".to_string()],
+            (Some("scenario"), 2) if preceding[1] == "load" => {
+                scenarios::names().map(str::to_string).collect()
+            }
+            (Some("import"), 2) if ["csv", "tsv"].contains(&preceding[1]) => {
+                return self.files.complete(line, pos, ctx);
+            }
+            (Some("import"), 3) if ["csv", "tsv"].contains(&preceding[1]) => {
+                vec!["--columns".to_string()]
+            }
+            (Some("export"), 1) => ["dot", "mermaid", "html", "csv"].map(str::to_string).to_vec(),
+            (Some("export" | "sayfile" | "check" | "compare"), 2)
+            | (
+                Some(
+                    "load" | "source" | "save" | "restore" | "import" | "replay" | "verify"
+                    | "check" | "compare",
+                ),
+                1,
+            ) => {
+                return self.files.complete(line, pos, ctx);
+            }
+            _ => vec![],
+        };
+        let candidates = options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .map(|option| Pair { display: option.clone(), replacement: option })
+            .collect();
+        Ok((word_start, candidates))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// Runs commands entered on a terminal, one line at a time, with line editing and history.
+pub fn interactive(config: &mut Config) {
+    let mut editor: Editor<Completions, DefaultHistory> =
+        Editor::new().expect("cannot create line editor");
+    let mut completions = Completions::default();
+    completions.refresh(config);
+    editor.set_helper(Some(completions));
+    // Line editing recalls the commands of the history file, which the session appends to
+    for entry in config.history() {
+        let _ = editor.add_history_entry(entry.command.as_str());
+    }
+    'outer: loop {
+        let mut line = match editor.readline(&prompt(config)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                println!("Cannot read input: {}", e);
+                break;
+            }
+        };
+        // The lines of a heredoc, up to its closing tag, belong to the same input
+        while tokens::in_heredoc(&line) {
+            match editor.readline("... ") {
+                Ok(more) => {
+                    line.push('\n');
+                    line.push_str(&more);
+                }
+                Err(ReadlineError::Interrupted) => continue 'outer,
+                Err(_) => break,
+            }
+        }
+        let _ = editor.add_history_entry(line.as_str());
+        for (_, command) in tokens::commands(&line) {
+            if let Flow::Quit = config.run(&command) {
+                break 'outer;
+            }
+        }
+        if let Some(completions) = editor.helper_mut() {
+            completions.refresh(config);
+        }
+    }
+}
+
+/// Runs `$`-separated commands read from a non-terminal stdin, e.g., a pipe.
+/// Each command is executed as soon as its terminating `$` has been read.
+pub fn piped(config: &mut Config) {
+    let mut buffer = String::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("read fail");
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if let Some(end) = tokens::last_separator(&buffer) {
+            for (_, command) in tokens::commands(&buffer[..end]) {
+                if let Flow::Quit = config.run(&command) {
+                    return;
+                }
+            }
+            buffer.drain(..=end);
+        }
+    }
+    // The last command needs no terminating `$`
+    for (_, command) in tokens::commands(&buffer) {
+        if let Flow::Quit = config.run(&command) {
+            return;
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Re-execution of a recorded history or trace, optionally one step at a time.
+
+use crate::{history, snapshot, tokens, Config, Flow, UpdateCmd};
+use std::{
+    io::{BufRead, IsTerminal},
+    path::Path,
+};
+
+/// Waits for the operator to continue, if there is one. Returns whether to continue.
+fn pause() -> bool {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return true;
+    }
+    println!("Press Enter for the next step, or q and Enter to stop");
+    let mut line = String::new();
+    matches!(stdin.lock().read_line(&mut line), Ok(n) if n > 0) && line.trim() != "q"
+}
+
+impl Config {
+    /// Re-executes the commands of a history file, as written with `--history`, or applies
+    /// the updates of a trace, as produced by `dump`. When stepping, shows how each step
+    /// changes the state and pauses in between.
+    pub fn replay_file(&mut self, path: &Path, step: bool) -> std::io::Result<Flow> {
+        let text = std::fs::read_to_string(path)?;
+        let start = self.snapshot();
+        let mut flow = Flow::Continue;
+        if text.trim_start().starts_with(['{', '[']) {
+            self.import_trace(&text, |config, update_cmd: UpdateCmd| {
+                let before = config.snapshot();
+                println!("{:?}", update_cmd);
+                config.update(update_cmd);
+                !step || {
+                    snapshot::print_diff(&before, &config.snapshot());
+                    pause()
+                }
+            })?;
+        } else {
+            let commands = text
+                .lines()
+                .map(history::command_of)
+                .filter(|c| !c.trim().is_empty() && !tokens::is_comment(c));
+            for command in commands {
+                let before = self.snapshot();
+                flow = self.run(&command);
+                if let Flow::Quit = flow {
+                    break;
+                }
+                if step {
+                    snapshot::print_diff(&before, &self.snapshot());
+                    if !pause() {
+                        break;
+                    }
+                }
+            }
+        }
+        if !step {
+            snapshot::print_diff(&start, &self.snapshot());
+        }
+        Ok(flow)
+    }
+}
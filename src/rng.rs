@@ -0,0 +1,64 @@
+//! A small pseudo-random number generator for simulations, which need no cryptographic
+//! quality, but do need to be cheap and portable.
+
+/// SplitMix64, as by Steele, Lea and Flood.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+    /// Whether any number has been drawn since seeding.
+    drawn: bool,
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self { seed, state: seed, drawn: false }
+    }
+
+    pub fn from_entropy() -> Self {
+        let mut seed = [0; 8];
+        // Falls back to a fixed seed on platforms without entropy, which is still usable
+        let _ = getrandom::getrandom(&mut seed);
+        Self::seeded(u64::from_le_bytes(seed))
+    }
+
+    /// The seed, if any number has been drawn since seeding, to reproduce the draws with.
+    pub fn used_seed(&self) -> Option<u64> {
+        self.drawn.then_some(self.seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.drawn = true;
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Whether an event of the given probability happens. Certain events draw no number.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        if probability >= 1.0 {
+            return true;
+        }
+        // The 53 most significant bits, as many as an f64 has in its mantissa
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// Whether the event identified by the key happens, with the given probability, drawn
+    /// from the seed and the key alone, such that replaying it draws the same, whatever was
+    /// drawn before. Certain and impossible events draw no number.
+    pub fn chance_of(&mut self, key: u64, probability: f64) -> bool {
+        if probability <= 0.0 || probability >= 1.0 {
+            return probability >= 1.0;
+        }
+        self.drawn = true;
+        Self::seeded(self.seed ^ key).chance(probability)
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
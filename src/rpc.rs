@@ -0,0 +1,415 @@
+//! A JSON-RPC 2.0 interface, one message per line, for tools that drive the engine
+//! programmatically rather than through the REPL grammar. It is served either over
+//! stdin and stdout, or to any number of TCP clients sharing the same state.
+//!
+//! Every update is a method of the same name, taking the fields of the update as
+//! named parameters, e.g., `{"jsonrpc": "2.0", "id": 1, "method": "now", "params":
+//! {"now": 3}}`, which, like `now` in the REPL, commits the updates scheduled by then and
+//! does not go back in time. Additionally, `state` returns the state and `dump` the trace.
+//! TCP clients must first `login` as an agent, proving to hold its key: `{"agent": "amy"}`
+//! returns a challenge, which the client signs with the secret key of the public key that
+//! the agent was given, and returns as `{"agent": "amy", "signature": "<hex>"}`. Clients may
+//! then only make updates as that agent, and none that configure the session as a whole.
+
+use crate::{signing, AgreeIdx, Config, StmtIdx, Time, UpdateCmd};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request was well-formed, but the update it describes is not applicable.
+pub const REJECTED: i64 = -32000;
+const NOT_LOGGED_IN: i64 = -32001;
+
+/// Who sends the requests.
+pub enum Caller {
+    /// May act as any agent.
+    Operator,
+    /// May only act as the agent it logged in as, if it has.
+    Client(Option<String>),
+    /// Was challenged to sign the nonce with the key of the agent it logs in as.
+    Challenged { agent: String, nonce: [u8; 32] },
+}
+
+impl Caller {
+    /// Fails if the caller may not make the update, i.e., if it is a client that would act
+    /// as another agent than it logged in as, or configure the session as a whole.
+    fn authorize(&self, method: &str, update_cmd: &UpdateCmd) -> Result<(), Error> {
+        let Caller::Client(agent) = self else { return Ok(()) };
+        if let (Some(agent), Some(actor)) = (agent, update_cmd.actor())
+            && agent != actor
+        {
+            let msg = format!("Logged in as {}, so cannot act as {}", agent, actor);
+            return Err(error(REJECTED, msg));
+        }
+        if update_cmd.administrative() {
+            return Err(error(REJECTED, format!("Only the operator may {}", method)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Login {
+    agent: String,
+    /// The signature of the challenge, in hex, if one was returned.
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+/// The bytes that a client signs to log in as the agent.
+fn login_bytes(agent: &str, nonce: &[u8; 32]) -> Vec<u8> {
+    [b"justact-pdx login".as_slice(), &[0], agent.as_bytes(), &[0], nonce].concat()
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Absent for notifications, which are not responded to.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+}
+
+#[derive(Serialize)]
+pub struct Error {
+    pub code: i64,
+    pub message: String,
+}
+
+/// The state as reported by the `state` method.
+#[derive(Serialize)]
+pub struct State<'a> {
+    current: Time,
+    statements: Vec<StatementState<'a>>,
+    agreements: Vec<AgreementState<'a>>,
+    actions: Vec<ActionState<'a>>,
+}
+
+#[derive(Serialize)]
+struct StatementState<'a> {
+    idx: StmtIdx,
+    sayer: &'a str,
+    payload: &'a str,
+    label: Option<&'a str>,
+    /// Stated to all agents if `None`.
+    recipients: Option<&'a [String]>,
+    at: Time,
+    /// The file the payload was read from, if any.
+    source: Option<&'a str>,
+    retracted: bool,
+}
+
+#[derive(Serialize)]
+struct AgreementState<'a> {
+    idx: AgreeIdx,
+    stmt: Option<StmtIdx>,
+    label: Option<&'a str>,
+    at: Time,
+    until: Option<Time>,
+    superseded_by: Option<AgreeIdx>,
+}
+
+#[derive(Serialize)]
+struct ActionState<'a> {
+    idx: usize,
+    actor: &'a str,
+    basis: AgreeIdx,
+    justification: Vec<Option<StmtIdx>>,
+    at: Time,
+    withdrawn: Option<Time>,
+    /// Why the action is invalid, if it is.
+    violation: Option<String>,
+}
+
+fn label_of(labels: &HashMap<String, usize>, idx: usize) -> Option<&str> {
+    labels.iter().find(|&(_, &i)| i == idx).map(|(label, _)| label.as_str())
+}
+
+fn error(code: i64, message: impl Into<String>) -> Error {
+    Error { code, message: message.into() }
+}
+
+impl Config {
+    pub fn state(&self) -> State<'_> {
+        let statements = self.statements.iter().enumerate().map(|(i, s)| StatementState {
+            idx: i,
+            sayer: &s.id.0,
+            payload: &s.payload,
+            label: label_of(&self.stmt_labels, i),
+            recipients: self.recipients.get(&i).map(|r| &r[..]),
+            at: self.stated_at[i],
+            source: self.sources.get(&i).map(String::as_str),
+            retracted: self.retracted.contains(&i),
+        });
+        let agreements = self.agreements.iter().enumerate().map(|(i, a)| AgreementState {
+            idx: i,
+            stmt: self.stmt_idx(&a.message),
+            label: label_of(&self.agree_labels, i),
+            at: a.at,
+            until: self.expires_at.get(&i).copied(),
+            superseded_by: self.superseded_by.get(&i).copied(),
+        });
+        let actions = self.enacted.iter().enumerate().map(|(i, e)| ActionState {
+            idx: i,
+            actor: &e.id.0,
+            basis: self.basis_idx(i),
+            justification: e.justification.iter().map(|s| self.stmt_idx(s)).collect(),
+            at: self.enacted_at[i],
+            withdrawn: self.withdrawn.get(&i).copied(),
+            violation: self.violation(i),
+        });
+        State {
+            current: self.current,
+            statements: statements.collect(),
+            agreements: agreements.collect(),
+            actions: actions.collect(),
+        }
+    }
+
+    pub fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+        caller: &mut Caller,
+    ) -> Result<Value, Error> {
+        match (method, &*caller) {
+            ("login", Caller::Client(_) | Caller::Challenged { .. }) => {
+                let login: Login = serde_json::from_value(params)
+                    .map_err(|e| error(INVALID_PARAMS, e.to_string()))?;
+                return self.login(login, caller);
+            }
+            (_, Caller::Client(None) | Caller::Challenged { .. }) => {
+                return Err(error(NOT_LOGGED_IN, "Not logged in"));
+            }
+            _ => {}
+        }
+        match method {
+            "state" => Ok(serde_json::to_value(self.state()).expect("state is serializable")),
+            "dump" => {
+                let mut trace = vec![];
+                self.write_inspection(&mut trace).expect("writing to memory does not fail");
+                let events = String::from_utf8(trace).expect("trace is UTF-8");
+                let events: Result<Vec<Value>, _> =
+                    events.lines().map(serde_json::from_str).collect();
+                Ok(Value::Array(events.expect("trace is JSON")))
+            }
+            _ => {
+                // Updates are externally tagged, i.e., `{"<method>": <params>}`
+                let mut tagged = Map::new();
+                tagged.insert(method.to_string(), params);
+                let update_cmd: UpdateCmd = match serde_json::from_value(Value::Object(tagged)) {
+                    Ok(update_cmd) => update_cmd,
+                    Err(e) if e.to_string().starts_with("unknown variant") => {
+                        return Err(error(METHOD_NOT_FOUND, format!("No method {:?}", method)));
+                    }
+                    Err(e) => return Err(error(INVALID_PARAMS, e.to_string())),
+                };
+                caller.authorize(method, &update_cmd)?;
+                // Time is set as by the REPL, committing scheduled updates and following the
+                // system clock if set to
+                self.sync_clock().map_err(|e| error(REJECTED, e))?;
+                match update_cmd {
+                    UpdateCmd::Now { now, .. } => self.set_time(now, false),
+                    update_cmd => self.try_update(update_cmd),
+                }
+                .map_err(|e| error(REJECTED, e))?;
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    /// Challenges the caller to sign a nonce with the key of the agent, or authenticates it
+    /// as the agent if it signed the nonce it was challenged with.
+    fn login(&self, login: Login, caller: &mut Caller) -> Result<Value, Error> {
+        self.check_agent(&login.agent).map_err(|e| error(REJECTED, e))?;
+        let key = (self.public_key(&login.agent)).ok_or_else(|| {
+            error(REJECTED, format!("{} has no key to log in with, see agent key", login.agent))
+        })?;
+        let Some(signature) = login.signature else {
+            let mut nonce = [0; 32];
+            getrandom::getrandom(&mut nonce)
+                .map_err(|e| error(REJECTED, format!("Cannot generate challenge: {}", e)))?;
+            *caller = Caller::Challenged { agent: login.agent, nonce };
+            return Ok(serde_json::json!({ "challenge": signing::to_hex(&nonce) }));
+        };
+        let Caller::Challenged { agent, nonce } = &*caller else {
+            return Err(error(REJECTED, "Not challenged, log in without a signature first"));
+        };
+        if *agent != login.agent {
+            return Err(error(REJECTED, format!("Challenged to log in as {}", agent)));
+        }
+        let signature = (signing::from_hex(&signature))
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .ok_or_else(|| error(INVALID_PARAMS, "Malformed signature"))?;
+        let verified = VerifyingKey::from_bytes(&key).and_then(|key| {
+            key.verify(&login_bytes(agent, nonce), &Signature::from_bytes(&signature))
+        });
+        // Each challenge is answered once, such that failed attempts need a new one
+        match verified {
+            Ok(()) => {
+                *caller = Caller::Client(Some(login.agent));
+                Ok(Value::Null)
+            }
+            Err(_) => {
+                *caller = Caller::Client(None);
+                Err(error(REJECTED, "The signature does not match the key of the agent"))
+            }
+        }
+    }
+
+    fn respond(&mut self, line: &str, caller: &mut Caller) -> Option<Response> {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => {
+                let code = if e.is_syntax() { PARSE_ERROR } else { INVALID_REQUEST };
+                let error = Some(error(code, e.to_string()));
+                return Some(Response { jsonrpc: "2.0", id: Value::Null, result: None, error });
+            }
+        };
+        let result = if request.jsonrpc == "2.0" {
+            self.call(&request.method, request.params, caller)
+        } else {
+            Err(error(INVALID_REQUEST, "Unsupported JSON-RPC version"))
+        };
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => Response { jsonrpc: "2.0", id, result: Some(result), error: None },
+            Err(error) => Response { jsonrpc: "2.0", id, result: None, error: Some(error) },
+        })
+    }
+}
+
+/// Serves requests, one per line, until the input is exhausted. Requests are handled
+/// one at a time, even if other connections share the state.
+fn serve_lines(
+    config: &Mutex<Config>,
+    caller: &mut Caller,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = config.lock().expect("no request panics").respond(&line, caller);
+        if let Some(response) = response {
+            let response = serde_json::to_string(&response).expect("response is serializable");
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Serves requests from stdin until it is closed. Responses are written to stdout, to which
+/// nothing else is, as what the engine prints while handling requests goes to stderr.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serve(config: Config) {
+    #[cfg(unix)]
+    use std::os::fd::AsFd;
+    #[cfg(windows)]
+    use std::os::windows::io::AsHandle;
+
+    let config = Mutex::new(config);
+    // Responses go to a copy of stdout, such that stdout itself can be redirected
+    let _ = std::io::stdout().flush();
+    #[cfg(unix)]
+    let responses = std::io::stdout().as_fd().try_clone_to_owned();
+    #[cfg(windows)]
+    let responses = std::io::stdout().as_handle().try_clone_to_owned();
+    let redirected = responses.map(std::fs::File::from).and_then(|responses| {
+        let redirect = gag::Redirect::stdout(std::io::stderr())?;
+        Ok((responses, redirect))
+    });
+    let (responses, _redirect) = match redirected {
+        Ok(redirected) => redirected,
+        Err(e) => {
+            eprintln!("Cannot keep diagnostics off stdout: {}", e);
+            return;
+        }
+    };
+    let stdin = std::io::stdin().lock();
+    if let Err(e) = serve_lines(&config, &mut Caller::Operator, stdin, responses) {
+        eprintln!("Cannot serve requests: {}", e);
+    }
+}
+
+/// Serves requests of any number of concurrent clients connecting to the given address.
+pub fn listen(config: Config, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!("Listening on {}", listener.local_addr()?);
+    let config = Arc::new(Mutex::new(config));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Cannot accept connection: {}", e);
+                continue;
+            }
+        };
+        let config = config.clone();
+        std::thread::spawn(move || {
+            let peer = stream.peer_addr().map_or("unknown peer".to_string(), |a| a.to_string());
+            println!("{} connected", peer);
+            let result = stream.try_clone().and_then(|input| {
+                serve_lines(&config, &mut Caller::Client(None), BufReader::new(input), stream)
+            });
+            match result {
+                Ok(()) => println!("{} disconnected", peer),
+                Err(e) => println!("{} disconnected: {}", peer, e),
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Caller, REJECTED};
+    use crate::{Cmd, Config};
+
+    fn authorize(agent: &str, command: &str) -> Result<(), i64> {
+        let Ok(Cmd::Update(update_cmd)) = Config::default().parse(command) else {
+            panic!("{:?} is not an update", command);
+        };
+        let caller = Caller::Client(Some(agent.to_string()));
+        caller.authorize(command, &update_cmd).map_err(|e| e.code)
+    }
+
+    #[test]
+    fn rejects_clients_acting_as_other_agents() {
+        assert_eq!(authorize("amy", "sync bob cho"), Err(REJECTED));
+        assert_eq!(authorize("amy", "dataset add patients.csv owner=bob"), Err(REJECTED));
+        assert_eq!(authorize("amy", "say bob ok."), Err(REJECTED));
+        assert_eq!(authorize("amy", "sync amy cho"), Ok(()));
+        assert_eq!(authorize("amy", "dataset add patients.csv owner=amy"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_clients_configuring_the_session() {
+        assert_eq!(authorize("amy", "heal"), Err(REJECTED));
+        assert_eq!(authorize("amy", "set strict on"), Err(REJECTED));
+    }
+}
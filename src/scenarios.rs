@@ -0,0 +1,54 @@
+//! Example scenarios compiled into the binary, to explore without writing a script first.
+
+use crate::{Config, Flow};
+
+struct Scenario {
+    name: &'static str,
+    description: &'static str,
+    script: &'static str,
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "medical",
+        description: "Hospitals share patient data for research, but not for training models",
+        script: include_str!("../justact_pdx_medical.txt"),
+    },
+    Scenario {
+        name: "marketplace",
+        description: "A data exchange punishing marketplaces, with a sale that is permitted \
+                      only once the marketplace is classified as such",
+        script: include_str!("../justact_pdx_script1.txt"),
+    },
+    Scenario {
+        name: "marketplace-monolithic",
+        description: "The marketplace scenario, with all DMI rules in a single statement",
+        script: include_str!("../justact_pdx_script2.txt"),
+    },
+    Scenario {
+        name: "marketplace-reagreed",
+        description: "The monolithic marketplace scenario, agreeing anew at time 1 to act on",
+        script: include_str!("../justact_pdx_script3.txt"),
+    },
+];
+
+impl Config {
+    pub fn list_scenarios(&self) {
+        for scenario in SCENARIOS {
+            println!("{}: {}", scenario.name, scenario.description);
+        }
+    }
+
+    /// Runs the commands of the named scenario, as if loading its script.
+    pub fn load_scenario(&mut self, name: &str) -> Result<Flow, String> {
+        let scenario = SCENARIOS
+            .iter()
+            .find(|scenario| scenario.name == name)
+            .ok_or_else(|| format!("Unknown scenario {:?}, see scenario list", name))?;
+        self.run_script(&format!("scenario {}", name), scenario.script)
+    }
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    SCENARIOS.iter().map(|scenario| scenario.name)
+}
@@ -0,0 +1,108 @@
+//! Enactments scheduled for a later time, committed once time advances that far.
+//!
+//! Scheduling is an update, and committing is part of advancing the time, such that
+//! replaying the journal, e.g., to undo, rebuilds the queue along with the state.
+
+use crate::{Config, Time, UpdateCmd};
+
+impl Config {
+    /// Queues the action, to be enacted once time reaches the given time.
+    pub(crate) fn schedule(&mut self, at: Time, update_cmd: &UpdateCmd) -> Result<(), String> {
+        if !matches!(update_cmd, UpdateCmd::Enact { .. }) {
+            return Err("Only actions can be scheduled".to_string());
+        } else if at <= self.current {
+            return Err(format!(
+                "Cannot schedule at time {}, which is not after the current time {}",
+                at, self.current
+            ));
+        }
+        // After those at the same time, so updates scheduled at the same time keep their order
+        let idx = self.scheduled.partition_point(|&(other, _)| other <= at);
+        self.scheduled.insert(idx, (at, update_cmd.clone()));
+        Ok(())
+    }
+
+    /// Sets the time, first committing the scheduled updates that are due by then at their
+    /// scheduled times, in order, one at a time. Updates that fail are dropped.
+    pub(crate) fn commit_scheduled(&mut self, now: Time) {
+        while self.scheduled.first().is_some_and(|&(at, _)| at <= now) {
+            let (at, update_cmd) = self.scheduled.remove(0);
+            self.current = at;
+            if let Err(e) = self.validate(&update_cmd).and_then(|()| self.apply(&update_cmd)) {
+                println!("Dropping scheduled update: {}", e);
+            }
+        }
+        self.current = now;
+    }
+
+    /// Advances time to the given time, committing the scheduled updates due by then.
+    pub fn advance(&mut self, now: Time) -> Result<(), String> {
+        self.announce_due(now);
+        self.update_and_bind(UpdateCmd::Now { now, tick: false }, None)
+    }
+
+    /// Prints the scheduled updates due by the given time, before they are committed.
+    pub(crate) fn announce_due(&self, now: Time) {
+        for (_, update_cmd) in self.scheduled.iter().take_while(|&&(at, _)| at <= now) {
+            println!("Committing scheduled {:?}", update_cmd);
+        }
+    }
+
+    pub fn list_scheduled(&self) {
+        if self.scheduled.is_empty() {
+            println!("Nothing scheduled");
+        }
+        for (at, update_cmd) in &self.scheduled {
+            println!("{: >8} | {:?}", at, update_cmd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+
+    #[test]
+    fn commits_due_actions_at_their_times_in_order() {
+        let config = config(&[
+            "say amy a.",
+            "agree 0 0 until 10",
+            "schedule 3 enact bob 0 0",
+            "schedule 2 enact cho 0 0",
+            "schedule 7 enact dan 0 0",
+            "now 5",
+        ]);
+        let actors: Vec<&str> = config.enacted.iter().map(|e| e.id.0.as_str()).collect();
+        assert_eq!(actors, ["cho", "bob"]);
+        assert_eq!(config.enacted_at, [2, 3]);
+        assert_eq!(config.current, 5);
+        assert_eq!(config.scheduled.len(), 1);
+    }
+
+    #[test]
+    fn drops_failing_actions_but_commits_the_others() {
+        let config = config(&[
+            "say amy a.",
+            "agree 0 0 until 10",
+            "schedule 2 enact bob 5 0",
+            "schedule 3 enact cho 0 0",
+            "now 5",
+        ]);
+        let actors: Vec<&str> = config.enacted.iter().map(|e| e.id.0.as_str()).collect();
+        assert_eq!(actors, ["cho"]);
+        assert!(config.scheduled.is_empty());
+    }
+
+    #[test]
+    fn undoing_restores_the_queue() {
+        let mut config = config(&["say amy a.", "agree 0 0 until 10", "schedule 3 enact bob 0 0"]);
+        let cmd = config.parse("now 5").unwrap();
+        config.execute(cmd).unwrap();
+        assert!(config.scheduled.is_empty());
+        config.undo();
+        assert_eq!(config.scheduled.len(), 1);
+        assert!(config.enacted.is_empty());
+        config.undo();
+        assert!(config.scheduled.is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+//! Validating payloads as they are said, against a built-in grammar or a JSON schema, such
+//! that they can be read by policy engines and other programs.
+//!
+//! JSON schemas are supported in part, by the keywords `type`, `enum`, `const`,
+//! `properties`, `required`, `additionalProperties`, `items`, `minItems`, `maxItems`,
+//! `minimum`, `maximum`, `minLength`, `maxLength`, `pattern`, `allOf` and `anyOf`. Other
+//! keywords are ignored.
+
+use crate::datalog;
+use regex::Regex;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Payloads are Datalog programs, as evaluated by the `datalog` policy engine.
+    Datalog,
+    /// Payloads are JSON values.
+    Json,
+    /// Payloads are JSON values valid according to the schema read from the path.
+    File { path: String, schema: Value },
+}
+
+impl Schema {
+    /// Parses the built-in grammar of the given name, or reads the JSON schema at the path.
+    pub fn load(name: &str) -> Result<Self, String> {
+        match name {
+            "datalog" => Ok(Self::Datalog),
+            "json" => Ok(Self::Json),
+            path => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read schema {:?}: {}", path, e))?;
+                let schema = serde_json::from_str(&text)
+                    .map_err(|e| format!("Cannot parse schema {:?}: {}", path, e))?;
+                Ok(Self::File { path: path.to_string(), schema })
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Datalog => "datalog",
+            Self::Json => "json",
+            Self::File { path, .. } => path,
+        }
+    }
+
+    /// Explains why the payload does not match the schema, if it does not.
+    pub fn validate(&self, payload: &str) -> Result<(), String> {
+        match self {
+            Self::Datalog => datalog::parse(payload).map(|_| ()),
+            Self::Json => {
+                serde_json::from_str::<Value>(payload).map(|_| ()).map_err(|e| e.to_string())
+            }
+            Self::File { schema, .. } => {
+                let value = serde_json::from_str(payload).map_err(|e| e.to_string())?;
+                check(schema, &value, "payload")
+            }
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match (name, value) {
+        ("integer", Value::Number(n)) => n.as_f64().is_some_and(|n| n.fract() == 0.0),
+        (name, value) => name == type_name(value),
+    }
+}
+
+/// Checks the value against the schema, where `at` describes where the value is.
+fn check(schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    // Boolean schemas accept everything or nothing
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{} is not allowed", at)),
+        Value::Object(schema) => schema,
+        _ => return Err("malformed schema".into()),
+    };
+    for (keyword, constraint) in schema {
+        let holds = match (keyword.as_str(), constraint) {
+            ("type", Value::String(name)) => has_type(value, name),
+            ("type", Value::Array(names)) => {
+                names.iter().any(|name| name.as_str().is_some_and(|name| has_type(value, name)))
+            }
+            ("enum", Value::Array(options)) => options.contains(value),
+            ("const", option) => option == value,
+            ("properties", Value::Object(properties)) => {
+                if let Value::Object(object) = value {
+                    for (name, property) in properties {
+                        if let Some(field) = object.get(name) {
+                            check(property, field, &format!("{}.{}", at, name))?;
+                        }
+                    }
+                }
+                true
+            }
+            ("required", Value::Array(names)) => match value {
+                Value::Object(object) => {
+                    let missing =
+                        names.iter().filter_map(Value::as_str).find(|n| !object.contains_key(*n));
+                    if let Some(name) = missing {
+                        return Err(format!("{} lacks the required field {:?}", at, name));
+                    }
+                    true
+                }
+                _ => true,
+            },
+            ("additionalProperties", additional) => {
+                if let Value::Object(object) = value {
+                    let known = schema.get("properties").and_then(Value::as_object);
+                    let extra = object
+                        .iter()
+                        .filter(|(name, _)| known.is_none_or(|k| !k.contains_key(*name)));
+                    for (name, field) in extra {
+                        check(additional, field, &format!("{}.{}", at, name))?;
+                    }
+                }
+                true
+            }
+            ("items", items) => {
+                if let Value::Array(elements) = value {
+                    for (i, element) in elements.iter().enumerate() {
+                        check(items, element, &format!("{}[{}]", at, i))?;
+                    }
+                }
+                true
+            }
+            ("minItems", Value::Number(n)) => match value {
+                Value::Array(elements) => n.as_f64().is_none_or(|n| elements.len() as f64 >= n),
+                _ => true,
+            },
+            ("maxItems", Value::Number(n)) => match value {
+                Value::Array(elements) => n.as_f64().is_none_or(|n| elements.len() as f64 <= n),
+                _ => true,
+            },
+            ("minimum", Value::Number(min)) => match value {
+                Value::Number(n) => n.as_f64().zip(min.as_f64()).is_none_or(|(n, min)| n >= min),
+                _ => true,
+            },
+            ("maximum", Value::Number(max)) => match value {
+                Value::Number(n) => n.as_f64().zip(max.as_f64()).is_none_or(|(n, max)| n <= max),
+                _ => true,
+            },
+            ("minLength", Value::Number(n)) => match value {
+                Value::String(s) => n.as_f64().is_none_or(|n| s.chars().count() as f64 >= n),
+                _ => true,
+            },
+            ("maxLength", Value::Number(n)) => match value {
+                Value::String(s) => n.as_f64().is_none_or(|n| s.chars().count() as f64 <= n),
+                _ => true,
+            },
+            ("pattern", Value::String(pattern)) => match value {
+                Value::String(s) => {
+                    let regex =
+                        Regex::new(pattern).map_err(|e| format!("malformed pattern: {}", e))?;
+                    regex.is_match(s)
+                }
+                _ => true,
+            },
+            ("allOf", Value::Array(schemas)) => {
+                for schema in schemas {
+                    check(schema, value, at)?;
+                }
+                true
+            }
+            ("anyOf", Value::Array(schemas)) => schemas.iter().any(|s| check(s, value, at).is_ok()),
+            _ => true,
+        };
+        if !holds {
+            return Err(format!("{} violates {:?}: {}", at, keyword, constraint));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(fields: &[(&str, Value)]) -> Value {
+        Value::Object(fields.iter().map(|(k, v)| (k.to_string(), v.clone())).collect())
+    }
+
+    fn strings(strings: &[&str]) -> Value {
+        Value::Array(strings.iter().map(|&s| Value::from(s)).collect())
+    }
+
+    /// `{"type": "object", "required": ["role"], "properties": {"role": {"enum":
+    /// ["admin", "user"]}, "level": {"type": "integer", "minimum": 1}},
+    /// "additionalProperties": false}`
+    fn grant_schema() -> Value {
+        let level = object(&[("type", "integer".into()), ("minimum", 1u64.into())]);
+        let properties =
+            object(&[("role", object(&[("enum", strings(&["admin", "user"]))])), ("level", level)]);
+        object(&[
+            ("type", "object".into()),
+            ("required", strings(&["role"])),
+            ("properties", properties),
+            ("additionalProperties", Value::Bool(false)),
+        ])
+    }
+
+    #[test]
+    fn accepts_values_matching_the_schema() {
+        let grant = object(&[("role", "admin".into()), ("level", 2u64.into())]);
+        assert_eq!(check(&grant_schema(), &grant, "payload"), Ok(()));
+        assert_eq!(check(&Value::Bool(true), &Value::Null, "payload"), Ok(()));
+    }
+
+    #[test]
+    fn explains_where_values_violate_the_schema() {
+        let missing = object(&[("level", 2u64.into())]);
+        let e = check(&grant_schema(), &missing, "payload").unwrap_err();
+        assert!(e.contains("lacks the required field \"role\""), "{}", e);
+        let low = object(&[("role", "user".into()), ("level", 0u64.into())]);
+        let e = check(&grant_schema(), &low, "payload").unwrap_err();
+        assert!(e.starts_with("payload.level violates \"minimum\""), "{}", e);
+        let extra = object(&[("role", "user".into()), ("note", "hi".into())]);
+        let e = check(&grant_schema(), &extra, "payload").unwrap_err();
+        assert!(e.starts_with("payload.note is not allowed"), "{}", e);
+        assert!(check(&grant_schema(), &strings(&["admin"]), "payload").is_err());
+    }
+
+    #[test]
+    fn checks_items_and_alternatives() {
+        let schema = object(&[
+            (
+                "items",
+                object(&[(
+                    "anyOf",
+                    Value::Array(vec![
+                        object(&[("type", "string".into()), ("maxLength", 3u64.into())]),
+                        object(&[("type", "integer".into())]),
+                    ]),
+                )]),
+            ),
+            ("minItems", 1u64.into()),
+        ]);
+        assert_eq!(check(&schema, &Value::Array(vec!["abc".into(), 7u64.into()]), "p"), Ok(()));
+        assert!(check(&schema, &Value::Array(vec!["abcd".into()]), "p").is_err());
+        assert!(check(&schema, &Value::Array(vec![]), "p").is_err());
+    }
+
+    #[test]
+    fn validates_datalog_payloads() {
+        assert_eq!(Schema::Datalog.validate("grant(amy, db). ok :- grant(amy, db)."), Ok(()));
+        assert!(Schema::Datalog.validate("grant(amy").is_err());
+    }
+}
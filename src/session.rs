@@ -0,0 +1,341 @@
+use crate::{dump, json, Config, Ref, UpdateCmd};
+use justact_prototype::{
+    auditing::{Event, EventControl},
+    spec::collections::Recipient,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Write},
+    path::Path,
+};
+
+/// First line of every session file, followed by the format version.
+const MAGIC: &str = "justact-pdx session";
+const VERSION: u32 = 2;
+
+/// The persisted form of a session, which is also kept in memory for checkpoints.
+/// The state itself is not stored, but reconstructed by replaying the journal upon
+/// restoration.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    journal: Vec<UpdateCmd>,
+    undone: Vec<UpdateCmd>,
+}
+
+impl Config {
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut w = BufWriter::new(std::fs::File::create(path)?);
+        writeln!(w, "{} {}", MAGIC, VERSION)?;
+        let session = Session { journal: self.journal.clone(), undone: self.undone.clone() };
+        serde_json::to_writer(&mut w, &session)?;
+        writeln!(w)?;
+        w.flush()
+    }
+
+    /// Replaces the current session with the one stored at the given path.
+    /// The current session is left untouched if the file cannot be read.
+    pub fn restore(&mut self, path: &Path) -> std::io::Result<()> {
+        let mut r = BufReader::new(std::fs::File::open(path)?);
+        let mut header = String::new();
+        r.read_line(&mut header)?;
+        let version = header
+            .trim_end()
+            .strip_prefix(MAGIC)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "not a session file"))?;
+        if version != VERSION {
+            let msg = format!("unsupported session format version {}", version);
+            return Err(Error::new(ErrorKind::InvalidData, msg));
+        }
+        let session: Session = serde_json::from_reader(r)?;
+        self.journal = session.journal;
+        self.undone = session.undone;
+        self.replay();
+        Ok(())
+    }
+
+    /// Remembers the current session under the given name, replacing any checkpoint
+    /// of the same name.
+    pub fn checkpoint(&mut self, name: String) {
+        let session = Session { journal: self.journal.clone(), undone: self.undone.clone() };
+        self.checkpoints.insert(name, session);
+    }
+
+    /// Replaces the current session with the checkpoint of the given name.
+    pub fn rollback(&mut self, name: &str) -> Result<(), String> {
+        let session = self.checkpoints.get(name).ok_or(format!("No checkpoint {:?}", name))?;
+        self.journal = session.journal.clone();
+        self.undone = session.undone.clone();
+        self.replay();
+        Ok(())
+    }
+
+    pub fn list_checkpoints(&self) {
+        for (name, session) in &self.checkpoints {
+            println!("{: <16} | {} updates", name, session.journal.len());
+        }
+    }
+
+    /// Creates a branch that starts out as the current one, and switches to it.
+    pub fn branch(&mut self, name: String) -> Result<(), String> {
+        if name == self.branch || self.branches.contains_key(&name) {
+            return Err(format!("Branch {:?} already exists", name));
+        }
+        let session = Session { journal: self.journal.clone(), undone: vec![] };
+        let old = std::mem::replace(&mut self.branch, name);
+        self.branches.insert(old, session);
+        Ok(())
+    }
+
+    /// Sets the current branch aside and continues with the given one.
+    pub fn switch(&mut self, name: String) -> Result<(), String> {
+        let session = self.branches.remove(&name).ok_or(format!("No branch {:?}", name))?;
+        let current = Session {
+            journal: std::mem::replace(&mut self.journal, session.journal),
+            undone: std::mem::replace(&mut self.undone, session.undone),
+        };
+        let old = std::mem::replace(&mut self.branch, name);
+        self.branches.insert(old, current);
+        self.replay();
+        Ok(())
+    }
+
+    /// Lists the branches, along with the number of updates they share with the current one.
+    pub fn list_branches(&self) {
+        println!("* {: <16} | {} updates", self.branch, self.journal.len());
+        for (name, session) in &self.branches {
+            let shared =
+                self.journal.iter().zip(&session.journal).take_while(|(a, b)| a == b).count();
+            println!(
+                "  {: <16} | {} updates, of which {} shared",
+                name,
+                session.journal.len(),
+                shared
+            );
+        }
+    }
+
+    /// The state of the branch with the given name, if it exists.
+    pub fn branch_state(&self, name: &str) -> Option<Config> {
+        let journal = match self.branches.get(name) {
+            Some(session) => session.journal.clone(),
+            None if name == self.branch => self.journal.clone(),
+            None => return None,
+        };
+        let mut config = Config { journal, ..Config::default() };
+        config.replay();
+        Some(config)
+    }
+
+    /// Applies the control events of a trace, as produced by `dump` or the runners of the
+    /// JustAct prototype, as updates. Other events are ignored, and nothing is applied if
+    /// any entry of the trace cannot be parsed.
+    pub fn import(&mut self, path: &Path) -> std::io::Result<()> {
+        let text = dump::read_trace(path)?;
+        self.import_trace(&text, |config, update_cmd| {
+            config.update(update_cmd);
+            true
+        })
+    }
+
+    /// Translates the control events of a trace into updates, one at a time, and passes
+    /// each to `step` to apply it, along with what `dump` records next to them, e.g.,
+    /// labels and withdrawals. Stops early if `step` returns `false`.
+    pub fn import_trace(
+        &mut self,
+        text: &str,
+        mut step: impl FnMut(&mut Self, UpdateCmd) -> bool,
+    ) -> std::io::Result<()> {
+        let records =
+            dump::read_records(text).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let (mut events, mut retracted, mut ignored) = (vec![], vec![], 0);
+        for (i, mut record) in records {
+            // Seeds before the events, as in dumps, are ignored, and so are signatures
+            if let Some(r) = dump::retracted(&record) {
+                retracted.push(r);
+                continue;
+            } else if dump::is_preamble(&record) {
+                continue;
+            }
+            let annotations = dump::strip_annotations(&mut record);
+            json::destructure(&mut record);
+            let control = record.get("Control").is_some();
+            match serde_json::from_value::<Event>(record) {
+                Ok(Event::Control(c)) => events.push((c, annotations)),
+                // Other kinds of events, e.g., the data events of the prototype's runners,
+                // do not change the state
+                Ok(_) => ignored += 1,
+                Err(_) if !control => ignored += 1,
+                Err(e) => {
+                    let msg = format!("entry {}: {}", i, e);
+                    return Err(Error::new(ErrorKind::InvalidData, msg));
+                }
+            }
+        }
+        if ignored > 0 {
+            println!("Ignoring {} events other than control events", ignored);
+        }
+
+        // Messages and agreements are referred to by their IDs in the trace,
+        // but indices into the state here, which may not coincide.
+        let mut statements = HashMap::new();
+        let mut agreements = HashMap::new();
+        // Messages to multiple recipients are stated once per recipient
+        let mut recipients: HashMap<_, Option<Vec<String>>> = HashMap::new();
+        for (c, _) in &events {
+            // Statements stated by others than their sayers are forwarded, see below
+            if let EventControl::StateMessage { who, to, msg } = c
+                && *who == msg.id.0
+            {
+                let entry = recipients.entry(msg.id.clone()).or_insert_with(|| Some(vec![]));
+                match (to, entry) {
+                    (Recipient::One(r), Some(rs)) => rs.push(r.to_string()),
+                    (Recipient::All, entry) => *entry = None,
+                    (_, None) => {}
+                }
+            }
+        }
+        // Retracted statements are said before the events, as the actions they justify may
+        // refer to them, and retracted after them
+        let mut retractions = vec![];
+        for r in retracted {
+            let (sayer, payload) = (r.id.0.clone(), r.payload.as_str().into());
+            let said = UpdateCmd::Say {
+                sayer,
+                payload,
+                label: r.label,
+                recipients: r.recipients,
+                source: None,
+            };
+            match self.imported(&mut step, said) {
+                None => return Ok(()),
+                Some(false) => {}
+                Some(true) => {
+                    let idx = self.statements.len() - 1;
+                    statements.insert(r.id.clone(), idx);
+                    retractions.push(UpdateCmd::Retract { retractor: r.id.0, stmt: Ref::Idx(idx) });
+                }
+            }
+        }
+        let mut synced = HashSet::new();
+        for (c, annotations) in events {
+            let update_cmd = match c {
+                // Traces of several dumps appended to each other may go back in time
+                EventControl::AdvanceTime { timestamp } if timestamp < self.current => {
+                    println!(
+                        "Skipping time {} before the current time {}",
+                        timestamp, self.current
+                    );
+                    continue;
+                }
+                EventControl::AdvanceTime { timestamp } => {
+                    UpdateCmd::Now { now: timestamp, tick: false }
+                }
+                // Forwarded statements are imported as syncs of their forwarders with their
+                // recipients, which forward all statements that the forwarders know of
+                EventControl::StateMessage { who, to: Recipient::One(to), msg }
+                    if *who != msg.id.0 && statements.contains_key(&msg.id) =>
+                {
+                    let (from, to) = (who.to_string(), to.to_string());
+                    if !synced.insert((from.clone(), to.clone())) {
+                        continue;
+                    }
+                    UpdateCmd::Sync { from, to }
+                }
+                EventControl::StateMessage { msg, .. } => {
+                    if statements.contains_key(&msg.id) {
+                        continue;
+                    }
+                    let (sayer, payload) = (msg.id.0.clone(), msg.payload.as_str().into());
+                    let recipients = recipients.remove(&msg.id).flatten();
+                    let label = annotations.label;
+                    let said = UpdateCmd::Say { sayer, payload, label, recipients, source: None };
+                    match self.imported(&mut step, said) {
+                        None => break,
+                        Some(false) => {}
+                        Some(true) => {
+                            statements.insert(msg.id.clone(), self.statements.len() - 1);
+                        }
+                    }
+                    continue;
+                }
+                EventControl::AddAgreement { agree } => {
+                    let Some(&on_idx) = statements.get(&agree.message.id) else {
+                        println!("Skipping agreement on unknown message {:?}", agree.message.id);
+                        continue;
+                    };
+                    let (on_idx, at) = (Ref::Idx(on_idx), agree.at);
+                    let (until, label) = (annotations.until, annotations.label);
+                    let agreed = match annotations.supersedes.and_then(|key| agreements.get(&key)) {
+                        Some(&old) => {
+                            let supersedes = Ref::Idx(old);
+                            UpdateCmd::Amend { supersedes, on_idx, at, until, label }
+                        }
+                        None => UpdateCmd::Agree { on_idx, at, until, label },
+                    };
+                    match self.imported(&mut step, agreed) {
+                        None => break,
+                        Some(false) => {}
+                        Some(true) => {
+                            let key = (agree.message.id.clone(), agree.at);
+                            agreements.insert(key, self.agreements.len() - 1);
+                        }
+                    }
+                    continue;
+                }
+                EventControl::EnactAction { action, .. } => {
+                    let Some(&basis) =
+                        agreements.get(&(action.basis.message.id.clone(), action.basis.at))
+                    else {
+                        println!("Skipping action {:?} with unknown basis", action.id);
+                        continue;
+                    };
+                    let Some(justification) = action
+                        .justification
+                        .iter()
+                        .map(|m| statements.get(&m.id).map(|&idx| Ref::Idx(idx)))
+                        .collect()
+                    else {
+                        println!("Skipping action {:?} justified by unknown messages", action.id);
+                        continue;
+                    };
+                    let (actor, basis) = (action.id.0.clone(), Ref::Idx(basis));
+                    // Traces do not record the effects of actions
+                    let enacted = UpdateCmd::Enact { actor, basis, justification, effect: None };
+                    match self.imported(&mut step, enacted) {
+                        Some(true) if annotations.withdrawn.is_some() => {
+                            let (actor, action) = (action.id.0.clone(), self.enacted.len() - 1);
+                            UpdateCmd::Withdraw { actor, action }
+                        }
+                        Some(_) => continue,
+                        None => break,
+                    }
+                }
+            };
+            if !step(self, update_cmd) {
+                break;
+            }
+        }
+        for retraction in retractions {
+            if !step(self, retraction) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes the update to `step`, and tells whether it added a statement, agreement or
+    /// action, such that its index is known, or `None` if `step` stops the import.
+    fn imported(
+        &mut self,
+        step: &mut impl FnMut(&mut Self, UpdateCmd) -> bool,
+        update_cmd: UpdateCmd,
+    ) -> Option<bool> {
+        let before = (self.statements.len(), self.agreements.len(), self.enacted.len());
+        let after = |config: &Self| {
+            (config.statements.len(), config.agreements.len(), config.enacted.len())
+        };
+        step(self, update_cmd).then(|| after(self) != before)
+    }
+}
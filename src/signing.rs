@@ -0,0 +1,78 @@
+//! Signing statements with the Ed25519 keys of their sayers, such that traces show who
+//! is accountable for what.
+
+use crate::{Config, StmtIdx, UpdateCmd};
+use ed25519_dalek::{Signer, SigningKey};
+use justact_prototype::wire::Message;
+use serde::{Deserialize, Serialize};
+
+/// How a signature appears in a trace, next to the event stating the signed statement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureRecord {
+    /// The public key of the signer, in hex.
+    pub key: String,
+    /// The signature of the statement, in hex.
+    pub sig: String,
+}
+
+/// The bytes that are signed for a statement, binding its sayer, identifier and payload.
+pub fn signed_bytes(msg: &Message) -> Vec<u8> {
+    [msg.id.0.as_bytes(), &[0], &msg.id.1.to_be_bytes(), &[0], msg.payload.as_bytes()].concat()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Parses a public key, as stored in the journal.
+pub fn parse_public_key(public: &str) -> Option<[u8; 32]> {
+    from_hex(public).and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+}
+
+impl Config {
+    /// Generates a new key for the agent, which signs its later statements.
+    pub fn keygen(&mut self, agent: String) -> Result<(), String> {
+        let mut secret = [0; 32];
+        getrandom::getrandom(&mut secret).map_err(|e| format!("Cannot generate key: {}", e))?;
+        let key = SigningKey::from_bytes(&secret);
+        let public = to_hex(key.verifying_key().as_bytes());
+        self.secrets.insert(public.clone(), key);
+        self.try_update(UpdateCmd::AddKey { agent: agent.clone(), public: public.clone() })?;
+        println!("{} now signs with public key {}", agent, public);
+        Ok(())
+    }
+
+    /// The public key that the agent was given last, if any.
+    pub(crate) fn public_key(&self, agent: &str) -> Option<[u8; 32]> {
+        self.journal.iter().rev().find_map(|update_cmd| match update_cmd {
+            UpdateCmd::AddKey { agent: given, public } if given == agent => {
+                parse_public_key(public)
+            }
+            _ => None,
+        })
+    }
+
+    /// Signs the statement with the key of its sayer, if it has one.
+    pub(crate) fn sign(&mut self, idx: StmtIdx) {
+        let msg = &self.statements[idx];
+        if let Some(key) = self.keys.get(&msg.id.0) {
+            let signature = key.sign(&signed_bytes(msg));
+            self.signatures.insert(idx, (key.verifying_key(), signature));
+        }
+    }
+
+    /// Whether any agent signs its statements, such that unsigned ones stand out.
+    pub fn signing(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// The signature of the statement, if any.
+    pub fn signature_record(&self, idx: StmtIdx) -> Option<SignatureRecord> {
+        let (key, signature) = self.signatures.get(&idx)?;
+        Some(SignatureRecord { key: to_hex(key.as_bytes()), sig: to_hex(&signature.to_bytes()) })
+    }
+}
@@ -0,0 +1,94 @@
+//! Comparison of states, e.g., before and after replaying a command, or of named
+//! snapshots and saved sessions.
+
+use crate::{Config, Time};
+use std::{collections::BTreeSet, io::ErrorKind, path::Path};
+
+/// A description of the state, in which every statement, agreement and action is
+/// described by a single line.
+#[derive(Clone)]
+pub struct Snapshot {
+    current: Time,
+    elements: BTreeSet<String>,
+}
+
+impl Config {
+    /// The named snapshot, the current state of the named branch or, if there is neither
+    /// by that name, the state stored in the session file or trace at that path.
+    pub fn snapshot_by_name(&self, name: &str) -> std::io::Result<Snapshot> {
+        if let Some(snapshot) = self.snapshots.get(name) {
+            return Ok(snapshot.clone());
+        }
+        if let Some(config) = self.branch_state(name) {
+            return Ok(config.snapshot());
+        }
+        let mut config = Config::default();
+        match config.restore(Path::new(name)) {
+            Err(e) if e.kind() == ErrorKind::InvalidData => config.import(Path::new(name))?,
+            result => result?,
+        }
+        Ok(config.snapshot())
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let statements = self.statements.iter().enumerate().map(|(i, s)| {
+            let retracted = if self.retracted.contains(&i) { " (retracted)" } else { "" };
+            format!("statement {} by {}: {:?}{}", i, s.id.0, s.payload, retracted)
+        });
+        let agreements = self.agreements.iter().enumerate().map(|(i, a)| {
+            format!("agreement {} on statement {:?} at {}", i, self.stmt_idx(&a.message), a.at)
+        });
+        let actions = self.enacted.iter().enumerate().map(|(i, e)| {
+            let justification: BTreeSet<_> =
+                e.justification.iter().filter_map(|s| self.stmt_idx(s)).collect();
+            format!(
+                "action {} by {} based on {:?} at {} justified by {:?}",
+                i,
+                e.id.0,
+                self.stmt_idx(&e.basis.message),
+                e.basis.at,
+                justification
+            )
+        });
+        Snapshot {
+            current: self.current,
+            elements: statements.chain(agreements).chain(actions).collect(),
+        }
+    }
+}
+
+/// Prints how the state changed from the old to the new snapshot.
+pub fn print_diff(old: &Snapshot, new: &Snapshot) {
+    if old.current == new.current && old.elements == new.elements {
+        println!("No changes");
+        return;
+    }
+    if old.current != new.current {
+        println!("~ current time: {} -> {}", old.current, new.current);
+    }
+    for removed in old.elements.difference(&new.elements) {
+        println!("- {}", removed);
+    }
+    for added in new.elements.difference(&old.elements) {
+        println!("+ {}", added);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+
+    #[test]
+    fn describes_justifications_in_order() {
+        let config = config(&[
+            "say amy a.",
+            "say amy b.",
+            "say amy c.",
+            "say amy d.",
+            "agree 0 0",
+            "enact bob 0 3 1 0 2",
+        ]);
+        let snapshot = config.snapshot();
+        assert!(snapshot.elements.iter().any(|e| e.ends_with("justified by {0, 1, 2, 3}")));
+    }
+}
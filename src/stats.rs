@@ -0,0 +1,95 @@
+//! Summarizing the size and shape of a session, e.g., of a large scenario file.
+
+use crate::{render, Config, StmtIdx, Time};
+use std::collections::BTreeMap;
+
+/// What an agent contributed to the session.
+#[derive(Default)]
+struct AgentStats {
+    statements: usize,
+    /// Agreements on statements said by the agent.
+    agreements: usize,
+    actions: usize,
+}
+
+impl Config {
+    /// The earliest and latest times at which anything was said, agreed or enacted, if any.
+    fn time_span(&self) -> Option<(Time, Time)> {
+        let times = (self.stated_at.iter().copied())
+            .chain(self.agreements.iter().map(|a| a.at))
+            .chain(self.enacted_at.iter().copied());
+        times.fold(None, |span, t| match span {
+            None => Some((t, t)),
+            Some((min, max)) => Some((min.min(t), max.max(t))),
+        })
+    }
+
+    /// The statement with the longest payload, if any, preferring the earliest.
+    fn largest_payload(&self) -> Option<StmtIdx> {
+        (0..self.statements.len()).rev().max_by_key(|&i| self.statements[i].payload.len())
+    }
+
+    pub fn print_stats(&self) {
+        let mut agents: BTreeMap<&str, AgentStats> = BTreeMap::new();
+        for s in &self.statements {
+            agents.entry(&s.id.0).or_default().statements += 1;
+        }
+        for a in &self.agreements {
+            agents.entry(&a.message.id.0).or_default().agreements += 1;
+        }
+        for e in &self.enacted {
+            agents.entry(&e.id.0).or_default().actions += 1;
+        }
+        println!(
+            "{} statements ({} retracted), {} agreements, {} actions ({} withdrawn)",
+            self.statements.len(),
+            self.retracted.len(),
+            self.agreements.len(),
+            self.enacted.len(),
+            self.withdrawn.len()
+        );
+        if !agents.is_empty() {
+            println!("___agent___|___stmts___|____agr____|____act____ PER AGENT");
+            for (agent, stats) in &agents {
+                let row = format!(
+                    "{: >10} | {: >9} | {: >9} | {: >9}",
+                    agent, stats.statements, stats.agreements, stats.actions
+                );
+                println!("{}", render::agent(agent, &row));
+            }
+        }
+        if !self.enacted.is_empty() {
+            let total: usize = self.enacted.iter().map(|e| e.justification.iter().count()).sum();
+            println!(
+                "average justification: {:.2} statements",
+                total as f64 / self.enacted.len() as f64
+            );
+        }
+        match self.time_span() {
+            Some((from, to)) => println!("time span: {} to {} (now {})", from, to, self.current),
+            None => println!("time span: none (now {})", self.current),
+        }
+        if let Some(i) = self.largest_payload() {
+            let s = &self.statements[i];
+            println!(
+                "largest payload: statement {} by {}, of {} bytes",
+                i,
+                s.id.0,
+                s.payload.len()
+            );
+        }
+    }
+
+    pub fn print_memory_stats(&self) {
+        // Each statement owns a copy of its payload, as the messages of the prototype do
+        let stated: usize = self.statements.iter().map(|s| s.payload.len()).sum();
+        println!("statements: {} bytes of payloads", stated);
+        // Updates in the journal, undone, and in checkpoints and branches share payloads
+        println!(
+            "updates: {} distinct payloads of {} bytes, referred to {} times",
+            self.pool.len(),
+            self.pool.bytes(),
+            self.pool.references()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Payload templates, with named parameters that are substituted when saying them, e.g.,
+//! `template grant = "$grantor grants $grantee access to $resource"` followed by
+//! `say amy @grant grantor=amy grantee=bob resource=db1`.
+
+use crate::{
+    tokens::{self, Tokens},
+    Config, ParseError,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+};
+
+/// The parameters `$<name>` or `${<name>}` occurring in the body, with their byte ranges.
+fn params(body: &str) -> impl Iterator<Item = (Range<usize>, &str)> + '_ {
+    body.match_indices('$').filter_map(|(idx, _)| {
+        let rest = &body[idx + 1..];
+        if let Some(braced) = rest.strip_prefix('{') {
+            let len = braced.find('}')?;
+            return (len > 0).then(|| (idx..idx + 3 + len, &braced[..len]));
+        }
+        let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        (len > 0).then(|| (idx..idx + 1 + len, &rest[..len]))
+    })
+}
+
+/// Substitutes the arguments for the parameters of the body. Every parameter must be given
+/// an argument, and every argument must be a parameter.
+fn expand(body: &str, args: &HashMap<String, String>) -> Result<String, String> {
+    let mut unused: BTreeSet<&str> = args.keys().map(String::as_str).collect();
+    let mut expanded = String::new();
+    let mut end = 0;
+    for (range, name) in params(body) {
+        let arg = args.get(name).ok_or(format!("expected an argument {}=<value>", name))?;
+        unused.remove(name);
+        expanded.push_str(&body[end..range.start]);
+        expanded.push_str(arg);
+        end = range.end;
+    }
+    expanded.push_str(&body[end..]);
+    match unused.first() {
+        Some(name) => Err(format!("the template has no parameter ${}", name)),
+        None => Ok(expanded),
+    }
+}
+
+impl Config {
+    pub fn define_template(&mut self, name: String, body: String) {
+        self.templates.insert(name, body);
+    }
+
+    pub fn list_templates(&self) {
+        for (name, body) in &self.templates {
+            println!("{: <16} = {:?}", name, body);
+        }
+    }
+
+    /// Rewrites a `say` or `sayto` command, possibly bound with `let`, whose payload is
+    /// `@<template>`, followed by `<param>=<value>` arguments and an optional `as <label>`,
+    /// to say the expanded template instead.
+    pub fn expand_template<'a>(&self, command: &'a str) -> Result<Cow<'a, str>, ParseError> {
+        let mut tokens = Tokens::new(command);
+        let mut words = vec![];
+        let keyword = loop {
+            let Some(keyword) = tokens.next() else {
+                return Ok(Cow::Borrowed(command));
+            };
+            words.push(tokens::quote(&keyword).into_owned());
+            if keyword != "let" {
+                break keyword;
+            }
+            // The name and `=` of the binding
+            words.extend(tokens.by_ref().take(2).map(|t| tokens::quote(&t).into_owned()));
+        };
+        let preceding = match &*keyword {
+            "say" => 1,
+            "sayto" => 2,
+            _ => return Ok(Cow::Borrowed(command)),
+        };
+        words.extend(tokens.by_ref().take(preceding).map(|t| tokens::quote(&t).into_owned()));
+        if !tokens.rest().starts_with('@') {
+            return Ok(Cow::Borrowed(command));
+        }
+        let malformed =
+            |reason: String| ParseError::Malformed { keyword: keyword.to_string(), reason };
+        let name = tokens.next().ok_or_else(|| malformed("expected a template".into()))?;
+        let body = (self.templates.get(&name[1..]))
+            .ok_or_else(|| malformed(format!("unknown template {:?}", &name[1..])))?;
+        let (mut args, mut label) = (HashMap::new(), None);
+        while let Some(token) = tokens.next() {
+            if token == "as" {
+                label = Some(tokens.next().ok_or_else(|| malformed("expected a label".into()))?);
+                break;
+            }
+            let (param, arg) = token
+                .split_once('=')
+                .ok_or_else(|| malformed(format!("expected <param>=<value>, got {:?}", token)))?;
+            if args.insert(param.to_string(), arg.to_string()).is_some() {
+                return Err(malformed(format!("parameter {} is given twice", param)));
+            }
+        }
+        if !tokens.is_empty() {
+            return Err(malformed(format!("unexpected {:?}", tokens.rest())));
+        }
+        let payload = expand(body, &args).map_err(malformed)?;
+        words.push(tokens::quote(&payload).into_owned());
+        if let Some(label) = label {
+            words.extend(["as".to_string(), label.into_owned()]);
+        }
+        Ok(Cow::Owned(words.join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens;
+
+    fn args(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn expands_plain_and_braced_parameters() {
+        let expanded = expand("${say} to $bob, ${bob}s", &args(&[("say", "hi"), ("bob", "b")]));
+        assert_eq!(expanded.unwrap(), "hi to b, bs");
+    }
+
+    #[test]
+    fn rejects_missing_and_unused_arguments() {
+        assert!(expand("$a and $b", &args(&[("a", "1")])).is_err());
+        assert!(expand("$a", &args(&[("a", "1"), ("b", "2")])).is_err());
+    }
+
+    #[test]
+    fn keeps_templates_named_like_commands_whole() {
+        let quoted = tokens::commands(r#"template t = "$say to $bob" $ templates"#);
+        assert_eq!(quoted.len(), 2);
+        assert_eq!(quoted[0].1.trim(), r#"template t = "$say to $bob""#);
+        let braced = tokens::commands("template t = ${say} to $bob");
+        assert_eq!(braced.len(), 1);
+    }
+}
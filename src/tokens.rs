@@ -0,0 +1,347 @@
+use crate::help;
+use std::borrow::Cow;
+
+/// Splits command input into whitespace-separated tokens.
+/// Parts of a token may be wrapped in double quotes to include whitespace.
+/// Within quotes, `\"`, `\\`, `\n`, `\t` and `\r` are unescaped.
+pub struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { rest: input }
+    }
+
+    /// The untokenized input, with leading whitespace trimmed.
+    pub fn rest(&self) -> &'a str {
+        self.rest.trim_start()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    /// Consumes a trailing free-form argument, e.g., a payload. This is either a single
+    /// quoted token spanning the rest of the input, or the (non-empty) rest verbatim.
+    pub fn remainder(&mut self) -> Option<Cow<'a, str>> {
+        let rest = self.rest();
+        if rest.starts_with('"') {
+            let token = self.next()?;
+            self.is_empty().then_some(token)
+        } else if rest.is_empty() {
+            None
+        } else {
+            self.rest = "";
+            Some(Cow::Borrowed(rest.trim_end()))
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Cow<'a, str>;
+
+    /// Returns the next token, or `None` if the input is exhausted or malformed,
+    /// i.e., it has an unterminated quote or an unknown escape sequence.
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        let input = self.rest();
+        if input.is_empty() {
+            return None;
+        }
+        let mut owned: Option<String> = None;
+        let mut chars = input.char_indices();
+        let mut end = input.len();
+        while let Some((idx, c)) = chars.next() {
+            if c.is_whitespace() {
+                end = idx;
+                break;
+            } else if c == '"' {
+                let s = owned.get_or_insert_with(|| input[..idx].to_string());
+                loop {
+                    match chars.next()?.1 {
+                        '"' => break,
+                        '\\' => s.push(match chars.next()?.1 {
+                            '"' => '"',
+                            '\\' => '\\',
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            _ => return None,
+                        }),
+                        c => s.push(c),
+                    }
+                }
+            } else if let Some(s) = &mut owned {
+                s.push(c);
+            }
+        }
+        self.rest = &input[end..];
+        Some(match owned {
+            Some(s) => Cow::Owned(s),
+            None => Cow::Borrowed(&input[..end]),
+        })
+    }
+}
+
+/// Wraps the token in quotes if needed for it to be read back as a single token.
+pub fn quote(token: &str) -> Cow<'_, str> {
+    if !token.is_empty() && !token.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return Cow::Borrowed(token);
+    }
+    let mut quoted = String::from('"');
+    for c in token.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// The name of the variable that the `$` at the given byte index refers to, if any. Names
+/// of commands are not variables, such that `$say` is still a separator before `say`.
+pub fn variable_at(text: &str, idx: usize) -> Option<&str> {
+    let rest = &text[idx + 1..];
+    let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-');
+    let name = &rest[..end.unwrap_or(rest.len())];
+    let is_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_');
+    (is_name && !help::is_keyword(name)).then_some(name)
+}
+
+/// Whether the byte index is within double quotes, as the tokens are read.
+pub fn is_quoted(text: &str, idx: usize) -> bool {
+    let (mut chars, mut quoted) = (text[..idx].chars(), false);
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    quoted
+}
+
+/// The commands in the text, separated by the `;` that are not within quotes, e.g., in a
+/// payload, trimmed, and leaving out empty ones.
+pub fn split_commands(text: &str) -> impl Iterator<Item = &str> {
+    let ends = text.match_indices(';').map(|(idx, _)| idx).filter(|&idx| !is_quoted(text, idx));
+    let mut start = 0;
+    (ends.chain([text.len()]))
+        .map(move |end| {
+            let command = &text[start..end];
+            start = end + 1;
+            command.trim()
+        })
+        .filter(|command| !command.is_empty())
+}
+
+/// Whether the `$` at the given byte index separates commands, rather than referring to a
+/// macro parameter like `$1`, a template parameter like `${say}` or a variable like
+/// `$last_stmt`. A `$` within quotes, e.g., in a payload, separates nothing.
+fn is_separator(text: &str, idx: usize) -> bool {
+    !text[idx + 1..].starts_with(|c: char| c.is_ascii_digit() || c == '{')
+        && variable_at(text, idx).is_none()
+        && !is_quoted(text, idx)
+}
+
+/// The byte index of the last command separator in the text, if any. Comments and the
+/// bodies of heredocs contain no separators.
+pub fn last_separator(text: &str) -> Option<usize> {
+    let (lines, _) = lines(text);
+    (lines.into_iter())
+        .filter(|&(_, line, part)| part == Part::Text && !is_comment(line))
+        .flat_map(|(offset, line, _)| {
+            let separators = line.match_indices('$').filter(|&(idx, _)| is_separator(line, idx));
+            separators.map(move |(idx, _)| offset + idx)
+        })
+        .max()
+}
+
+/// How a line of a script is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    /// Commands, separated by `$`.
+    Text,
+    /// Part of the body of a heredoc, read verbatim.
+    Body,
+    /// The tag closing a heredoc.
+    Close,
+}
+
+/// Splits a line opening a heredoc, i.e., ending in `<<TAG`, into the text before it and
+/// the tag.
+fn heredoc(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_end();
+    let start = line.rfind("<<")?;
+    let tag = &line[start + 2..];
+    let is_tag = !tag.is_empty() && tag.chars().all(|c| c.is_alphanumeric() || c == '_');
+    is_tag.then(|| (&line[..start], tag))
+}
+
+/// The lines of the script, with their byte offsets and how they are read, and whether
+/// the script ends within the body of a heredoc.
+fn lines(script: &str) -> (Vec<(usize, &str, Part)>, bool) {
+    let (mut lines, mut offset, mut open) = (vec![], 0, None);
+    for line in script.split('\n') {
+        let part = match open {
+            Some(tag) if line.trim() == tag => {
+                open = None;
+                Part::Close
+            }
+            Some(_) => Part::Body,
+            None => {
+                if !is_comment(line) {
+                    open = heredoc(line).map(|(_, tag)| tag);
+                }
+                Part::Text
+            }
+        };
+        lines.push((offset, line, part));
+        offset += line.len() + 1;
+    }
+    (lines, open.is_some())
+}
+
+/// Whether the script ends within the body of a heredoc, such that more lines are needed.
+pub fn in_heredoc(script: &str) -> bool {
+    lines(script).1
+}
+
+/// Whether the line is a comment, i.e., starts with `#` after any whitespace.
+pub fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+/// Splits a script into its `$`-separated commands, each paired with the (1-based)
+/// line number on which it starts. Comment lines are ignored entirely, even if they
+/// contain `$`, and empty commands are skipped. A `$` directly followed by a digit, `{` or
+/// a variable name, or within quotes, is not a separator.
+///
+/// A line ending in `<<TAG` opens a heredoc, whose body is the lines up to one that is
+/// `TAG`, read verbatim as a single token, even if they contain `$` or `#`. A heredoc
+/// that is never closed ends with the script.
+pub fn commands(script: &str) -> Vec<(usize, String)> {
+    let mut commands = vec![];
+    let mut command = String::new();
+    let mut line = 1;
+    let mut finish = |command: &mut String, line: usize| {
+        let leading = &command[..command.len() - command.trim_start().len()];
+        let start = line + leading.matches('\n').count();
+        if !command.trim().is_empty() {
+            commands.push((start, command.trim().to_string()));
+        }
+        command.clear();
+    };
+    let (lines, _) = lines(script);
+    let mut body: Vec<&str> = vec![];
+    for (idx, &(_, text, part)) in lines.iter().enumerate() {
+        let text = match part {
+            Part::Body => {
+                body.push(text);
+                continue;
+            }
+            Part::Close => {
+                command.push_str(&quote(&std::mem::take(&mut body).join("\n")));
+                ""
+            }
+            // The body is quoted in place of the tag, once it is closed
+            Part::Text if is_comment(text) => text,
+            Part::Text => heredoc(text).map_or(text, |(before, _)| before),
+        };
+        if !is_comment(text) {
+            let mut from = 0;
+            for (sep, _) in text.match_indices('$').filter(|&(sep, _)| is_separator(text, sep)) {
+                command.push_str(&text[from..sep]);
+                finish(&mut command, line);
+                line = idx + 1;
+                from = sep + 1;
+            }
+            command.push_str(&text[from..]);
+        }
+        command.push('\n');
+    }
+    if !body.is_empty() {
+        command.push_str(&quote(&body.join("\n")));
+    }
+    finish(&mut command, line);
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<String> {
+        Tokens::new(input).map(Cow::into_owned).collect()
+    }
+
+    fn commands_of(script: &str) -> Vec<String> {
+        commands(script).into_iter().map(|(_, command)| command.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn splits_on_whitespace_and_unescapes_quotes() {
+        assert_eq!(tokens(r#"say "agent 1"  a"b\"c"d"#), ["say", "agent 1", r#"ab"cd"#]);
+        assert_eq!(tokens(r#""\n\t\\""#), ["\n\t\\"]);
+        assert!(tokens("  ").is_empty());
+    }
+
+    #[test]
+    fn splits_commands_on_unquoted_semicolons() {
+        let commands: Vec<&str> = split_commands(r#"say amy "a; \"b;\"" ;; say bob c;"#).collect();
+        assert_eq!(commands, [r#"say amy "a; \"b;\"""#, "say bob c"]);
+    }
+
+    #[test]
+    fn stops_at_malformed_quotes() {
+        assert_eq!(tokens(r#"say "open"#), ["say"]);
+        assert_eq!(tokens(r#"say "\q""#), ["say"]);
+    }
+
+    #[test]
+    fn quotes_tokens_to_read_back() {
+        for token in ["", "plain", "two words", "\"quoted\"", "back\\slash", "line\nbreak"] {
+            assert_eq!(tokens(&quote(token)), [token]);
+        }
+        assert!(matches!(quote("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn takes_the_remainder_verbatim_or_quoted() {
+        let mut quoted = Tokens::new(r#"say amy "a  b""#);
+        quoted.by_ref().take(2).for_each(drop);
+        assert_eq!(quoted.remainder().as_deref(), Some("a  b"));
+        let mut trailing = Tokens::new(r#"say amy "a" b"#);
+        trailing.by_ref().take(2).for_each(drop);
+        assert_eq!(trailing.remainder(), None);
+        let mut verbatim = Tokens::new("say amy p(a), q(b).  ");
+        verbatim.by_ref().take(2).for_each(drop);
+        assert_eq!(verbatim.remainder().as_deref(), Some("p(a), q(b)."));
+    }
+
+    #[test]
+    fn separates_commands_but_not_parameters_variables_or_quotes() {
+        assert_eq!(commands_of("say amy a. $ say bob b."), ["say amy a.", "say bob b."]);
+        assert_eq!(commands_of("macro m = say amy $1"), ["macro m = say amy $1"]);
+        assert_eq!(commands_of("agree $offer 3 $$ tick"), ["agree $offer 3", "tick"]);
+        assert_eq!(commands_of(r#"say amy "$ 5" $say bob b."#), [r#"say amy "$ 5""#, "say bob b."]);
+    }
+
+    #[test]
+    fn skips_comments_and_reads_heredocs_verbatim() {
+        let script = "# say amy $ no.\nsay amy <<END\n# kept $ too\nEND\n$ tick";
+        let commands = commands_of(script);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(tokens(&commands[0]), ["say", "amy", "# kept $ too"]);
+        assert_eq!(commands[1], "tick");
+        assert!(in_heredoc("say amy <<END\nopen"));
+        assert_eq!(last_separator("say amy a. $ say $last_stmt"), Some(11));
+    }
+}
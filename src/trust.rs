@@ -0,0 +1,41 @@
+//! Trust between agents. An agent that declares whom it trusts only accepts statements of
+//! those agents, and its own, as justification, while other agents accept any statement.
+
+use crate::Config;
+use std::collections::BTreeSet;
+
+impl Config {
+    /// Whether the truster accepts statements by the trustee.
+    pub fn trusts(&self, truster: &str, trustee: &str) -> bool {
+        truster == trustee
+            || self.trust.get(truster).is_none_or(|trusted| trusted.contains(trustee))
+    }
+
+    /// Prints which agents trust which, with a row per truster and a column per trustee.
+    pub fn show_trust(&self) {
+        let mut agents: BTreeSet<&str> = self.agents.keys().map(String::as_str).collect();
+        agents.extend(self.statements.iter().map(|s| s.id.0.as_str()));
+        agents.extend(self.enacted.iter().map(|e| e.id.0.as_str()));
+        for (truster, trusted) in &self.trust {
+            agents.insert(truster);
+            agents.extend(trusted.iter().map(String::as_str));
+        }
+        if agents.is_empty() {
+            println!("No agents");
+            return;
+        }
+        let columns: Vec<String> = agents.iter().map(|a| format!("{: ^9}", a)).collect();
+        println!("__trusts___|{}", columns.join("|"));
+        for truster in &agents {
+            let cells: Vec<String> = agents
+                .iter()
+                .map(|trustee| match (truster == trustee, self.trusts(truster, trustee)) {
+                    (true, _) => format!("{: ^9}", "-"),
+                    (false, true) => format!("{: ^9}", "yes"),
+                    (false, false) => format!("{: ^9}", ""),
+                })
+                .collect();
+            println!("{: >10} |{}", truster, cells.join("|"));
+        }
+    }
+}
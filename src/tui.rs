@@ -0,0 +1,145 @@
+//! A full-screen interface, with panes showing the statements, agreements and actions as
+//! they change, the output of the latest commands, and a line to enter commands on.
+
+use crate::{query::Section, render, tokens, Config, Flow};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::io::{Read, Write};
+
+/// The most lines of command output that are kept.
+const OUTPUT_LINES: usize = 200;
+
+#[derive(Default)]
+struct Tui {
+    input: String,
+    /// Output of the latest commands, oldest first.
+    output: Vec<String>,
+}
+
+impl Config {
+    /// Runs the full-screen interface until `quit` or escape, restoring the terminal after.
+    pub fn tui(&mut self) -> std::io::Result<()> {
+        // Styling is up to the panes, rather than escape codes in their rows
+        render::set_plain(true);
+        let mut terminal = ratatui::init();
+        let result = Tui::default().run(self, &mut terminal);
+        ratatui::restore();
+        render::set_plain(false);
+        result
+    }
+
+    /// The rows of a section as they are shown, each colored as its agent if any.
+    fn pane_items(&self, section: Section) -> Vec<ListItem<'static>> {
+        let len = match section {
+            Section::Statements => self.statements.len(),
+            Section::Agreements => self.agreements.len(),
+            Section::Actions => self.enacted.len(),
+        };
+        let indices: Vec<usize> = (0..len).collect();
+        // The header is left to the title of the pane
+        let rows = self.rows(section, &indices).into_iter().skip(1);
+        (indices.into_iter().zip(rows))
+            .map(|(i, row)| {
+                let agent = match section {
+                    Section::Statements => Some(&self.statements[i].id.0),
+                    Section::Agreements => None,
+                    Section::Actions => Some(&self.enacted[i].id.0),
+                };
+                let style = agent.map_or(Style::default(), |agent| {
+                    // The ANSI colors 31 to 36 are the indexed colors 1 to 6
+                    Style::default().fg(Color::Indexed(render::agent_color(agent) - 30))
+                });
+                ListItem::new(Line::from(row)).style(style)
+            })
+            .collect()
+    }
+}
+
+impl Tui {
+    fn run(&mut self, config: &mut Config, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(config, frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Enter => {
+                    let line = std::mem::take(&mut self.input);
+                    if let Flow::Quit = self.enter(config, &line)? {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs the commands on the line, collecting what they print as output.
+    fn enter(&mut self, config: &mut Config, line: &str) -> std::io::Result<Flow> {
+        self.output.push(format!("> {}", line));
+        let mut captured = gag::BufferRedirect::stdout()?;
+        let mut flow = Flow::Continue;
+        for (_, command) in tokens::commands(line) {
+            if let Flow::Quit = config.run(&command) {
+                flow = Flow::Quit;
+                break;
+            }
+        }
+        std::io::stdout().flush()?;
+        let mut text = String::new();
+        captured.read_to_string(&mut text)?;
+        drop(captured);
+        self.output.extend(text.lines().map(str::to_string));
+        let excess = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.output.drain(..excess);
+        Ok(flow)
+    }
+
+    fn draw(&self, config: &Config, frame: &mut Frame) {
+        let [panes, output, input] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(10), Constraint::Length(3)])
+                .areas(frame.area());
+        let [statements, agreements, actions] =
+            Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(panes);
+        let title = format!("statements at t={}", config.current);
+        draw_pane(frame, statements, &title, config.pane_items(Section::Statements));
+        draw_pane(frame, agreements, "agreements", config.pane_items(Section::Agreements));
+        draw_pane(frame, actions, "actions", config.pane_items(Section::Actions));
+
+        // The latest output that fits, within the borders
+        let fits = output.height.saturating_sub(2) as usize;
+        let latest = &self.output[self.output.len().saturating_sub(fits)..];
+        let lines: Vec<Line> = latest.iter().map(|l| Line::from(l.as_str())).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title("output")), output);
+
+        let prompt = Paragraph::new(format!("> {}", self.input))
+            .block(Block::bordered().title("command (esc to leave)"));
+        frame.render_widget(prompt, input);
+        let x = input.x + 3 + self.input.chars().count() as u16;
+        frame.set_cursor_position((x.min(input.right().saturating_sub(2)), input.y + 1));
+    }
+}
+
+/// Draws the latest items that fit in the pane, within its borders.
+fn draw_pane(frame: &mut Frame, area: Rect, title: &str, mut items: Vec<ListItem<'static>>) {
+    let fits = area.height.saturating_sub(2) as usize;
+    items.drain(..items.len().saturating_sub(fits));
+    frame.render_widget(List::new(items).block(Block::bordered().title(title.to_string())), area);
+}
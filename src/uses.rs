@@ -0,0 +1,24 @@
+//! Looking up what depends on a statement, e.g., before retracting or amending it.
+
+use crate::{query::Section, AgreeIdx, Config, StmtIdx};
+
+impl Config {
+    /// The agreements on the statement, and the enacted actions based on or justified by it.
+    pub fn uses(&self, idx: StmtIdx) -> (Vec<AgreeIdx>, Vec<usize>) {
+        let msg = &self.statements[idx];
+        (self.agreements_on(msg).to_vec(), self.actions_using(msg).to_vec())
+    }
+
+    pub fn print_uses(&self, idx: StmtIdx) -> Result<(), String> {
+        if idx >= self.statements.len() {
+            return Err(format!("Cannot look up unsaid message {}", idx));
+        }
+        let (agreements, actions) = self.uses(idx);
+        if agreements.is_empty() && actions.is_empty() {
+            println!("Statement {} is not used by any agreement or action", idx);
+        }
+        self.print_rows(Section::Agreements, &agreements);
+        self.print_rows(Section::Actions, &actions);
+        Ok(())
+    }
+}
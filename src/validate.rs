@@ -0,0 +1,79 @@
+//! Validating updates before they are applied, on every path that applies them, e.g., the
+//! REPL, scripts, imports, the engine and the RPC and HTTP servers. Invalid updates are
+//! refused in strict mode, while the REPL warns about them otherwise. Actions that the policy
+//! engine does not license are refused when enforcing, and found by audit otherwise.
+//!
+//! Replaying the journal does not validate its updates anew, as they were when applied.
+
+use crate::{AgreeIdx, Config, UpdateCmd};
+
+impl Config {
+    /// The schema that the payload of the statement does not match, and why, if any.
+    pub(crate) fn schema_mismatch(&self, update_cmd: &UpdateCmd) -> Option<(String, String)> {
+        match (update_cmd, &self.schema) {
+            (UpdateCmd::Say { payload, .. }, Some(schema)) => {
+                schema.validate(payload).err().map(|e| (schema.name().to_string(), e))
+            }
+            _ => None,
+        }
+    }
+
+    /// The basis of the action, if it is an agreement that is not current, and why.
+    pub(crate) fn stale_basis(&self, update_cmd: &UpdateCmd) -> Option<(AgreeIdx, String)> {
+        match update_cmd {
+            UpdateCmd::Enact { basis, .. } => (basis.resolve(&self.agree_labels).ok())
+                .filter(|&basis| basis < self.agreements.len())
+                .and_then(|basis| self.not_current(basis).map(|why| (basis, why))),
+            _ => None,
+        }
+    }
+
+    /// Refuses the update if the current settings forbid it.
+    pub(crate) fn validate(&mut self, update_cmd: &UpdateCmd) -> Result<(), String> {
+        if let Some((schema, e)) = self.schema_mismatch(update_cmd)
+            && self.strict
+        {
+            return Err(format!("Payload does not match schema {}: {}", schema, e));
+        }
+        if let Some((basis, why)) = self.stale_basis(update_cmd)
+            && self.strict
+        {
+            return Err(format!("Basis agreement {} is not current: {}", basis, why));
+        }
+        if let UpdateCmd::Enact { .. } = update_cmd
+            && self.enforce
+        {
+            let name = (self.policy.as_ref().map(|policy| policy.name()))
+                .ok_or("No policy engine is set to license the action, see set policy")?;
+            if let Some(why) = self.enactment_violation(update_cmd)? {
+                return Err(format!("{} does not license the action: {}", name, why));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+
+    #[test]
+    fn validates_redone_updates_anew() {
+        let mut config = config(&[
+            "agent add amy",
+            "set strict on",
+            "say amy ok.",
+            "undo",
+            "set schema json",
+            "redo",
+        ]);
+        assert!(config.statements.is_empty());
+        assert!(config.undone.is_empty());
+
+        let cmd = config.parse("set schema none").unwrap();
+        config.execute(cmd).unwrap();
+        let cmd = config.parse("say amy ok.").unwrap();
+        config.execute(cmd).unwrap();
+        assert_eq!(config.statements.len(), 1);
+    }
+}
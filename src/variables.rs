@@ -0,0 +1,119 @@
+//! Variables bound to the indices of created statements, agreements and actions, such that
+//! scripts can refer to them without hardcoding their positions.
+
+use crate::{tokens, tokens::Tokens, Config, UpdateCmd};
+use std::borrow::Cow;
+
+impl Config {
+    /// Replaces each `$<name>` referring to a bound variable by its index. References to
+    /// unbound variables are left as they are, as are those within quotes, e.g., in payloads,
+    /// and in the bodies of macro and template definitions, which are substituted when used.
+    pub fn substitute<'a>(&self, command: &'a str) -> Cow<'a, str> {
+        if matches!(Tokens::new(command).next().as_deref(), Some("macro" | "template")) {
+            return Cow::Borrowed(command);
+        }
+        let mut substituted = String::new();
+        let mut end = 0;
+        for (idx, _) in command.match_indices('$') {
+            let Some(name) = tokens::variable_at(command, idx) else { continue };
+            if tokens::is_quoted(command, idx) {
+                continue;
+            }
+            if let Some(value) = self.variables.get(name) {
+                substituted.push_str(&command[end..idx]);
+                substituted.push_str(&value.to_string());
+                end = idx + 1 + name.len();
+            }
+        }
+        if end == 0 {
+            return Cow::Borrowed(command);
+        }
+        substituted.push_str(&command[end..]);
+        Cow::Owned(substituted)
+    }
+
+    /// Applies the update and, if it creates a statement, agreement or action, binds its
+    /// index to `$last_stmt`, `$last_agreement` or `$last_action`, and the given variable.
+    pub fn update_and_bind(
+        &mut self,
+        update_cmd: UpdateCmd,
+        name: Option<String>,
+    ) -> Result<(), String> {
+        let duplicated = match &update_cmd {
+            UpdateCmd::Say { sayer, payload, .. } => self.duplicated(sayer, payload),
+            _ => None,
+        };
+        // Invalid payloads are rejected by validation in strict mode, and flagged otherwise
+        let invalid = self.schema_mismatch(&update_cmd);
+        // Actions based on agreements that are not current are rejected by validation in
+        // strict mode, and flagged otherwise
+        let stale = self.stale_basis(&update_cmd);
+        let last = match update_cmd {
+            UpdateCmd::Say { .. } => Some(("last_stmt", self.statements.len())),
+            UpdateCmd::Agree { .. } | UpdateCmd::Amend { .. } => {
+                Some(("last_agreement", self.agreements.len()))
+            }
+            UpdateCmd::Enact { .. } => Some(("last_action", self.enacted.len())),
+            _ => None,
+        };
+        self.try_update(update_cmd)?;
+        if let Some((schema, e)) = invalid {
+            println!(
+                "Warning: statement {} does not match schema {}: {}",
+                self.statements.len() - 1,
+                schema,
+                e
+            );
+        }
+        if let Some((basis, why)) = stale {
+            println!(
+                "Warning: action {} is based on agreement {}, which is not current: {}",
+                self.enacted.len() - 1,
+                basis,
+                why
+            );
+        }
+        if let Some(original) = duplicated {
+            println!(
+                "Warning: statement {} duplicates statement {}",
+                self.statements.len() - 1,
+                original
+            );
+        }
+        if let Some((last, idx)) = last {
+            for name in std::iter::once(last.to_string()).chain(name) {
+                println!("${} = {}", name, idx);
+                self.variables.insert(name, idx);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn list_variables(&self) {
+        for (name, idx) in &self.variables {
+            println!("${: <15} = {}", name, idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config;
+
+    #[test]
+    fn substitutes_neither_quotes_nor_definitions() {
+        let config = config(&[
+            "say amy a.",
+            "say amy \"refers to $last_stmt\"",
+            "macro approve = agree $last_stmt 0",
+            "let resource = say amy b.",
+            "template grant = \"$grantee may use $resource.\"",
+            "approve",
+            "say bob @grant grantee=cho resource=db1",
+        ]);
+        assert_eq!(&*config.statements[1].payload, "refers to $last_stmt");
+        assert_eq!(config.macros["approve"], "agree $last_stmt 0");
+        assert_eq!(config.agreements[0].message.id, config.statements[2].id);
+        assert_eq!(&*config.statements[3].payload, "cho may use db1.");
+    }
+}
@@ -0,0 +1,194 @@
+//! Verifying the signatures of the statements in a trace, such that third parties can
+//! audit shared traces.
+//!
+//! A signature only shows who is accountable if its key is known to be the sayer's, by the
+//! session or a file of trusted keys. The keys of other sayers are taken from the trace,
+//! pinning the first one seen per sayer, which catches keys that change midway, but not a
+//! trace re-signed with other keys throughout.
+
+use crate::{
+    dump, json,
+    signing::{self, SignatureRecord},
+    Config, UpdateCmd,
+};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use justact_prototype::{
+    auditing::{Event, EventControl},
+    wire::Message,
+};
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// The public keys of agents, by their names.
+pub type KnownKeys = BTreeMap<String, BTreeSet<[u8; 32]>>;
+
+/// Reads trusted public keys from a file with a line `<agent> <public key>` per key, in hex.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn read_keys(path: &Path) -> Result<KnownKeys, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read {:?}: {}", path, e))?;
+    let mut keys = KnownKeys::new();
+    for (i, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (agent, key) = (line.split_once(char::is_whitespace))
+            .ok_or(format!("{:?}:{}: expected an agent and a public key", path, i))?;
+        let key = (signing::parse_public_key(key.trim()))
+            .ok_or(format!("{:?}:{}: malformed public key", path, i))?;
+        keys.entry(agent.to_string()).or_default().insert(key);
+    }
+    Ok(keys)
+}
+
+/// The outcome of verifying a trace or the session.
+#[derive(Debug, Default)]
+pub struct Verification {
+    pub verified: usize,
+    pub unsigned: usize,
+    /// The number of verified statements whose keys were taken from the trace only.
+    pub from_trace: usize,
+    /// Descriptions of the forged, tampered or malformed entries.
+    pub failures: Vec<String>,
+    /// The first key seen in the trace per sayer whose keys are not known otherwise.
+    pinned: BTreeMap<String, [u8; 32]>,
+}
+
+impl Verification {
+    /// Verifies the signature of the message, if any, by the keys known for its sayer, or
+    /// the key it was pinned to otherwise, unless only known keys are trusted.
+    fn check(
+        &mut self,
+        what: &str,
+        msg: &Message,
+        record: Option<SignatureRecord>,
+        known: &KnownKeys,
+        known_only: bool,
+    ) {
+        let Some(record) = record else {
+            self.unsigned += 1;
+            return;
+        };
+        let key = signing::from_hex(&record.key).and_then(|b| <[u8; 32]>::try_from(b).ok());
+        let sig = signing::from_hex(&record.sig).and_then(|b| <[u8; 64]>::try_from(b).ok());
+        let (Some(key), Some(sig)) = (key, sig) else {
+            self.failures.push(format!("{}: malformed signature", what));
+            return;
+        };
+        let sayer = &msg.id.0;
+        let from_trace = match known.get(sayer) {
+            // Signed with some key, but not one that the sayer is known to have
+            Some(keys) if !keys.contains(&key) => {
+                self.failures
+                    .push(format!("{}: signed with a key that {} does not have", what, sayer));
+                return;
+            }
+            Some(_) => false,
+            None if known_only => {
+                self.failures
+                    .push(format!("{}: signed with a key not trusted for {}", what, sayer));
+                return;
+            }
+            None => match self.pinned.entry(sayer.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(key);
+                    true
+                }
+                Entry::Occupied(entry) if *entry.get() == key => true,
+                Entry::Occupied(_) => {
+                    self.failures.push(format!(
+                        "{}: signed with another key than {} signed with first",
+                        what, sayer
+                    ));
+                    return;
+                }
+            },
+        };
+        let verified = VerifyingKey::from_bytes(&key)
+            .and_then(|key| key.verify(&signing::signed_bytes(msg), &Signature::from_bytes(&sig)));
+        match verified {
+            Ok(()) => {
+                self.verified += 1;
+                self.from_trace += from_trace as usize;
+            }
+            Err(_) => self.failures.push(format!("{}: forged or tampered with", what)),
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "{} signed statements verified, {} unsigned, {} failed",
+            self.verified,
+            self.unsigned,
+            self.failures.len()
+        );
+        if self.from_trace > 0 {
+            println!(
+                "Warning: {} verified by keys taken from the trace only, which --keys trusts \
+                 instead",
+                self.from_trace
+            );
+        }
+        for failure in &self.failures {
+            println!("{}", failure);
+        }
+    }
+}
+
+impl Config {
+    /// The public keys that agents were given in this session, including replaced ones.
+    fn known_keys(&self) -> KnownKeys {
+        let mut known = KnownKeys::new();
+        for update_cmd in &self.journal {
+            if let UpdateCmd::AddKey { agent, public } = update_cmd
+                && let Some(key) = signing::parse_public_key(public)
+            {
+                known.entry(agent.clone()).or_default().insert(key);
+            }
+        }
+        known
+    }
+
+    /// Verifies the signatures of the statements of the session.
+    pub fn verify_session(&self) -> Verification {
+        let (known, mut verification) = (self.known_keys(), Verification::default());
+        for (i, msg) in self.statements.iter().enumerate() {
+            let what = format!("statement {}", i);
+            verification.check(&what, msg, self.signature_record(i), &known, false);
+        }
+        verification
+    }
+
+    /// Verifies the signatures in a trace as written by `dump`, as JSON lines or a JSON
+    /// array. Keys that the agents of this session were given, or that are trusted, are the
+    /// only ones accepted for them. The keys of other agents are rejected if any keys are
+    /// trusted, and are taken from the trace otherwise.
+    pub fn verify_trace(
+        &self,
+        text: &str,
+        trusted: Option<&KnownKeys>,
+    ) -> Result<Verification, String> {
+        let records = dump::read_records(text)?;
+        let (mut known, mut verification) = (self.known_keys(), Verification::default());
+        for (agent, keys) in trusted.into_iter().flatten() {
+            known.entry(agent.clone()).or_default().extend(keys);
+        }
+        for (i, mut record) in records.into_iter().filter(|(_, r)| !dump::is_preamble(r)) {
+            let signature = dump::strip_annotations(&mut record).signature;
+            json::destructure(&mut record);
+            let event: Event =
+                serde_json::from_value(record).map_err(|e| format!("entry {}: {}", i, e))?;
+            let Event::Control(EventControl::StateMessage { msg, .. }) = event else {
+                continue;
+            };
+            let signature: Option<SignatureRecord> = match signature {
+                Some(signature) => serde_json::from_value(signature)
+                    .map_err(|e| format!("entry {}: malformed signature: {}", i, e))?,
+                None => None,
+            };
+            let what = format!("entry {} ({} {})", i, msg.id.0, msg.id.1);
+            verification.check(&what, &msg, signature, &known, trusted.is_some());
+        }
+        Ok(verification)
+    }
+}
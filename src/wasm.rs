@@ -0,0 +1,38 @@
+//! A JavaScript-facing API for running the engine in the browser, built with
+//! `cargo build --lib --target wasm32-unknown-unknown` and `wasm-bindgen`.
+
+use crate::Engine;
+use wasm_bindgen::prelude::*;
+
+/// A session, to which updates are applied one command at a time.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Session {
+    engine: Engine,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a single update command in the REPL grammar, e.g., `say amy "ok."`,
+    /// throwing if it cannot be parsed or applied.
+    pub fn apply(&mut self, command: &str) -> Result<(), JsError> {
+        self.engine.apply_line(command).map(|_| ()).map_err(JsError::from)
+    }
+
+    /// The state as JSON, in the same form as reported by the `state` method of `--rpc`.
+    pub fn state(&self) -> String {
+        serde_json::to_string(&self.engine.state()).expect("state is serializable")
+    }
+
+    /// The trace of the state as control events, as written by `dump --format upstream`.
+    pub fn dump(&self) -> String {
+        let mut trace = vec![];
+        self.engine.write_trace(&mut trace).expect("writing to memory does not fail");
+        String::from_utf8(trace).expect("trace is UTF-8")
+    }
+}
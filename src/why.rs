@@ -0,0 +1,80 @@
+//! Explaining why an enacted action is (in)valid, as a tree of what it depends on.
+
+use crate::{render, Config};
+
+impl Config {
+    /// Prints the basis agreement and justification statements of the action, and the facts
+    /// that the policy engine, if any, derives from the justification.
+    pub fn why(&self, idx: usize) -> Result<(), String> {
+        let action =
+            self.enacted.get(idx).ok_or(format!("Cannot explain unenacted action {}", idx))?;
+        let verdict = match self.violation(idx) {
+            None => "valid".to_string(),
+            Some(violation) => format!("INVALID: {}", violation),
+        };
+        println!(
+            "action {} by {} at {}: {}",
+            idx,
+            render::agent(&action.id.0, &action.id.0),
+            self.enacted_at[idx],
+            verdict
+        );
+        let basis = format!("agreement {}", self.basis_idx(idx));
+        let on = match self.stmt_idx(&action.basis.message) {
+            Some(i) => format!("statement {}", i),
+            None => "an unknown statement".to_string(),
+        };
+        println!("├── basis: {} at {}, on {}", basis, action.basis.at, on);
+        let policy = self.policy.as_ref();
+        let last = if policy.is_some() { "├──" } else { "└──" };
+        println!("{} justification:", last);
+        let indent = if policy.is_some() { "│   " } else { "    " };
+        let mut justification: Vec<_> =
+            action.justification.iter().map(|m| (self.stmt_idx(m), m)).collect();
+        justification.sort_by_key(|(i, _)| *i);
+        for (n, (i, m)) in justification.iter().enumerate() {
+            let branch = if n + 1 == justification.len() { "└──" } else { "├──" };
+            let mut notes = vec![];
+            if m.id == action.basis.message.id {
+                notes.push("the basis");
+            }
+            if i.is_some_and(|i| self.retracted.contains(&i)) {
+                notes.push("retracted");
+            }
+            if !self.trusts(&action.id.0, &m.id.0) && m.id != action.basis.message.id {
+                notes.push("untrusted");
+            }
+            let notes =
+                if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+            let i = i.map_or("?".to_string(), |i| i.to_string());
+            println!(
+                "{}{} statement {} by {}: {:?}{}",
+                indent,
+                branch,
+                i,
+                render::agent(&m.id.0, &m.id.0),
+                m.payload,
+                notes
+            );
+        }
+        let Some(policy) = policy else {
+            return Ok(());
+        };
+        let payloads: Vec<&str> = action.justification.iter().map(|m| &*m.payload).collect();
+        match policy.derive(&payloads) {
+            None => println!("└── {} does not report derived facts", policy.name()),
+            Some(Err(e)) => println!("└── {} cannot evaluate justification: {}", policy.name(), e),
+            Some(Ok(facts)) if facts.is_empty() => {
+                println!("└── {} derives nothing", policy.name())
+            }
+            Some(Ok(facts)) => {
+                println!("└── derived by {}:", policy.name());
+                for (n, fact) in facts.iter().enumerate() {
+                    let branch = if n + 1 == facts.len() { "└──" } else { "├──" };
+                    println!("    {} {}", branch, fact);
+                }
+            }
+        }
+        Ok(())
+    }
+}